@@ -28,6 +28,10 @@ struct MyVertexOutput {
     @location(2) uv: vec2<f32>,
     // TODO: Can we make this smaller than a u32?
     @location(3) @interpolate(flat) atlas_index: u32,
+    // 0 in fully occluded corners, 1 out in the open
+    @location(4) ao: f32,
+    // How bright the face is from sky and block light, 1 at full light
+    @location(5) light: f32,
 }
 
 @vertex
@@ -41,7 +45,8 @@ fn vertex(vertex: Vertex) -> MyVertexOutput {
     // 3x5 bits = 0-31 for each position component
     // 3 bits for each axis of normal, 1 bit for negative.
     // 2x5 bits = 0-31 for quad size to determine UV
-    // 4 bytes for U32 to represent atlas index
+    // Second U32: 2 bits of ambient occlusion, 4 bits of sky light, 4 bits
+    // of block light, then 22 bits of atlas index
     var hack = vertex.hack_vert.x;
     var in_pos_x = hack >> 26u;
     var in_pos_y = (hack << 6u) >> 26u;
@@ -83,7 +88,12 @@ fn vertex(vertex: Vertex) -> MyVertexOutput {
         get_instance_index(vertex.instance_index)
     );
     out.uv = uv;
-    out.atlas_index = vertex.hack_vert.y;
+    out.atlas_index = vertex.hack_vert.y & 0x3fffffu;
+    out.ao = f32(vertex.hack_vert.y >> 30u) / 3.0;
+    var sky_light = (vertex.hack_vert.y >> 26u) & 0xfu;
+    var block_light = (vertex.hack_vert.y >> 22u) & 0xfu;
+    // Each level is a fifth dimmer than the one above it
+    out.light = pow(0.8, f32(15u - max(sky_light, block_light)));
 
     return out;
 }
@@ -104,12 +114,20 @@ fn fragment(
     // Generate a PbrInput struct from the StandardMaterial bindings
     var pbr_input = pbr_input_from_standard_material(pbr_vert_out, is_front);
 
-    // Alpha discard
+    pbr_input.material.base_color *= textureSampleBias(pbr_bindings::base_color_texture, pbr_bindings::base_color_sampler, uv, view.mip_bias);
+
+    // Alpha discard, after sampling so cutout voxels get their holes
     pbr_input.material.base_color = alpha_discard(
         pbr_input.material,
         pbr_input.material.base_color
     );
-    pbr_input.material.base_color *= textureSampleBias(pbr_bindings::base_color_texture, pbr_bindings::base_color_sampler, uv, view.mip_bias);
+
+    // Darken the corners, never all the way so the shape of dark places is
+    // still visible. Then dim everything the sky and torches don't reach.
+    pbr_input.material.base_color = vec4<f32>(
+        pbr_input.material.base_color.rgb * mix(0.35, 1.0, input.ao) * input.light,
+        pbr_input.material.base_color.a
+    );
 
     var out: FragmentOutput;
 
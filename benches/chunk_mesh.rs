@@ -0,0 +1,61 @@
+//! Compares the bitmask mesher against the original slice by slice one.
+//!
+//! ```text
+//! cargo bench --bench chunk_mesh
+//! ```
+
+use bevy::prelude::*;
+use cjs_whole_new_world::voxel::{
+    generate_chunk_with, generate_mesh, generate_mesh_by_slices, world_noise::WorldNoiseSettings,
+    BiomeTable, BiomeTableFile, Chunk, ChunkPos, InChunkPos, MeshNeighbors, VoxelRegistry,
+    WorldGenConfig, WorldGenerator, CHUNK_WIDTH,
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use itertools::iproduct;
+
+/// The chunk at the surface in the middle of a world, the kind of chunk
+/// that gets meshed the most.
+fn surface_chunk(registry: &VoxelRegistry) -> Chunk {
+    let biome_file: BiomeTableFile =
+        ron::de::from_str(include_str!("../assets/data/overworld.biomes.ron")).unwrap();
+    let biome_table = BiomeTable::new(biome_file.biomes).unwrap();
+    let generator = WorldNoiseSettings::new(0, &WorldGenConfig::original(), biome_table, registry);
+    let pos = ChunkPos(IVec3::ZERO);
+    let column = generator.column_noise(pos.0.xz());
+    generate_chunk_with(&generator, pos, column.as_ref(), registry).0
+}
+
+/// The worst case, where no two faces can be merged.
+fn checkerboard_chunk(registry: &VoxelRegistry) -> Chunk {
+    let stone = registry.by_name("stone").unwrap();
+    let mut chunk = Chunk::default();
+    for (x, y, z) in iproduct!(0..CHUNK_WIDTH, 0..CHUNK_WIDTH, 0..CHUNK_WIDTH) {
+        if (x + y + z) % 2 == 0 {
+            chunk.set(InChunkPos::new(UVec3::new(x, y, z)).unwrap(), stone);
+        }
+    }
+    chunk.update_edge_slice_bits(registry);
+    chunk
+}
+
+fn bench_meshers(c: &mut Criterion) {
+    let registry = VoxelRegistry::builtin();
+    let chunks = [
+        ("surface", surface_chunk(&registry)),
+        ("checkerboard", checkerboard_chunk(&registry)),
+    ];
+
+    let mut group = c.benchmark_group("generate_mesh");
+    for (name, chunk) in &chunks {
+        group.bench_with_input(BenchmarkId::new("bitmasks", name), chunk, |b, chunk| {
+            b.iter(|| generate_mesh(chunk, MeshNeighbors::default(), &registry))
+        });
+        group.bench_with_input(BenchmarkId::new("slices", name), chunk, |b, chunk| {
+            b.iter(|| generate_mesh_by_slices(chunk, MeshNeighbors::default(), &registry))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_meshers);
+criterion_main!(benches);
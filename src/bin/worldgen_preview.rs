@@ -0,0 +1,281 @@
+//! Renders the noise generator's 2D noise for a rectangle of chunk columns to
+//! PNGs, without opening a window. Handy for tuning worldgen configs, and the
+//! output is deterministic so it can be diffed.
+//!
+//! ```text
+//! cargo run --bin worldgen_preview -- --seed 1234 --rect -8,-8,8,8
+//! ```
+
+use bevy::prelude::*;
+use cjs_whole_new_world::voxel::{
+    world_noise::{Chunk2dNoiseValues, WorldNoiseSettings},
+    BiomeTable, BiomeTableFile, VoxelRegistry, VoxelRegistryFile, WorldGenConfig, CHUNK_WIDTH,
+};
+use image::{Rgb, RgbImage};
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+const USAGE: &str = "\
+Usage: worldgen_preview [options]
+
+Options:
+    --seed <u32>              World seed, as saved in world.ron (default 0)
+    --config <path>           Worldgen config (default assets/data/default.worldgen.ron)
+    --biomes <path>           Biome table (default assets/data/overworld.biomes.ron)
+    --registry <path>         Voxel registry (default assets/data/voxels.registry.ron)
+    --rect <x0,z0,x1,z1>      Chunk columns to render, inclusive (default -4,-4,4,4)
+    --out <dir>               Where to write the images (default worldgen_preview)
+    -h, --help                Show this message";
+
+struct Args {
+    seed: u32,
+    config: PathBuf,
+    biomes: PathBuf,
+    registry: PathBuf,
+    min: IVec2,
+    max: IVec2,
+    out: PathBuf,
+}
+
+impl Args {
+    /// `Ok(None)` means help was asked for.
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Option<Self>, String> {
+        let mut parsed = Self {
+            seed: 0,
+            config: "assets/data/default.worldgen.ron".into(),
+            biomes: "assets/data/overworld.biomes.ron".into(),
+            registry: "assets/data/voxels.registry.ron".into(),
+            min: IVec2::splat(-4),
+            max: IVec2::splat(4),
+            out: "worldgen_preview".into(),
+        };
+
+        while let Some(arg) = args.next() {
+            if arg == "-h" || arg == "--help" {
+                return Ok(None);
+            }
+            let value = args
+                .next()
+                .ok_or_else(|| format!("missing value for {arg}"))?;
+            match arg.as_str() {
+                "--seed" => {
+                    parsed.seed = value
+                        .parse()
+                        .map_err(|_| format!("invalid seed \"{value}\""))?
+                }
+                "--config" => parsed.config = value.into(),
+                "--biomes" => parsed.biomes = value.into(),
+                "--registry" => parsed.registry = value.into(),
+                "--rect" => {
+                    let coords = value
+                        .split(',')
+                        .map(|coord| coord.trim().parse::<i32>())
+                        .collect::<Result<Vec<_>, _>>()
+                        .map_err(|_| format!("invalid rect \"{value}\""))?;
+                    let [x0, z0, x1, z1] = coords[..] else {
+                        return Err(format!("rect needs four numbers, got \"{value}\""));
+                    };
+                    parsed.min = IVec2::new(x0, z0).min(IVec2::new(x1, z1));
+                    parsed.max = IVec2::new(x0, z0).max(IVec2::new(x1, z1));
+                }
+                "--out" => parsed.out = value.into(),
+                _ => return Err(format!("unknown option {arg}")),
+            }
+        }
+
+        Ok(Some(parsed))
+    }
+}
+
+fn main() -> ExitCode {
+    let args = match Args::parse(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("{err}\n\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match run(&args) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    let config: WorldGenConfig = ron::de::from_bytes(&read(&args.config)?)?;
+    let biome_file: BiomeTableFile = ron::de::from_bytes(&read(&args.biomes)?)?;
+    let biome_table = BiomeTable::new(biome_file.biomes)?;
+    let registry_file: VoxelRegistryFile = ron::de::from_bytes(&read(&args.registry)?)?;
+    let registry = VoxelRegistry::new(registry_file.voxels)?;
+
+    let noise = WorldNoiseSettings::new(args.seed, &config, biome_table.clone(), &registry);
+    let preview = Preview::generate(&noise, args.min, args.max);
+
+    fs::create_dir_all(&args.out)?;
+    let images = [
+        ("heightmap.png", preview.heightmap(config.sea_level)),
+        (
+            "temperature.png",
+            preview.climate(|n| &n.temperature, TEMPERATURE),
+        ),
+        ("humidity.png", preview.climate(|n| &n.humidity, HUMIDITY)),
+        ("biomes.png", preview.biomes()),
+    ];
+    for (name, image) in images {
+        let path = args.out.join(name);
+        image.save(&path)?;
+        println!("wrote {}", path.display());
+    }
+
+    println!("biomes:");
+    for (index, biome) in biome_table.biomes().iter().enumerate() {
+        let [r, g, b] = biome_color(index).0;
+        println!("  #{r:02x}{g:02x}{b:02x} {}", biome.name());
+    }
+
+    Ok(())
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, Box<dyn Error>> {
+    fs::read(path).map_err(|err| format!("failed to read {}: {err}", path.display()).into())
+}
+
+/// Shore to peak.
+const LAND: [[u8; 3]; 4] = [
+    [80, 150, 60],
+    [150, 140, 90],
+    [120, 110, 100],
+    [245, 245, 245],
+];
+/// Shallow to deep.
+const SEA: [[u8; 3]; 2] = [[70, 130, 220], [10, 30, 90]];
+/// Cold to hot.
+const TEMPERATURE: [[u8; 3]; 3] = [[40, 70, 200], [235, 235, 220], [210, 50, 30]];
+/// Dry to wet.
+const HUMIDITY: [[u8; 3]; 3] = [[200, 160, 90], [120, 180, 80], [30, 90, 170]];
+
+/// The noise for every column in the rectangle, laid out as one big image
+/// with +X to the right and +Z down.
+struct Preview {
+    columns: Vec<Chunk2dNoiseValues>,
+    min: IVec2,
+    chunks: UVec2,
+}
+
+impl Preview {
+    fn generate(noise: &WorldNoiseSettings, min: IVec2, max: IVec2) -> Self {
+        let chunks = (max - min + IVec2::ONE).as_uvec2();
+        let columns = (0..chunks.y as i32)
+            .flat_map(|z| (0..chunks.x as i32).map(move |x| min + IVec2::new(x, z)))
+            .map(|column| noise.generate_chunk_2d_noise(column))
+            .collect();
+        Self {
+            columns,
+            min,
+            chunks,
+        }
+    }
+
+    fn size(&self) -> UVec2 {
+        self.chunks * CHUNK_WIDTH
+    }
+
+    /// The column noise and the index into it for a pixel.
+    fn at(&self, x: u32, y: u32) -> (&Chunk2dNoiseValues, usize) {
+        let chunk = UVec2::new(x, y) / CHUNK_WIDTH;
+        let noise = &self.columns[(chunk.y * self.chunks.x + chunk.x) as usize];
+        debug_assert_eq!(noise.chunk_pos, self.min + chunk.as_ivec2());
+        let local = UVec2::new(x, y) % CHUNK_WIDTH;
+        (noise, (local.y * CHUNK_WIDTH + local.x) as usize)
+    }
+
+    fn height(&self, x: u32, y: u32) -> f64 {
+        let (noise, i) = self.at(x, y);
+        noise.heightmap[i]
+    }
+
+    /// Land is colored by height and lit from the north-west so slopes stand
+    /// out. Anything under the sea is blue, darker the deeper it is.
+    fn heightmap(&self, sea_level: i32) -> RgbImage {
+        let size = self.size();
+        let sea_level = sea_level as f64;
+        let (low, high) = self
+            .columns
+            .iter()
+            .flat_map(|noise| noise.heightmap.iter().copied())
+            .fold((sea_level, sea_level + 1.0), |(low, high), height| {
+                (low.min(height), high.max(height))
+            });
+
+        RgbImage::from_fn(size.x, size.y, |x, y| {
+            let height = self.height(x, y);
+            if height.round() < sea_level {
+                let depth = ((sea_level - height) / (sea_level - low).max(1.0)).clamp(0.0, 1.0);
+                return Rgb(lerp_color(&SEA, depth));
+            }
+
+            let dx = self.height((x + 1).min(size.x - 1), y) - self.height(x.saturating_sub(1), y);
+            let dy = self.height(x, (y + 1).min(size.y - 1)) - self.height(x, y.saturating_sub(1));
+            let normal = Vec3::new(-dx as f32, 2.0, -dy as f32).normalize();
+            let light = normal.dot(Vec3::new(-1.0, 1.0, -1.0).normalize()).max(0.0);
+            let shade = 0.45 + 0.55 * light;
+
+            let t = (height - sea_level) / (high - sea_level);
+            let color = lerp_color(&LAND, t);
+            Rgb(color.map(|c| (c as f32 * shade).min(255.0) as u8))
+        })
+    }
+
+    fn climate(
+        &self,
+        values: impl Fn(&Chunk2dNoiseValues) -> &Vec<f64>,
+        gradient: [[u8; 3]; 3],
+    ) -> RgbImage {
+        let size = self.size();
+        RgbImage::from_fn(size.x, size.y, |x, y| {
+            let (noise, i) = self.at(x, y);
+            Rgb(lerp_color(
+                &gradient,
+                BiomeTable::normalize(values(noise)[i]),
+            ))
+        })
+    }
+
+    fn biomes(&self) -> RgbImage {
+        let size = self.size();
+        RgbImage::from_fn(size.x, size.y, |x, y| {
+            let (noise, i) = self.at(x, y);
+            biome_color(noise.biomes[i])
+        })
+    }
+}
+
+/// Neighboring indices are spread around the color wheel so they're easy to
+/// tell apart.
+fn biome_color(index: usize) -> Rgb<u8> {
+    let hue = (index as f32 * 137.508) % 360.0;
+    let lightness = [0.45, 0.6, 0.35][index % 3];
+    let [r, g, b, _] = Color::hsl(hue, 0.65, lightness).as_rgba_u8();
+    Rgb([r, g, b])
+}
+
+/// Sample a gradient of evenly spaced stops, with `t` in `0.0..=1.0`.
+fn lerp_color(stops: &[[u8; 3]], t: f64) -> [u8; 3] {
+    let scaled = t.clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+    let low = (scaled.floor() as usize).min(stops.len() - 2);
+    let t = scaled - low as f64;
+    let (a, b) = (stops[low], stops[low + 1]);
+    [0, 1, 2].map(|c| (a[c] as f64 + (b[c] as f64 - a[c] as f64) * t).round() as u8)
+}
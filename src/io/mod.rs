@@ -1,18 +1,28 @@
-use crate::voxel::{RegionHandler, RegionPos, VoxelRegion};
-use bevy::prelude::IVec3;
+mod region_file;
+mod region_format;
+mod save_error;
+mod saves;
+mod world_file;
+
+pub use region_file::*;
+pub use region_format::*;
+pub use save_error::*;
+pub use saves::*;
+pub use world_file::*;
+
+use crate::voxel::{InRegionChunkPos, PendingWrites, RegionHandler, RegionPos};
+use bevy::prelude::*;
 use bincode::config::Configuration;
 use directories::ProjectDirs;
-use flate2::{read::GzDecoder, write::GzEncoder, Compression};
 use lazy_static::lazy_static;
-use serde::de::DeserializeOwned;
 use std::{
-    fs::File,
-    io::{BufReader, Write},
-    path::{Path, PathBuf},
+    fs::{File, OpenOptions},
+    path::PathBuf,
 };
 
 pub const SAVES_DIR_NAME: &str = "saves";
 pub const REGIONS_DIR_NAME: &str = "regions";
+pub const PENDING_WRITES_FILE_NAME: &str = "pending_writes.bin";
 
 lazy_static! {
     pub static ref PROJECT_DIRS: ProjectDirs =
@@ -34,41 +44,150 @@ pub fn save_regions_dir(world_name: &str) -> PathBuf {
     saves_dir(world_name).join(REGIONS_DIR_NAME)
 }
 
-pub fn save_region_file(world_name: &str, RegionPos(IVec3 { x, y, z }): RegionPos) -> PathBuf {
-    save_regions_dir(world_name).join(format!("{x}_{y}_{z}.region.gz"))
+pub fn save_pending_writes_file(world_name: &str) -> PathBuf {
+    saves_dir(world_name).join(PENDING_WRITES_FILE_NAME)
+}
+
+/// Write the decorations still waiting for their chunks. Written to a
+/// temporary file first so a crash can't leave half a file behind.
+pub fn write_pending_writes(
+    world_name: &str,
+    pending_writes: &PendingWrites,
+) -> Result<(), SaveError> {
+    let dir = saves_dir(world_name);
+    std::fs::create_dir_all(&dir).map_err(|err| SaveError::CreateDir(dir.clone(), err))?;
+
+    let path = save_pending_writes_file(world_name);
+    let temp_path = path.with_extension("bin.tmp");
+    let contents = bincode::serde::encode_to_vec(pending_writes, SERIAL_CONFIG)?;
+    std::fs::write(&temp_path, contents)
+        .and_then(|_| std::fs::rename(&temp_path, &path))
+        .map_err(|err| SaveError::WritePendingWrites(path, err))
 }
 
-pub fn write_region_to_file(world_name: &str, region_pos: RegionPos, region: &VoxelRegion) {
-    std::fs::create_dir_all(save_regions_dir(world_name)).unwrap();
-    let region_file_path = save_region_file(world_name, region_pos);
-    write_to_file(&region_file_path, region);
+/// Returns an empty queue if the world doesn't have any pending writes.
+pub fn read_pending_writes(world_name: &str) -> Result<PendingWrites, SaveError> {
+    let path = save_pending_writes_file(world_name);
+    if !path.exists() {
+        return Ok(PendingWrites::default());
+    }
+    let bytes =
+        std::fs::read(&path).map_err(|err| SaveError::ReadPendingWrites(path.clone(), err))?;
+    bincode::serde::decode_from_slice(&bytes, SERIAL_CONFIG)
+        .map(|(pending_writes, _)| pending_writes)
+        .map_err(|err| SaveError::DecodePendingWrites(path, err))
+}
+
+/// Write the world file next to the regions directory.
+pub fn write_world_file(world_file: &WorldFile) -> Result<(), SaveError> {
+    write_world_file_in(&saves_dir(&world_file.name), world_file)
 }
 
-pub fn write_regions_to_file(world_name: &str, region_handler: &RegionHandler) {
-    region_handler
-        .regions()
-        .for_each(|(pos, region)| write_region_to_file(world_name, *pos, region));
+/// Returns `Ok(None)` if the world doesn't have a world file yet.
+pub fn read_world_file(world_name: &str) -> Result<Option<WorldFile>, SaveError> {
+    read_world_file_in(&saves_dir(world_name))
 }
 
-pub fn read_region_from_file(world_name: &str, region_pos: RegionPos) -> Option<VoxelRegion> {
-    read_from_file(&save_region_file(world_name, region_pos))
+pub fn save_region_file(world_name: &str, RegionPos(IVec3 { x, y, z }): RegionPos) -> PathBuf {
+    save_regions_dir(world_name).join(format!("{x}_{y}_{z}.region"))
+}
+
+/// Where regions were saved before they were split into sectors.
+pub fn legacy_save_region_file(
+    world_name: &str,
+    RegionPos(IVec3 { x, y, z }): RegionPos,
+) -> PathBuf {
+    save_regions_dir(world_name).join(format!("{x}_{y}_{z}.region.gz"))
 }
 
-fn write_to_file<Data: serde::Serialize>(path: &Path, input_data: Data) {
-    // Serialize chunk
-    let serialized_data = bincode::serde::encode_to_vec(input_data, SERIAL_CONFIG).unwrap();
-    let mut gzip_encoder = GzEncoder::new(File::create(path).unwrap(), Compression::default());
-    gzip_encoder.write_all(&serialized_data[..]).unwrap();
-    let mut file_write = gzip_encoder.finish().unwrap();
-    file_write.flush().unwrap();
+/// Tries to write every region with modified chunks, even if some fail.
+/// Returns the errors for the regions that couldn't be written.
+pub fn write_regions_to_file(
+    world_name: &str,
+    region_handler: &mut RegionHandler,
+) -> Vec<SaveError> {
+    let mut errors = vec![];
+    for pos in region_handler.dirty_region_positions() {
+        // Don't clobber files we failed to read, the player's edits are in
+        // there.
+        if region_handler.is_unreadable(pos) {
+            warn!(
+                "not saving region {} because its file couldn't be read",
+                pos.0
+            );
+            continue;
+        }
+        if let Err(err) = region_handler.write_region(world_name, pos) {
+            error!("{err}");
+            errors.push(err);
+        }
+    }
+
+    if let Some(pending_writes) = region_handler.dirty_pending_writes() {
+        match write_pending_writes(world_name, pending_writes) {
+            Ok(()) => region_handler.mark_pending_writes_clean(),
+            Err(err) => {
+                error!("{err}");
+                errors.push(err);
+            }
+        }
+    }
+    errors
 }
 
-fn read_from_file<Data: DeserializeOwned>(path: &Path) -> Option<Data> {
-    match path.exists() {
-        true => {
-            let gzip_decoder = GzDecoder::new(File::open(path).ok()?);
-            bincode::serde::decode_from_reader(BufReader::new(gzip_decoder), SERIAL_CONFIG).ok()
+/// Returns `Ok(None)` if the region hasn't been saved yet. Regions saved in
+/// the old whole-region format are converted to the sector format (and the
+/// old file removed) the first time they're opened. The converted region is
+/// written to a temporary file first, so a crash part way through leaves
+/// the old file to convert again next time.
+pub fn open_region_file(
+    world_name: &str,
+    region_pos: RegionPos,
+) -> Result<Option<RegionFile<File>>, RegionFileError> {
+    let path = save_region_file(world_name, region_pos);
+    if path.exists() {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        return RegionFile::open(file).map(Some);
+    }
+
+    let legacy_path = legacy_save_region_file(world_name, region_pos);
+    if legacy_path.exists() {
+        info!("migrating region {region_pos} of world \"{world_name}\" to the sector format");
+        let region = read_legacy_region(File::open(&legacy_path)?)?;
+        let temp_path = path.with_extension("region.tmp");
+        let mut region_file = RegionFile::create(File::create(&temp_path)?)?;
+        for (index, chunk) in region.chunks().iter().enumerate() {
+            if let Some(chunk) = chunk {
+                region_file.write_chunk(InRegionChunkPos::from_index(index), Some(chunk))?;
+            }
         }
-        false => None,
+        region_file.into_inner().sync_all()?;
+        std::fs::rename(&temp_path, &path)?;
+        std::fs::remove_file(legacy_path)?;
+
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        return RegionFile::open(file).map(Some);
     }
+
+    Ok(None)
+}
+
+/// Open the region's file, creating it (and the regions directory) if it
+/// doesn't exist yet.
+pub fn open_or_create_region_file(
+    world_name: &str,
+    region_pos: RegionPos,
+) -> Result<RegionFile<File>, SaveError> {
+    if let Some(region_file) = open_region_file(world_name, region_pos)
+        .map_err(|err| SaveError::ReadRegion(region_pos, err))?
+    {
+        return Ok(region_file);
+    }
+
+    let regions_dir = save_regions_dir(world_name);
+    std::fs::create_dir_all(&regions_dir).map_err(|err| SaveError::CreateDir(regions_dir, err))?;
+    File::create(save_region_file(world_name, region_pos))
+        .map_err(RegionFileError::from)
+        .and_then(RegionFile::create)
+        .map_err(|err| SaveError::WriteRegion(region_pos, err))
 }
@@ -0,0 +1,414 @@
+//! Sector-based region files, so single chunks can be read and rewritten
+//! without touching the rest of the region.
+//!
+//! | Offset              | Contents                                        |
+//! |---------------------|-------------------------------------------------|
+//! | 0                   | [`RegionHeader`]                                |
+//! | [`TABLE_OFFSET`]    | One [`ChunkSlot`] per chunk in the region       |
+//! | [`FIRST_DATA_SECTOR`] * [`SECTOR_SIZE`] | Chunk data, in sectors |
+//!
+//! Each slot is the first sector of the chunk's data followed by its length
+//! in bytes (both `u32`, little endian). A length of 0 means the chunk isn't
+//! saved. Chunk data is a gzipped, bincode-encoded [`VoxelContainer`] taking
+//! up as many whole sectors as it needs.
+
+use super::{RegionFileError, RegionHeader, SERIAL_CONFIG};
+use crate::voxel::{InRegionChunkPos, VoxelContainer, REGION_CUBE};
+use flate2::{read::GzDecoder, write::GzEncoder, Compression};
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+
+pub const SECTOR_SIZE: u64 = 4096;
+pub const TABLE_OFFSET: u64 = RegionHeader::SIZE;
+pub const TABLE_SIZE: u64 = REGION_CUBE as u64 * ChunkSlot::SIZE;
+pub const FIRST_DATA_SECTOR: u32 = (TABLE_OFFSET + TABLE_SIZE).div_ceil(SECTOR_SIZE) as u32;
+
+/// Where a chunk's data lives within the region file.
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ChunkSlot {
+    pub sector: u32,
+    pub len: u32,
+}
+
+impl ChunkSlot {
+    const SIZE: u64 = 8;
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn sector_count(&self) -> u32 {
+        sectors_for(self.len as u64)
+    }
+
+    fn to_bytes(self) -> [u8; Self::SIZE as usize] {
+        let mut bytes = [0; Self::SIZE as usize];
+        bytes[..4].copy_from_slice(&self.sector.to_le_bytes());
+        bytes[4..].copy_from_slice(&self.len.to_le_bytes());
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Self {
+        Self {
+            sector: u32::from_le_bytes(bytes[..4].try_into().unwrap()),
+            len: u32::from_le_bytes(bytes[4..].try_into().unwrap()),
+        }
+    }
+}
+
+fn sectors_for(len: u64) -> u32 {
+    len.div_ceil(SECTOR_SIZE) as u32
+}
+
+/// An open region file. Works on anything seekable so it can be tested
+/// in memory.
+pub struct RegionFile<F> {
+    inner: F,
+    slots: Vec<ChunkSlot>,
+    /// Whether each sector is taken by a chunk, indexed from the start of
+    /// the file. Sectors past the end are free.
+    used_sectors: Vec<bool>,
+}
+
+impl<F: Read + Write + Seek> RegionFile<F> {
+    /// Write the header and an empty offset table, overwriting whatever was
+    /// at the start of `inner`.
+    pub fn create(mut inner: F) -> Result<Self, RegionFileError> {
+        inner.seek(SeekFrom::Start(0))?;
+        RegionHeader::current().write(&mut inner)?;
+        inner.write_all(&vec![0; TABLE_SIZE as usize])?;
+        inner.flush()?;
+
+        Ok(Self {
+            inner,
+            slots: vec![ChunkSlot::default(); REGION_CUBE as usize],
+            used_sectors: vec![true; FIRST_DATA_SECTOR as usize],
+        })
+    }
+
+    pub fn open(mut inner: F) -> Result<Self, RegionFileError> {
+        inner.seek(SeekFrom::Start(0))?;
+        let header = RegionHeader::read(&mut BufReader::new(&mut inner))?;
+        header.validate()?;
+        if header.version != RegionHeader::current().version {
+            // Older versions are whole-region blobs, handled by the legacy
+            // reader instead.
+            return Err(RegionFileError::UnsupportedVersion(header.version));
+        }
+
+        let file_len = inner.seek(SeekFrom::End(0))?;
+        let mut table = vec![0; TABLE_SIZE as usize];
+        inner.seek(SeekFrom::Start(TABLE_OFFSET))?;
+        inner.read_exact(&mut table)?;
+
+        let mut region_file = Self {
+            inner,
+            slots: table
+                .chunks_exact(ChunkSlot::SIZE as usize)
+                .map(ChunkSlot::from_bytes)
+                .collect(),
+            used_sectors: vec![true; FIRST_DATA_SECTOR as usize],
+        };
+
+        // Rebuild the sector map, making sure every chunk is within the file
+        // and no two chunks overlap
+        for index in 0..region_file.slots.len() {
+            let slot = region_file.slots[index];
+            if slot.is_empty() {
+                continue;
+            }
+            let Some(end_sector) = slot.sector.checked_add(slot.sector_count()) else {
+                return Err(RegionFileError::BadChunkSlot(index));
+            };
+            let mut sectors = slot.sector as usize..end_sector as usize;
+            if slot.sector < FIRST_DATA_SECTOR
+                || slot.sector as u64 * SECTOR_SIZE + slot.len as u64 > file_len
+                || sectors.any(|sector| region_file.is_used(sector))
+            {
+                return Err(RegionFileError::BadChunkSlot(index));
+            }
+            region_file.mark_sectors(slot, true);
+        }
+
+        Ok(region_file)
+    }
+
+    pub fn slot(&self, pos: InRegionChunkPos) -> ChunkSlot {
+        self.slots[pos.index()]
+    }
+
+    pub fn has_chunk(&self, pos: InRegionChunkPos) -> bool {
+        !self.slot(pos).is_empty()
+    }
+
+    /// Read the compressed bytes of a chunk exactly as they are stored.
+    pub fn read_chunk_bytes(
+        &mut self,
+        pos: InRegionChunkPos,
+    ) -> Result<Option<Vec<u8>>, RegionFileError> {
+        let slot = self.slot(pos);
+        if slot.is_empty() {
+            return Ok(None);
+        }
+
+        let mut bytes = vec![0; slot.len as usize];
+        self.inner
+            .seek(SeekFrom::Start(slot.sector as u64 * SECTOR_SIZE))?;
+        self.inner.read_exact(&mut bytes)?;
+        Ok(Some(bytes))
+    }
+
+    pub fn read_chunk(
+        &mut self,
+        pos: InRegionChunkPos,
+    ) -> Result<Option<VoxelContainer>, RegionFileError> {
+        match self.read_chunk_bytes(pos)? {
+            Some(bytes) => Ok(Some(bincode::serde::decode_from_reader(
+                BufReader::new(GzDecoder::new(&bytes[..])),
+                SERIAL_CONFIG,
+            )?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Save a chunk, or remove it from the file if `None`. The chunk is
+    /// rewritten in place if it still fits in its sectors, otherwise it's
+    /// moved to the first free run of sectors that's big enough. No other
+    /// chunk's data is touched.
+    pub fn write_chunk(
+        &mut self,
+        pos: InRegionChunkPos,
+        chunk: Option<&VoxelContainer>,
+    ) -> Result<(), RegionFileError> {
+        let old_slot = self.slot(pos);
+        self.mark_sectors(old_slot, false);
+
+        let new_slot = match chunk {
+            Some(chunk) => {
+                let serialized_data = bincode::serde::encode_to_vec(chunk, SERIAL_CONFIG)?;
+                let mut gzip_encoder = GzEncoder::new(vec![], Compression::default());
+                gzip_encoder.write_all(&serialized_data[..])?;
+                let bytes = gzip_encoder.finish()?;
+
+                let sector_count = sectors_for(bytes.len() as u64);
+                let slot = ChunkSlot {
+                    sector: match !old_slot.is_empty() && sector_count <= old_slot.sector_count() {
+                        true => old_slot.sector,
+                        false => self.find_free_sectors(sector_count),
+                    },
+                    len: bytes.len() as u32,
+                };
+                self.inner
+                    .seek(SeekFrom::Start(slot.sector as u64 * SECTOR_SIZE))?;
+                self.inner.write_all(&bytes)?;
+                self.mark_sectors(slot, true);
+                slot
+            }
+            None => ChunkSlot::default(),
+        };
+
+        self.slots[pos.index()] = new_slot;
+        self.inner.seek(SeekFrom::Start(
+            TABLE_OFFSET + pos.index() as u64 * ChunkSlot::SIZE,
+        ))?;
+        self.inner.write_all(&new_slot.to_bytes())?;
+        self.inner.flush()?;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> F {
+        self.inner
+    }
+
+    fn is_used(&self, sector: usize) -> bool {
+        self.used_sectors.get(sector).copied().unwrap_or(false)
+    }
+
+    fn mark_sectors(&mut self, slot: ChunkSlot, used: bool) {
+        if slot.is_empty() {
+            return;
+        }
+        let end = (slot.sector + slot.sector_count()) as usize;
+        if self.used_sectors.len() < end {
+            self.used_sectors.resize(end, false);
+        }
+        self.used_sectors[slot.sector as usize..end].fill(used);
+    }
+
+    fn find_free_sectors(&self, count: u32) -> u32 {
+        let mut start = FIRST_DATA_SECTOR as usize;
+        while start < self.used_sectors.len() {
+            match (start..start + count as usize).find(|sector| self.is_used(*sector)) {
+                // Skip past the taken sector
+                Some(used) => start = used + 1,
+                None => return start as u32,
+            }
+        }
+        start as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::{InChunkPos, Voxel, CHUNK_WIDTH, REGION_WIDTH};
+    use bevy::math::UVec3;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::io::Cursor;
+
+    fn random_region_pos(rng: &mut StdRng) -> InRegionChunkPos {
+        InRegionChunkPos::new(UVec3::new(
+            rng.gen_range(0..REGION_WIDTH),
+            rng.gen_range(0..REGION_WIDTH),
+            rng.gen_range(0..REGION_WIDTH),
+        ))
+        .unwrap()
+    }
+
+    /// Noisier chunks compress worse, so this gives a good spread of sizes.
+    fn random_chunk(rng: &mut StdRng) -> VoxelContainer {
+        let mut chunk = VoxelContainer::from_voxel(Voxel(rng.gen_range(0..4)));
+        for _ in 0..rng.gen_range(0..4000) {
+            let pos = UVec3::new(
+                rng.gen_range(0..CHUNK_WIDTH),
+                rng.gen_range(0..CHUNK_WIDTH),
+                rng.gen_range(0..CHUNK_WIDTH),
+            );
+            chunk.set(InChunkPos::new(pos).unwrap(), Voxel(rng.gen_range(0..20)));
+        }
+        chunk
+    }
+
+    fn random_chunks(rng: &mut StdRng, count: usize) -> Vec<(InRegionChunkPos, VoxelContainer)> {
+        let mut chunks: Vec<(InRegionChunkPos, VoxelContainer)> = vec![];
+        while chunks.len() < count {
+            let pos = random_region_pos(rng);
+            if chunks.iter().all(|(other, _)| *other != pos) {
+                chunks.push((pos, random_chunk(rng)));
+            }
+        }
+        chunks
+    }
+
+    #[test]
+    fn round_trip_random_chunks() {
+        let mut rng = StdRng::seed_from_u64(1234);
+        let chunks = random_chunks(&mut rng, 64);
+
+        let mut region_file = RegionFile::create(Cursor::new(vec![])).unwrap();
+        for (pos, chunk) in &chunks {
+            region_file.write_chunk(*pos, Some(chunk)).unwrap();
+        }
+
+        // Reopen from the raw bytes to make sure the table was written
+        let mut region_file = RegionFile::open(region_file.into_inner()).unwrap();
+        for (pos, chunk) in &chunks {
+            assert_eq!(region_file.read_chunk(*pos).unwrap().as_ref(), Some(chunk));
+        }
+        let loaded = (0..REGION_CUBE)
+            .filter(|index| !region_file.slots[*index as usize].is_empty())
+            .count();
+        assert_eq!(loaded, chunks.len());
+    }
+
+    #[test]
+    fn rewriting_leaves_other_chunks_untouched() {
+        let mut rng = StdRng::seed_from_u64(5678);
+        let chunks = random_chunks(&mut rng, 32);
+
+        let mut region_file = RegionFile::create(Cursor::new(vec![])).unwrap();
+        for (pos, chunk) in &chunks {
+            region_file.write_chunk(*pos, Some(chunk)).unwrap();
+        }
+        let original_bytes = chunks
+            .iter()
+            .map(|(pos, _)| region_file.read_chunk_bytes(*pos).unwrap().unwrap())
+            .collect::<Vec<_>>();
+
+        // Grow one chunk so it has to move, shrink another so it's rewritten
+        // in place, and remove a third.
+        let (grown, shrunk, removed) = (chunks[3].0, chunks[10].0, chunks[20].0);
+        let mut noisy = VoxelContainer::default();
+        for index in 0..CHUNK_WIDTH * CHUNK_WIDTH * CHUNK_WIDTH {
+            let pos = UVec3::new(
+                index % CHUNK_WIDTH,
+                index / CHUNK_WIDTH % CHUNK_WIDTH,
+                index / (CHUNK_WIDTH * CHUNK_WIDTH),
+            );
+            noisy.set(InChunkPos::new(pos).unwrap(), Voxel(rng.gen_range(0..64)));
+        }
+        let uniform = VoxelContainer::from_voxel(Voxel(1));
+        let shrunk_sector = region_file.slot(shrunk).sector;
+        region_file.write_chunk(grown, Some(&noisy)).unwrap();
+        region_file.write_chunk(shrunk, Some(&uniform)).unwrap();
+        region_file.write_chunk(removed, None).unwrap();
+        assert_eq!(region_file.slot(shrunk).sector, shrunk_sector);
+
+        let mut region_file = RegionFile::open(region_file.into_inner()).unwrap();
+        assert_eq!(region_file.read_chunk(grown).unwrap(), Some(noisy));
+        assert_eq!(region_file.read_chunk(shrunk).unwrap(), Some(uniform));
+        assert_eq!(region_file.read_chunk(removed).unwrap(), None);
+        for ((pos, _), bytes) in chunks.iter().zip(original_bytes) {
+            if ![grown, shrunk, removed].contains(pos) {
+                assert_eq!(region_file.read_chunk_bytes(*pos).unwrap(), Some(bytes));
+            }
+        }
+    }
+
+    #[test]
+    fn freed_sectors_are_reused() {
+        let mut rng = StdRng::seed_from_u64(91011);
+        let chunks = random_chunks(&mut rng, 8);
+
+        let mut region_file = RegionFile::create(Cursor::new(vec![])).unwrap();
+        for (pos, chunk) in &chunks {
+            region_file.write_chunk(*pos, Some(chunk)).unwrap();
+        }
+        let first_sector = region_file.slot(chunks[0].0).sector;
+        region_file.write_chunk(chunks[0].0, None).unwrap();
+
+        let small = VoxelContainer::from_voxel(Voxel(2));
+        let pos = (0..REGION_CUBE)
+            .map(|index| InRegionChunkPos::from_index(index as usize))
+            .find(|pos| !region_file.has_chunk(*pos))
+            .unwrap();
+        region_file.write_chunk(pos, Some(&small)).unwrap();
+        assert_eq!(region_file.slot(pos).sector, first_sector);
+    }
+
+    #[test]
+    fn slots_past_the_end_are_rejected() {
+        let mut rng = StdRng::seed_from_u64(121314);
+        let (pos, chunk) = random_chunks(&mut rng, 1).remove(0);
+        let mut region_file = RegionFile::create(Cursor::new(vec![])).unwrap();
+        region_file.write_chunk(pos, Some(&chunk)).unwrap();
+        let bytes = region_file.into_inner().into_inner();
+
+        let slot_offset = (TABLE_OFFSET + pos.index() as u64 * ChunkSlot::SIZE) as usize;
+        let sector = u32::from_le_bytes(bytes[slot_offset..slot_offset + 4].try_into().unwrap());
+        for bad_slot in [
+            // Overflows when adding up the sectors
+            ChunkSlot {
+                sector: u32::MAX,
+                len: u32::MAX,
+            },
+            // Would need gigabytes of sectors
+            ChunkSlot {
+                sector,
+                len: u32::MAX,
+            },
+            // Just one byte too long
+            ChunkSlot {
+                sector,
+                len: (bytes.len() as u64 - sector as u64 * SECTOR_SIZE) as u32 + 1,
+            },
+        ] {
+            let mut bytes = bytes.clone();
+            bytes[slot_offset..slot_offset + ChunkSlot::SIZE as usize]
+                .copy_from_slice(&bad_slot.to_bytes());
+            assert!(matches!(
+                RegionFile::open(Cursor::new(bytes)),
+                Err(RegionFileError::BadChunkSlot(index)) if index == pos.index()
+            ));
+        }
+    }
+}
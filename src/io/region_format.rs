@@ -0,0 +1,180 @@
+//! Region file headers, and reading the old whole-region formats.
+//!
+//! Every region file since version 1 starts with an uncompressed header:
+//!
+//! | Bytes | Contents                          |
+//! |-------|-----------------------------------|
+//! | 8     | Magic bytes, [`REGION_MAGIC`]     |
+//! | 2     | Format version (little endian)    |
+//! | 4     | Chunk width (little endian)       |
+//! | 4     | Region width (little endian)      |
+//!
+//! Version 1 follows it with the gzipped, bincode-encoded [`VoxelRegion`].
+//! Files written before the header existed start directly with the gzip
+//! stream and are treated as version 0. Both are only read to be migrated;
+//! see [`super::region_file`] for the current layout.
+
+use super::SERIAL_CONFIG;
+use crate::voxel::{
+    Voxel, VoxelContainer, VoxelRegion, CHUNK_CUBE, CHUNK_WIDTH, REGION_CUBE, REGION_WIDTH,
+};
+use flate2::read::GzDecoder;
+use serde::Deserialize;
+use serde_with::serde_as;
+use std::io::{BufRead, BufReader, Read, Write};
+use thiserror::Error;
+
+pub const REGION_MAGIC: [u8; 8] = *b"CWNWREGN";
+pub const REGION_FORMAT_VERSION: u16 = 2;
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+#[derive(Debug, Error)]
+pub enum RegionFileError {
+    #[error("failed to access region file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("not a region file (bad magic bytes)")]
+    BadMagic,
+    #[error(
+        "region file version {0} is newer than the supported version {}",
+        REGION_FORMAT_VERSION
+    )]
+    UnsupportedVersion(u16),
+    #[error("region file has chunk width {0}, expected {}", CHUNK_WIDTH)]
+    ChunkWidthMismatch(u32),
+    #[error("region file has region width {0}, expected {}", REGION_WIDTH)]
+    RegionWidthMismatch(u32),
+    #[error("chunk slot {0} points outside the data sectors or overlaps another chunk")]
+    BadChunkSlot(usize),
+    #[error("failed to encode region: {0}")]
+    Encode(#[from] bincode::error::EncodeError),
+    #[error("failed to decode region: {0}")]
+    Decode(#[from] bincode::error::DecodeError),
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct RegionHeader {
+    pub version: u16,
+    pub chunk_width: u32,
+    pub region_width: u32,
+}
+
+impl RegionHeader {
+    /// Size of the header on disk, in bytes.
+    pub const SIZE: u64 = 18;
+
+    pub fn current() -> Self {
+        Self {
+            version: REGION_FORMAT_VERSION,
+            chunk_width: CHUNK_WIDTH,
+            region_width: REGION_WIDTH,
+        }
+    }
+
+    /// Headerless files from before versioning always used these widths.
+    fn legacy() -> Self {
+        Self {
+            version: 0,
+            chunk_width: 31,
+            region_width: 16,
+        }
+    }
+
+    pub fn write(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        writer.write_all(&REGION_MAGIC)?;
+        writer.write_all(&self.version.to_le_bytes())?;
+        writer.write_all(&self.chunk_width.to_le_bytes())?;
+        writer.write_all(&self.region_width.to_le_bytes())
+    }
+
+    pub fn read(reader: &mut impl BufRead) -> Result<Self, RegionFileError> {
+        // Peek to check for a legacy file, which is just a gzip stream
+        if reader.fill_buf()?.starts_with(&GZIP_MAGIC) {
+            return Ok(Self::legacy());
+        }
+
+        let mut magic = [0; 8];
+        reader.read_exact(&mut magic)?;
+        if magic != REGION_MAGIC {
+            return Err(RegionFileError::BadMagic);
+        }
+
+        let mut version = [0; 2];
+        let mut chunk_width = [0; 4];
+        let mut region_width = [0; 4];
+        reader.read_exact(&mut version)?;
+        reader.read_exact(&mut chunk_width)?;
+        reader.read_exact(&mut region_width)?;
+
+        Ok(Self {
+            version: u16::from_le_bytes(version),
+            chunk_width: u32::from_le_bytes(chunk_width),
+            region_width: u32::from_le_bytes(region_width),
+        })
+    }
+
+    pub fn validate(&self) -> Result<(), RegionFileError> {
+        if self.version > REGION_FORMAT_VERSION {
+            Err(RegionFileError::UnsupportedVersion(self.version))
+        } else if self.chunk_width != CHUNK_WIDTH {
+            Err(RegionFileError::ChunkWidthMismatch(self.chunk_width))
+        } else if self.region_width != REGION_WIDTH {
+            Err(RegionFileError::RegionWidthMismatch(self.region_width))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Read a whole region from a version 0 or 1 file.
+pub fn read_legacy_region(reader: impl Read) -> Result<VoxelRegion, RegionFileError> {
+    let mut reader = BufReader::new(reader);
+    let header = RegionHeader::read(&mut reader)?;
+    header.validate()?;
+    migrate_region(header.version, reader)
+}
+
+/// Migration hook: decode the region data that follows a header of the
+/// provided version, upgrading it to the current in-memory format.
+fn migrate_region(version: u16, reader: impl Read) -> Result<VoxelRegion, RegionFileError> {
+    let gzip_decoder = BufReader::new(GzDecoder::new(reader));
+    match version {
+        0 => {
+            let legacy: LegacyVoxelRegion =
+                bincode::serde::decode_from_reader(gzip_decoder, SERIAL_CONFIG)?;
+            Ok(legacy.upgrade())
+        }
+        1 => Ok(bincode::serde::decode_from_reader(
+            gzip_decoder,
+            SERIAL_CONFIG,
+        )?),
+        version => Err(RegionFileError::UnsupportedVersion(version)),
+    }
+}
+
+/// Version 0 stored every voxel of every chunk. Voxels were an enum back
+/// then, but bincode writes the variant index as a varint, which decodes
+/// to the same IDs as the current [`Voxel`].
+#[serde_as]
+#[derive(Deserialize)]
+struct LegacyVoxelContainer(
+    #[serde_as(as = "Box<[_; CHUNK_CUBE as usize]>")] Box<[Voxel; CHUNK_CUBE as usize]>,
+);
+
+#[serde_as]
+#[derive(Deserialize)]
+struct LegacyVoxelRegion {
+    #[serde_as(as = "Box<[_; REGION_CUBE as usize]>")]
+    chunks: Box<[Option<LegacyVoxelContainer>; REGION_CUBE as usize]>,
+}
+
+impl LegacyVoxelRegion {
+    fn upgrade(self) -> VoxelRegion {
+        let mut region = VoxelRegion::default();
+        let legacy_chunks: Box<[_]> = self.chunks;
+        for (chunk, legacy_chunk) in region.chunks_mut().iter_mut().zip(legacy_chunks.into_vec()) {
+            *chunk = legacy_chunk.and_then(|c| VoxelContainer::from_voxels(c.0.to_vec()));
+        }
+        region
+    }
+}
@@ -0,0 +1,38 @@
+use super::{RegionFileError, WORLD_FORMAT_VERSION};
+use crate::voxel::RegionPos;
+use std::path::PathBuf;
+use thiserror::Error;
+
+/// Anything that can go wrong while reading or writing a world save.
+#[derive(Debug, Error)]
+pub enum SaveError {
+    #[error("failed to create save directory {0}: {1}")]
+    CreateDir(PathBuf, #[source] std::io::Error),
+    #[error("failed to write region {0}: {1}")]
+    WriteRegion(RegionPos, #[source] RegionFileError),
+    #[error("failed to read region {0}: {1}")]
+    ReadRegion(RegionPos, #[source] RegionFileError),
+    #[error("failed to write world file {0}: {1}")]
+    WriteWorldFile(PathBuf, #[source] std::io::Error),
+    #[error("failed to serialize world file: {0}")]
+    SerializeWorldFile(#[from] ron::Error),
+    #[error("failed to read world file {0}: {1}")]
+    ReadWorldFile(PathBuf, #[source] std::io::Error),
+    #[error("failed to parse world file {0}: {1}")]
+    ParseWorldFile(PathBuf, #[source] ron::error::SpannedError),
+    #[error(
+        "world file version {0} is newer than the supported version {}",
+        WORLD_FORMAT_VERSION
+    )]
+    UnsupportedWorldVersion(u16),
+    #[error("failed to write pending writes file {0}: {1}")]
+    WritePendingWrites(PathBuf, #[source] std::io::Error),
+    #[error("failed to encode pending writes: {0}")]
+    EncodePendingWrites(#[from] bincode::error::EncodeError),
+    #[error("failed to read pending writes file {0}: {1}")]
+    ReadPendingWrites(PathBuf, #[source] std::io::Error),
+    #[error("failed to decode pending writes file {0}: {1}")]
+    DecodePendingWrites(PathBuf, #[source] bincode::error::DecodeError),
+    #[error("the region handler lock was poisoned by a panicking thread")]
+    LockPoisoned,
+}
@@ -0,0 +1,386 @@
+use super::{read_world_file_in, write_world_file_in, SaveError, WorldFile, SAVES_DIR};
+use bevy::prelude::*;
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum ManageSaveError {
+    #[error("\"{0}\" isn't a valid world name")]
+    InvalidName(String),
+    #[error("a world named \"{0}\" already exists")]
+    AlreadyExists(String),
+    #[error("there is no world named \"{0}\"")]
+    NotFound(String),
+    #[error("{0}")]
+    Io(#[from] io::Error),
+    #[error("{0}")]
+    WorldFile(#[from] SaveError),
+}
+
+/// A world save found in the saves directory.
+pub struct SaveSummary {
+    pub dir_name: String,
+    /// `None` if the save doesn't have a readable world file.
+    pub world_file: Option<WorldFile>,
+    /// Total size of the save directory, in bytes.
+    pub size: u64,
+}
+
+/// A directory full of world saves, one directory per world. The game only
+/// ever uses [`SavesDir::main`], but anywhere else works too (like a temp
+/// directory in tests).
+#[derive(Debug, Clone)]
+pub struct SavesDir {
+    root: PathBuf,
+}
+
+impl SavesDir {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    pub fn main() -> Self {
+        Self::new(SAVES_DIR.as_path())
+    }
+
+    pub fn world_dir(&self, world_name: &str) -> PathBuf {
+        self.root.join(world_name)
+    }
+
+    pub fn exists(&self, world_name: &str) -> bool {
+        self.world_dir(world_name).is_dir()
+    }
+
+    /// List every world, most recently played first.
+    pub fn list(&self) -> io::Result<Vec<SaveSummary>> {
+        if !self.root.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut saves = vec![];
+        for entry in std::fs::read_dir(&self.root)? {
+            let entry = entry?;
+            if !entry.file_type()?.is_dir() {
+                continue;
+            }
+            let dir_name = entry.file_name().to_string_lossy().into_owned();
+            let world_file = match read_world_file_in(&entry.path()) {
+                Ok(world_file) => world_file,
+                Err(err) => {
+                    warn!("failed to read world file of save \"{dir_name}\": {err}");
+                    None
+                }
+            };
+            saves.push(SaveSummary {
+                size: dir_size(&entry.path())?,
+                dir_name,
+                world_file,
+            });
+        }
+
+        saves.sort_by_key(|save| {
+            std::cmp::Reverse(save.world_file.as_ref().map(|file| file.last_played))
+        });
+        Ok(saves)
+    }
+
+    pub fn delete(&self, world_name: &str) -> Result<(), ManageSaveError> {
+        let dir = self.existing_world_dir(world_name)?;
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    /// Move the world to a new directory and update the name in its world
+    /// file.
+    pub fn rename(&self, world_name: &str, new_name: &str) -> Result<(), ManageSaveError> {
+        let dir = self.existing_world_dir(world_name)?;
+        let new_dir = self.new_world_dir(new_name)?;
+        std::fs::rename(dir, &new_dir)?;
+        Self::rename_world_file(&new_dir, new_name)
+    }
+
+    /// Copy the world into a new directory with the new name.
+    pub fn duplicate(&self, world_name: &str, new_name: &str) -> Result<(), ManageSaveError> {
+        let dir = self.existing_world_dir(world_name)?;
+        let new_dir = self.new_world_dir(new_name)?;
+        copy_dir(&dir, &new_dir)?;
+        Self::rename_world_file(&new_dir, new_name)
+    }
+
+    /// Find a name for a copy of the world that isn't taken yet.
+    pub fn copy_name(&self, world_name: &str) -> String {
+        let mut new_name = format!("{world_name} (copy)");
+        let mut number = 2;
+        while self.exists(&new_name) {
+            new_name = format!("{world_name} (copy {number})");
+            number += 1;
+        }
+        new_name
+    }
+
+    /// Check that a new world can be saved under this name without
+    /// clobbering another world.
+    pub fn check_new_name(&self, world_name: &str) -> Result<(), ManageSaveError> {
+        self.new_world_dir(world_name).map(|_| ())
+    }
+
+    /// Existing worlds only need a name that stays inside the saves
+    /// directory, so worlds from before names were validated (or named
+    /// some other way) can still be managed.
+    fn existing_world_dir(&self, world_name: &str) -> Result<PathBuf, ManageSaveError> {
+        check_dir_name(world_name)?;
+        match self.exists(world_name) {
+            true => Ok(self.world_dir(world_name)),
+            false => Err(ManageSaveError::NotFound(world_name.to_string())),
+        }
+    }
+
+    fn new_world_dir(&self, world_name: &str) -> Result<PathBuf, ManageSaveError> {
+        validate_name(world_name)?;
+        match self.world_dir(world_name).exists() {
+            true => Err(ManageSaveError::AlreadyExists(world_name.to_string())),
+            false => Ok(self.world_dir(world_name)),
+        }
+    }
+
+    fn rename_world_file(dir: &Path, new_name: &str) -> Result<(), ManageSaveError> {
+        if let Some(mut world_file) = read_world_file_in(dir)? {
+            world_file.name = new_name.to_string();
+            write_world_file_in(dir, &world_file)?;
+        }
+        Ok(())
+    }
+}
+
+/// World names are used as directory names, so they can't escape the saves
+/// directory or be the saves directory itself.
+fn check_dir_name(world_name: &str) -> Result<(), ManageSaveError> {
+    let invalid = world_name.is_empty()
+        || world_name == "."
+        || world_name == ".."
+        || world_name.contains(['/', '\\']);
+    match invalid {
+        true => Err(ManageSaveError::InvalidName(world_name.to_string())),
+        false => Ok(()),
+    }
+}
+
+/// New names also can't be blank or start or end with spaces, since those
+/// are too easy to miss.
+fn validate_name(world_name: &str) -> Result<(), ManageSaveError> {
+    check_dir_name(world_name)?;
+    let invalid = world_name.trim().is_empty() || world_name != world_name.trim();
+    match invalid {
+        true => Err(ManageSaveError::InvalidName(world_name.to_string())),
+        false => Ok(()),
+    }
+}
+
+fn dir_size(path: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        size += match metadata.is_dir() {
+            true => dir_size(&entry.path())?,
+            false => metadata.len(),
+        };
+    }
+    Ok(size)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let to = to.join(entry.file_name());
+        match entry.file_type()?.is_dir() {
+            true => copy_dir(&entry.path(), &to)?,
+            false => {
+                std::fs::copy(entry.path(), to)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        io::WORLD_FORMAT_VERSION,
+        voxel::{GeneratorPreset, WorldGenConfig},
+    };
+
+    /// A fresh saves directory under the system temp directory, removed
+    /// when dropped.
+    struct TempSaves(SavesDir);
+
+    impl TempSaves {
+        fn new(test_name: &str) -> Self {
+            let root = std::env::temp_dir().join(format!(
+                "cjs_whole_new_world-{test_name}-{}",
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&root);
+            std::fs::create_dir_all(&root).unwrap();
+            Self(SavesDir::new(root))
+        }
+    }
+
+    impl Drop for TempSaves {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0.root);
+        }
+    }
+
+    fn make_world(saves: &SavesDir, name: &str) {
+        let dir = saves.world_dir(name);
+        write_world_file_in(
+            &dir,
+            &WorldFile {
+                format_version: WORLD_FORMAT_VERSION,
+                name: name.to_string(),
+                seed: 1234,
+                created: 1,
+                last_played: 2,
+                generator: GeneratorPreset::default(),
+                worldgen: WorldGenConfig::default(),
+                player: None,
+            },
+        )
+        .unwrap();
+        std::fs::create_dir_all(dir.join("regions")).unwrap();
+        std::fs::write(dir.join("regions").join("0_0_0.region"), [1, 2, 3]).unwrap();
+    }
+
+    fn world_file(saves: &SavesDir, name: &str) -> WorldFile {
+        read_world_file_in(&saves.world_dir(name)).unwrap().unwrap()
+    }
+
+    #[test]
+    fn list_finds_worlds() {
+        let saves = TempSaves::new("list");
+        make_world(&saves.0, "one");
+        make_world(&saves.0, "two");
+        std::fs::create_dir_all(saves.0.world_dir("no world file")).unwrap();
+
+        let mut names = saves
+            .0
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|save| (save.dir_name, save.world_file.is_some(), save.size > 0))
+            .collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                ("no world file".to_string(), false, false),
+                ("one".to_string(), true, true),
+                ("two".to_string(), true, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn delete_removes_world() {
+        let saves = TempSaves::new("delete");
+        make_world(&saves.0, "doomed");
+        saves.0.delete("doomed").unwrap();
+        assert!(!saves.0.exists("doomed"));
+        assert!(matches!(
+            saves.0.delete("doomed"),
+            Err(ManageSaveError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn rename_moves_world_and_updates_name() {
+        let saves = TempSaves::new("rename");
+        make_world(&saves.0, "old");
+        saves.0.rename("old", "new").unwrap();
+
+        assert!(!saves.0.exists("old"));
+        assert_eq!(world_file(&saves.0, "new").name, "new");
+        assert_eq!(world_file(&saves.0, "new").seed, 1234);
+        assert!(saves
+            .0
+            .world_dir("new")
+            .join("regions")
+            .join("0_0_0.region")
+            .exists());
+    }
+
+    #[test]
+    fn duplicate_copies_everything() {
+        let saves = TempSaves::new("duplicate");
+        make_world(&saves.0, "original");
+        let copy_name = saves.0.copy_name("original");
+        assert_eq!(copy_name, "original (copy)");
+        saves.0.duplicate("original", &copy_name).unwrap();
+
+        assert_eq!(world_file(&saves.0, "original").name, "original");
+        assert_eq!(world_file(&saves.0, &copy_name).name, copy_name);
+        let region = |name: &str| {
+            std::fs::read(saves.0.world_dir(name).join("regions").join("0_0_0.region")).unwrap()
+        };
+        assert_eq!(region("original"), region(&copy_name));
+        assert_eq!(saves.0.copy_name("original"), "original (copy 2)");
+    }
+
+    #[test]
+    fn refuses_bad_names_and_conflicts() {
+        let saves = TempSaves::new("conflicts");
+        make_world(&saves.0, "a");
+        make_world(&saves.0, "b");
+
+        assert!(matches!(
+            saves.0.rename("a", "b"),
+            Err(ManageSaveError::AlreadyExists(_))
+        ));
+        assert!(matches!(
+            saves.0.duplicate("a", "b"),
+            Err(ManageSaveError::AlreadyExists(_))
+        ));
+        assert!(matches!(
+            saves.0.check_new_name("b"),
+            Err(ManageSaveError::AlreadyExists(_))
+        ));
+        for bad_name in ["", " ", "..", "../escape", "sub/dir", " padded "] {
+            assert!(matches!(
+                saves.0.rename("a", bad_name),
+                Err(ManageSaveError::InvalidName(_))
+            ));
+            assert!(matches!(
+                saves.0.check_new_name(bad_name),
+                Err(ManageSaveError::InvalidName(_))
+            ));
+        }
+        assert!(saves.0.check_new_name("c").is_ok());
+        assert!(saves.0.exists("a"));
+    }
+
+    #[test]
+    fn manages_worlds_with_old_names() {
+        let saves = TempSaves::new("old_names");
+        make_world(&saves.0, " padded ");
+        make_world(&saves.0, "doomed ");
+
+        saves.0.rename(" padded ", "padded").unwrap();
+        assert_eq!(world_file(&saves.0, "padded").name, "padded");
+        saves.0.delete("doomed ").unwrap();
+        assert!(!saves.0.exists("doomed "));
+
+        for escaping_name in ["", ".", "..", "../escape", "sub\\dir"] {
+            assert!(matches!(
+                saves.0.delete(escaping_name),
+                Err(ManageSaveError::InvalidName(_))
+            ));
+        }
+        assert!(saves.0.exists("padded"));
+    }
+}
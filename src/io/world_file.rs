@@ -0,0 +1,86 @@
+use super::SaveError;
+use crate::{
+    plugin::control::PlyCamRot,
+    voxel::{GeneratorPreset, WorldGenConfig},
+};
+use bevy::prelude::*;
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub const WORLD_FILE_NAME: &str = "world.ron";
+pub const WORLD_FORMAT_VERSION: u16 = 3;
+
+/// Everything about a world that isn't stored in its regions, saved as
+/// `world.ron` in the world's save directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorldFile {
+    pub format_version: u16,
+    pub name: String,
+    pub seed: u32,
+    /// Seconds since the Unix epoch.
+    pub created: u64,
+    /// Seconds since the Unix epoch.
+    pub last_played: u64,
+    /// Added in version 2, older worlds all used the noise generator.
+    #[serde(default)]
+    pub generator: GeneratorPreset,
+    /// Added in version 3. Older worlds were generated before the defaults
+    /// could change, so they get the original settings.
+    #[serde(default = "WorldGenConfig::original")]
+    pub worldgen: WorldGenConfig,
+    /// `None` if the world was saved before the player spawned.
+    pub player: Option<SavedPlayer>,
+}
+
+/// Where the player was and which way they were looking.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedPlayer {
+    pub transform: Transform,
+    pub cam_rot: PlyCamRot,
+}
+
+/// The current time in seconds since the Unix epoch.
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+/// Write the world file into the provided world save directory. It's
+/// written to a temporary file first so a crash can't leave a half-written
+/// world file.
+pub fn write_world_file_in(dir: &Path, world_file: &WorldFile) -> Result<(), SaveError> {
+    std::fs::create_dir_all(dir).map_err(|err| SaveError::CreateDir(dir.to_path_buf(), err))?;
+
+    let path = dir.join(WORLD_FILE_NAME);
+    let temp_path = path.with_extension("ron.tmp");
+    let contents = ron::ser::to_string_pretty(world_file, PrettyConfig::default())?;
+    std::fs::write(&temp_path, contents)
+        .and_then(|_| std::fs::rename(&temp_path, &path))
+        .map_err(|err| SaveError::WriteWorldFile(path, err))
+}
+
+/// Read the world file from the provided world save directory, returning
+/// `Ok(None)` if it doesn't have one.
+pub fn read_world_file_in(dir: &Path) -> Result<Option<WorldFile>, SaveError> {
+    let path = dir.join(WORLD_FILE_NAME);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let contents = std::fs::read_to_string(&path)
+        .map_err(|err| SaveError::ReadWorldFile(path.clone(), err))?;
+    let world_file: WorldFile =
+        ron::from_str(&contents).map_err(|err| SaveError::ParseWorldFile(path, err))?;
+    match world_file.format_version > WORLD_FORMAT_VERSION {
+        true => Err(SaveError::UnsupportedWorldVersion(
+            world_file.format_version,
+        )),
+        false => Ok(Some(world_file)),
+    }
+}
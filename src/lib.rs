@@ -0,0 +1,7 @@
+pub mod io;
+pub mod oct_tree;
+pub mod plugin;
+pub mod voxel;
+
+pub const PKG_NAME: &str = env!("CARGO_PKG_NAME");
+pub const PKG_VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -1,10 +1,3 @@
-#![feature(const_option)]
-
-mod io;
-mod oct_tree;
-mod plugin;
-mod voxel;
-
 use bevy::{
     diagnostic::FrameTimeDiagnosticsPlugin,
     log::{Level, LogPlugin},
@@ -12,12 +5,15 @@ use bevy::{
     prelude::*,
 };
 use bevy_rapier3d::prelude::*;
-use control::{input::PlyAction, PrimaryCamera};
+use cjs_whole_new_world::{
+    plugin::{
+        asset::AssetState,
+        control::{controller_2, input::PlyAction, PrimaryCamera},
+        *,
+    },
+    PKG_NAME, PKG_VERSION,
+};
 use leafwing_input_manager::prelude::*;
-use plugin::{control::controller_2, *};
-
-pub const PKG_NAME: &str = env!("CARGO_PKG_NAME");
-pub const PKG_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 fn main() {
     App::new()
@@ -47,6 +43,7 @@ fn main() {
             game_gui::GameGuiPlugin,
             voxel_world::VoxelWorldPlugin,
         ))
+        .add_systems(OnEnter(AssetState::Ready), init_world_system)
         .insert_resource(ClearColor(Color::rgb(0.5, 0.5, 0.8)))
         .insert_resource(AmbientLight {
             brightness: 0.45,
@@ -1,7 +1,7 @@
 //! Implementation of a voxel oct-tree-esque structure to track which chunks
 //! of which LOD level need to be loaded.
 
-use crate::{voxel::Chunk, voxel_world::beef::ChunkState};
+use crate::{plugin::voxel_world::beef::ChunkState, voxel::Chunk};
 use bevy::{
     prelude::*,
     utils::{HashMap, HashSet},
@@ -18,7 +18,7 @@ pub struct LodPos {
 impl LodPos {
     pub fn to_level(&self, level: u8) -> Self {
         let diff = level as i16 - self.level as i16;
-        let diff_pow = 1 << diff.abs() as usize;
+        let diff_pow = 1 << diff.unsigned_abs() as usize;
         match diff {
             // No change
             0 => *self,
@@ -0,0 +1,43 @@
+use crate::voxel::{BiomeTable, BiomeTableError, BiomeTableFile};
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    utils::BoxedFuture,
+};
+use thiserror::Error;
+
+#[derive(Default)]
+pub struct BiomeTableLoader;
+
+#[derive(Debug, Error)]
+pub enum BiomeTableLoaderError {
+    #[error("failed to read biome table: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to parse biome table: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("invalid biome table: {0}")]
+    Table(#[from] BiomeTableError),
+}
+
+impl AssetLoader for BiomeTableLoader {
+    type Asset = BiomeTable;
+    type Error = BiomeTableLoaderError;
+    type Settings = ();
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = vec![];
+            reader.read_to_end(&mut bytes).await?;
+            let file: BiomeTableFile = ron::de::from_bytes(&bytes)?;
+            Ok(BiomeTable::new(file.biomes)?)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["biomes.ron"]
+    }
+}
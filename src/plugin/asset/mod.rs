@@ -1,17 +1,41 @@
+mod biome_table_loader;
+mod voxel_registry_loader;
+mod worldgen_config_loader;
+
+use crate::voxel::{BiomeTable, VoxelRegistry, WorldGenConfig};
 use bevy::prelude::*;
 use bevy_asset_loader::{
     asset_collection::AssetCollection, loading_state::LoadingState, prelude::*,
 };
+use biome_table_loader::BiomeTableLoader;
+use voxel_registry_loader::VoxelRegistryLoader;
+use worldgen_config_loader::WorldGenConfigLoader;
 
 pub struct CwnwAssetPlugin;
 
 impl Plugin for CwnwAssetPlugin {
     fn build(&self, app: &mut App) {
-        app.add_state::<AssetState>().add_loading_state(
-            LoadingState::new(AssetState::Loading)
-                .continue_to_state(AssetState::Ready)
-                .load_collection::<FontAssets>(),
-        );
+        app.init_asset::<VoxelRegistry>()
+            .init_asset_loader::<VoxelRegistryLoader>()
+            .init_asset::<BiomeTable>()
+            .init_asset_loader::<BiomeTableLoader>()
+            .init_asset::<WorldGenConfig>()
+            .init_asset_loader::<WorldGenConfigLoader>()
+            .add_state::<AssetState>()
+            .add_loading_state(
+                LoadingState::new(AssetState::Loading)
+                    .continue_to_state(AssetState::Ready)
+                    .load_collection::<FontAssets>()
+                    .load_collection::<DataAssets>(),
+            )
+            .add_systems(
+                OnEnter(AssetState::Ready),
+                (
+                    insert_voxel_registry_system,
+                    insert_biome_table_system,
+                    insert_worldgen_config_system,
+                ),
+            );
     }
 }
 
@@ -35,3 +59,51 @@ pub struct FontAssets {
     #[asset(path = "fonts/FiraSans/FiraSans-Regular.ttf")]
     pub fira_sans_regular: Handle<Font>,
 }
+
+#[derive(AssetCollection, Resource)]
+pub struct DataAssets {
+    #[asset(path = "data/voxels.registry.ron")]
+    pub voxel_registry: Handle<VoxelRegistry>,
+    #[asset(path = "data/overworld.biomes.ron")]
+    pub biome_table: Handle<BiomeTable>,
+    #[asset(path = "data/default.worldgen.ron")]
+    pub worldgen_config: Handle<WorldGenConfig>,
+}
+
+/// The registry is needed all over the place (including inside async
+/// tasks), so make it available as a resource once it has loaded.
+fn insert_voxel_registry_system(
+    mut commands: Commands,
+    data_assets: Res<DataAssets>,
+    registries: Res<Assets<VoxelRegistry>>,
+) {
+    match registries.get(&data_assets.voxel_registry) {
+        Some(registry) => commands.insert_resource(registry.clone()),
+        None => error!("voxel registry asset is missing after loading finished"),
+    }
+}
+
+/// Same as the registry, the biome table is sent off to generation tasks.
+fn insert_biome_table_system(
+    mut commands: Commands,
+    data_assets: Res<DataAssets>,
+    biome_tables: Res<Assets<BiomeTable>>,
+) {
+    match biome_tables.get(&data_assets.biome_table) {
+        Some(biome_table) => commands.insert_resource(biome_table.clone()),
+        None => error!("biome table asset is missing after loading finished"),
+    }
+}
+
+/// New worlds start with a copy of this config, so it can be tweaked without
+/// a rebuild.
+fn insert_worldgen_config_system(
+    mut commands: Commands,
+    data_assets: Res<DataAssets>,
+    worldgen_configs: Res<Assets<WorldGenConfig>>,
+) {
+    match worldgen_configs.get(&data_assets.worldgen_config) {
+        Some(config) => commands.insert_resource(config.clone()),
+        None => error!("worldgen config asset is missing after loading finished"),
+    }
+}
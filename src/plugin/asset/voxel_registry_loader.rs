@@ -0,0 +1,43 @@
+use crate::voxel::{VoxelRegistry, VoxelRegistryError, VoxelRegistryFile};
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    utils::BoxedFuture,
+};
+use thiserror::Error;
+
+#[derive(Default)]
+pub struct VoxelRegistryLoader;
+
+#[derive(Debug, Error)]
+pub enum VoxelRegistryLoaderError {
+    #[error("failed to read voxel registry: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to parse voxel registry: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("invalid voxel registry: {0}")]
+    Registry(#[from] VoxelRegistryError),
+}
+
+impl AssetLoader for VoxelRegistryLoader {
+    type Asset = VoxelRegistry;
+    type Error = VoxelRegistryLoaderError;
+    type Settings = ();
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = vec![];
+            reader.read_to_end(&mut bytes).await?;
+            let file: VoxelRegistryFile = ron::de::from_bytes(&bytes)?;
+            Ok(VoxelRegistry::new(file.voxels)?)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["registry.ron"]
+    }
+}
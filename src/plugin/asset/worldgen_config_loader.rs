@@ -0,0 +1,40 @@
+use crate::voxel::WorldGenConfig;
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    utils::BoxedFuture,
+};
+use thiserror::Error;
+
+#[derive(Default)]
+pub struct WorldGenConfigLoader;
+
+#[derive(Debug, Error)]
+pub enum WorldGenConfigLoaderError {
+    #[error("failed to read worldgen config: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to parse worldgen config: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+impl AssetLoader for WorldGenConfigLoader {
+    type Asset = WorldGenConfig;
+    type Error = WorldGenConfigLoaderError;
+    type Settings = ();
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut bytes = vec![];
+            reader.read_to_end(&mut bytes).await?;
+            Ok(ron::de::from_bytes(&bytes)?)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["worldgen.ron"]
+    }
+}
@@ -10,7 +10,7 @@ use crate::{
             world_state::WorldState,
         },
     },
-    voxel::{ChunkPos, InChunkPos, Voxel, CHUNK_WIDTH},
+    voxel::{relight_voxel, ChunkPos, InChunkPos, Voxel, VoxelRegistry, CHUNK_WIDTH},
 };
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
@@ -40,20 +40,32 @@ impl Plugin for Controller2ElectricBoogalooPlugin {
 fn modify_block_system(
     mut commands: Commands,
     mut chunks: ResMut<FixedChunkWorld>,
+    registry: Res<VoxelRegistry>,
     look_at: Res<PlayerLookAtRes>,
     camera: Query<&ActionState<PlyAction>>,
 ) {
     if let Ok(ctrl) = camera.get_single() {
         if ctrl.just_pressed(PlyAction::Fire) {
             if let Some(look_at) = &look_at.0 {
+                // Unbreakable voxels stay put
+                if registry
+                    .get(look_at.voxel)
+                    .is_some_and(|def| !def.is_breakable())
+                {
+                    return;
+                }
                 if let Some(LoadedChunk {
                     entity,
                     chunk: Some(chunk),
                     ..
                 }) = chunks.chunks.get_mut(&ChunkPos(look_at.chunk_pos))
                 {
-                    chunk.set(look_at.voxel_pos_in_chunk, Voxel::Air);
+                    chunk.set(look_at.voxel_pos_in_chunk, Voxel::AIR);
                     commands.entity(*entity).insert(DirtyChunk);
+
+                    // Let the light into the hole
+                    let changed = relight_voxel(&mut *chunks, look_at.global_voxel_pos, &registry);
+                    chunks.mark_dirty(&mut commands, changed);
                 }
             }
         }
@@ -207,7 +219,7 @@ fn update_character_controller_rotations(
     if parent.get() == entity {
         let mouse_motion = action.axis_pair(PlyAction::Look).unwrap();
         cam_rot.0 += Vec2::new(1.0, -1.0) * mouse_motion.xy() * controller.rot_speed * PI / 180.0;
-        cam_rot.0.y = cam_rot.0.y.max(-PI / 2.0).min(PI / 2.0);
+        cam_rot.0.y = cam_rot.0.y.clamp(-PI / 2.0, PI / 2.0);
         controller_transform.rotation = Quat::from_axis_angle(Vec3::Y, -cam_rot.0.x);
         camera_transform.rotation = Quat::from_axis_angle(Vec3::X, cam_rot.0.y);
     }
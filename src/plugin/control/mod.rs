@@ -4,6 +4,7 @@ pub mod pause;
 
 use bevy::{prelude::*, window::CursorGrabMode};
 use pause::PauseState;
+use serde::{Deserialize, Serialize};
 
 pub struct PlyControlPlugin;
 
@@ -35,7 +36,7 @@ impl Default for PlyCamControl {
     }
 }
 
-#[derive(Default, Component, Debug)]
+#[derive(Default, Component, Debug, Copy, Clone, Serialize, Deserialize)]
 pub struct PlyCamRot(pub Vec2);
 
 fn on_pause_system(mut windows: Query<&mut Window, With<bevy::window::PrimaryWindow>>) {
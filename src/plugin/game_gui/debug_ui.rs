@@ -1,15 +1,14 @@
 use crate::{
-    init_world_system,
     plugin::{
         asset::{AssetState, FontAssets},
         control::controller_2::{CharControl2, PlayerLookAtRes},
         voxel_world::beef::{
             DIAG_DELETE_REQUIRED, DIAG_DIRTY_CHUNKS, DIAG_GENERATED_CHUNKS, DIAG_GENERATE_REQUIRED,
             DIAG_NON_CULLED_CHUNKS, DIAG_RENDERED_CHUNKS, DIAG_RENDER_REQUIRED,
-            DIAG_VISIBLE_CHUNKS,
+            DIAG_VISIBLE_CHUNKS, DIAG_VOXEL_MEMORY,
         },
     },
-    voxel::{ChunkPos, REGION_WIDTH},
+    voxel::{ChunkPos, VoxelRegistry, REGION_WIDTH},
 };
 use bevy::{
     diagnostic::{Diagnostic, DiagnosticsStore, FrameTimeDiagnosticsPlugin},
@@ -22,17 +21,15 @@ pub struct GameDebugUIPlugin;
 
 impl Plugin for GameDebugUIPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            OnEnter(AssetState::Ready),
-            (init_world_system, init_ui_system),
-        )
-        .add_systems(
-            Update,
-            (
-                update_chunk_info_ui_system,
-                update_ui_system.run_if(on_timer(Duration::from_millis(100))),
-            ),
-        );
+        app.add_systems(OnEnter(AssetState::Ready), init_ui_system)
+            .add_systems(
+                Update,
+                (
+                    update_chunk_info_ui_system,
+                    update_voxel_memory_ui_system,
+                    update_ui_system.run_if(on_timer(Duration::from_millis(100))),
+                ),
+            );
     }
 }
 
@@ -75,6 +72,9 @@ struct VisibleChunksText;
 #[derive(Component)]
 struct NonCulledChunksText;
 
+#[derive(Component)]
+struct VoxelMemoryText;
+
 #[derive(Component)]
 struct PosText;
 
@@ -473,6 +473,28 @@ fn init_ui_system(mut commands: Commands, fonts: Res<FontAssets>) {
                 ]),
                 NonCulledChunksText,
             ));
+
+            cmds.spawn((
+                TextBundle::from_sections([
+                    TextSection::new(
+                        "Loaded voxel memory: ",
+                        TextStyle {
+                            font: fonts.fira_sans_regular.clone(),
+                            font_size,
+                            color: Color::WHITE,
+                        },
+                    ),
+                    TextSection::new(
+                        "0",
+                        TextStyle {
+                            font: fonts.fira_code_bold.clone(),
+                            font_size,
+                            color: Color::YELLOW,
+                        },
+                    ),
+                ]),
+                VoxelMemoryText,
+            ));
         });
 }
 
@@ -480,6 +502,7 @@ fn init_ui_system(mut commands: Commands, fonts: Res<FontAssets>) {
 fn update_ui_system(
     diagnostics: Res<DiagnosticsStore>,
     looking_at: Res<PlayerLookAtRes>,
+    registry: Option<Res<VoxelRegistry>>,
     mut queries: ParamSet<(
         Query<&mut Text, With<FpsText>>,
         Query<&mut Text, With<PosText>>,
@@ -541,7 +564,10 @@ fn update_ui_system(
         text.sections[1].value = looking_at
             .0
             .as_ref()
-            .map(|pla| format!("{:?}", pla.voxel))
+            .map(|pla| match &registry {
+                Some(registry) => registry.name(pla.voxel).to_string(),
+                None => format!("{:?}", pla.voxel),
+            })
             .unwrap_or_else(|| "Nothing".to_string());
     }
 }
@@ -632,3 +658,19 @@ fn update_chunk_info_ui_system(
         }
     }
 }
+
+// The chunk info system's `ParamSet` is already full, so this one gets its own
+// system.
+fn update_voxel_memory_ui_system(
+    diagnostics: Res<DiagnosticsStore>,
+    mut text: Query<&mut Text, With<VoxelMemoryText>>,
+) {
+    let voxel_memory = diagnostics
+        .get(DIAG_VOXEL_MEMORY)
+        .and_then(Diagnostic::value);
+    if let Some(voxel_memory) = voxel_memory {
+        if let Ok(mut text) = text.get_single_mut() {
+            text.sections[1].value = format!("{voxel_memory:.1} KiB");
+        }
+    }
+}
@@ -0,0 +1,497 @@
+use super::{
+    input_text_bundle, label_bundle, make_btn, menu_node, menu_title_text_bundle,
+    menu_wrapper_node, text_input::TextValue, update_state_button, was_button_just_pressed,
+    ActiveMenuButton, MenuState, ToastEvent, BUTTON_BG_INACTIVE, DEFAULT_BACK_COVER_COLOR,
+    FULL_BACK_COVER_COLOR,
+};
+use crate::{
+    io::{unix_now, SaveSummary, SavesDir},
+    plugin::{
+        asset::FontAssets,
+        control::pause::PauseState,
+        voxel_world::{world_info::WorldInfo, world_state::WorldState},
+    },
+};
+use bevy::{prelude::*, ui::FocusPolicy};
+
+pub struct LoadWorldMenuPlugin;
+
+impl Plugin for LoadWorldMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<RefreshLoadWorldMenu>()
+            .add_systems(
+                OnEnter(MenuState::LoadWorldMenu),
+                spawn_load_world_menu_system,
+            )
+            .add_systems(
+                OnExit(MenuState::LoadWorldMenu),
+                despawn_load_world_menu_system,
+            )
+            .add_systems(
+                Update,
+                (
+                    update_state_button::<ReturnToMainMenuButton, _>(
+                        MenuState::LoadWorldMenu,
+                        MenuState::MainMenu,
+                    ),
+                    (
+                        on_pressed_save_button_system,
+                        on_pressed_manage_button_system,
+                        on_pressed_confirm_dialog_system
+                            .run_if(was_button_just_pressed::<ConfirmDialogButton>()),
+                        on_pressed_cancel_dialog_system
+                            .run_if(was_button_just_pressed::<CancelDialogButton>()),
+                        refresh_load_world_menu_system,
+                    )
+                        .chain()
+                        .run_if(in_state(MenuState::LoadWorldMenu)),
+                ),
+            );
+    }
+}
+
+/// Rebuild the menu after the saves have changed.
+#[derive(Event)]
+struct RefreshLoadWorldMenu;
+
+#[derive(Component)]
+struct LoadWorldMenu;
+
+#[derive(Component)]
+struct ReturnToMainMenuButton;
+
+/// A button to load the save at this index in the [`SaveList`].
+#[derive(Component)]
+struct SaveButton(usize);
+
+#[derive(Component)]
+struct RenameSaveButton(usize);
+
+#[derive(Component)]
+struct DuplicateSaveButton(usize);
+
+#[derive(Component)]
+struct DeleteSaveButton(usize);
+
+/// The popup asking the player to confirm an action on the save at this
+/// index in the [`SaveList`].
+#[derive(Component)]
+enum SaveDialog {
+    Rename(usize),
+    Delete(usize),
+}
+
+#[derive(Component)]
+struct ConfirmDialogButton;
+
+#[derive(Component)]
+struct CancelDialogButton;
+
+#[derive(Component)]
+struct RenameValueMarker;
+
+/// The saves shown in the menu, read when the menu is opened.
+#[derive(Resource)]
+struct SaveList(Vec<SaveSummary>);
+
+fn spawn_load_world_menu_system(mut commands: Commands, font_assets: Res<FontAssets>) {
+    spawn_load_world_menu(&mut commands, &font_assets);
+}
+
+fn spawn_load_world_menu(commands: &mut Commands, font_assets: &FontAssets) {
+    let saves = SavesDir::main().list().unwrap_or_else(|err| {
+        error!("failed to list saves: {err}");
+        vec![]
+    });
+
+    // Entire screen node
+    commands
+        .spawn((LoadWorldMenu, menu_wrapper_node(FULL_BACK_COVER_COLOR)))
+        .with_children(|commands| {
+            // Load world menu node
+            commands.spawn(menu_node()).with_children(|commands| {
+                // Menu title text
+                commands.spawn(menu_title_text_bundle(font_assets, "Load World"));
+
+                if saves.is_empty() {
+                    commands.spawn(label_bundle(
+                        &font_assets.fira_sans_regular,
+                        "No worlds yet, go make one!",
+                    ));
+                }
+
+                for (index, save) in saves.iter().enumerate() {
+                    // One row per save, the save itself and then the buttons
+                    // to manage it
+                    commands
+                        .spawn(NodeBundle {
+                            style: Style {
+                                column_gap: Val::Px(10.0),
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|commands| {
+                            make_save_btn(commands, font_assets, index, save);
+                            make_small_btn(
+                                commands,
+                                font_assets,
+                                "Rename",
+                                RenameSaveButton(index),
+                            );
+                            make_small_btn(
+                                commands,
+                                font_assets,
+                                "Copy",
+                                DuplicateSaveButton(index),
+                            );
+                            make_small_btn(
+                                commands,
+                                font_assets,
+                                "Delete",
+                                DeleteSaveButton(index),
+                            );
+                        });
+                }
+
+                make_btn(
+                    commands,
+                    font_assets,
+                    "Back",
+                    Some(ReturnToMainMenuButton),
+                    true,
+                );
+            });
+        });
+
+    commands.insert_resource(SaveList(saves));
+}
+
+/// Like [`make_btn`], but with a second line of smaller text for the save's
+/// details. Saves without a world file can't be loaded.
+fn make_save_btn(
+    commands: &mut ChildBuilder,
+    font_assets: &FontAssets,
+    index: usize,
+    save: &SaveSummary,
+) {
+    let (name, details) = match &save.world_file {
+        Some(world_file) => (
+            world_file.name.as_str(),
+            format!(
+                "Seed {} | Played {} | {}",
+                world_file.seed,
+                format_time_ago(world_file.last_played),
+                format_size(save.size)
+            ),
+        ),
+        None => (
+            save.dir_name.as_str(),
+            format!("Missing world file | {}", format_size(save.size)),
+        ),
+    };
+
+    let mut e = commands.spawn((
+        SaveButton(index),
+        ButtonBundle {
+            style: Style {
+                padding: UiRect::axes(Val::Px(10.0), Val::Px(10.0)),
+                flex_direction: FlexDirection::Column,
+                flex_grow: 1.0,
+                ..default()
+            },
+            background_color: BUTTON_BG_INACTIVE.into(),
+            ..default()
+        },
+    ));
+    if save.world_file.is_some() {
+        e.insert(ActiveMenuButton);
+    }
+    e.with_children(|commands| {
+        commands.spawn(TextBundle::from_sections([
+            TextSection::new(
+                format!("{name}\n"),
+                TextStyle {
+                    font: Handle::clone(&font_assets.fira_sans_regular),
+                    font_size: 26.0,
+                    color: Color::BLACK,
+                },
+            ),
+            TextSection::new(
+                details,
+                TextStyle {
+                    font: Handle::clone(&font_assets.fira_sans_regular),
+                    font_size: 16.0,
+                    color: Color::DARK_GRAY,
+                },
+            ),
+        ]));
+    });
+}
+
+/// A compact version of [`make_btn`] for the save management actions.
+fn make_small_btn(
+    commands: &mut ChildBuilder,
+    font_assets: &FontAssets,
+    text: &str,
+    bundle: impl Bundle,
+) {
+    commands
+        .spawn((
+            bundle,
+            ActiveMenuButton,
+            ButtonBundle {
+                style: Style {
+                    padding: UiRect::axes(Val::Px(8.0), Val::Px(8.0)),
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: BUTTON_BG_INACTIVE.into(),
+                ..default()
+            },
+        ))
+        .with_children(|commands| {
+            commands.spawn(TextBundle::from_section(
+                text,
+                TextStyle {
+                    font: Handle::clone(&font_assets.fira_sans_regular),
+                    font_size: 16.0,
+                    color: Color::BLACK,
+                },
+            ));
+        });
+}
+
+fn spawn_dialog(
+    commands: &mut Commands,
+    font_assets: &FontAssets,
+    dialog: SaveDialog,
+    world_name: &str,
+) {
+    let (title, message, confirm_text) = match dialog {
+        SaveDialog::Rename(_) => (
+            "Rename World",
+            format!("New name for \"{world_name}\":"),
+            "Rename",
+        ),
+        SaveDialog::Delete(_) => (
+            "Delete World",
+            format!("Delete \"{world_name}\" forever? This can't be undone!"),
+            "Delete",
+        ),
+    };
+    let is_rename = matches!(dialog, SaveDialog::Rename(_));
+
+    // Cover the whole menu so it can't be clicked while the dialog is open
+    let mut wrapper = menu_wrapper_node(DEFAULT_BACK_COVER_COLOR);
+    wrapper.focus_policy = FocusPolicy::Block;
+    wrapper.z_index = ZIndex::Global(10);
+
+    commands.spawn((dialog, wrapper)).with_children(|commands| {
+        commands.spawn(menu_node()).with_children(|commands| {
+            commands.spawn(menu_title_text_bundle(font_assets, title));
+            commands.spawn(label_bundle(&font_assets.fira_sans_regular, message));
+            if is_rename {
+                commands.spawn((
+                    RenameValueMarker,
+                    input_text_bundle(&font_assets.fira_sans_regular),
+                ));
+            }
+            make_btn(
+                commands,
+                font_assets,
+                confirm_text,
+                Some(ConfirmDialogButton),
+                true,
+            );
+            make_btn(
+                commands,
+                font_assets,
+                "Cancel",
+                Some(CancelDialogButton),
+                true,
+            );
+        });
+    });
+}
+
+#[allow(clippy::type_complexity)]
+fn despawn_load_world_menu_system(
+    mut commands: Commands,
+    query: Query<Entity, Or<(With<LoadWorldMenu>, With<SaveDialog>)>>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<SaveList>();
+}
+
+#[allow(clippy::type_complexity)]
+fn refresh_load_world_menu_system(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    mut refresh_events: EventReader<RefreshLoadWorldMenu>,
+    query: Query<Entity, Or<(With<LoadWorldMenu>, With<SaveDialog>)>>,
+) {
+    if refresh_events.is_empty() {
+        return;
+    }
+    refresh_events.clear();
+
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_load_world_menu(&mut commands, &font_assets);
+}
+
+#[allow(clippy::type_complexity)]
+fn on_pressed_save_button_system(
+    mut commands: Commands,
+    saves: Res<SaveList>,
+    mut next_menu_state: ResMut<NextState<MenuState>>,
+    mut next_pause_state: ResMut<NextState<PauseState>>,
+    mut next_world_state: ResMut<NextState<WorldState>>,
+    buttons: Query<(&Interaction, &SaveButton), (Changed<Interaction>, With<ActiveMenuButton>)>,
+) {
+    let Some((dir_name, mut world_file)) = buttons
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Pressed)
+        .and_then(|(_, SaveButton(index))| saves.0.get(*index))
+        .and_then(|save| Some((save.dir_name.clone(), save.world_file.clone()?)))
+    else {
+        return;
+    };
+
+    // The save lives wherever it was listed from, even if the world file
+    // was copied from somewhere else and has another name
+    world_file.name = dir_name;
+
+    commands.insert_resource(WorldInfo::from_file(world_file));
+
+    next_menu_state.set(MenuState::LoadingScreen);
+    next_pause_state.set(PauseState::Playing);
+    next_world_state.set(WorldState::LoadingStartArea);
+
+    debug!("updating world state to loading");
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn on_pressed_manage_button_system(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    saves: Res<SaveList>,
+    mut toasts: EventWriter<ToastEvent>,
+    mut refresh_events: EventWriter<RefreshLoadWorldMenu>,
+    rename_buttons: Query<(&Interaction, &RenameSaveButton), Changed<Interaction>>,
+    duplicate_buttons: Query<(&Interaction, &DuplicateSaveButton), Changed<Interaction>>,
+    delete_buttons: Query<(&Interaction, &DeleteSaveButton), Changed<Interaction>>,
+) {
+    let pressed = |interaction: &Interaction| *interaction == Interaction::Pressed;
+    let save_name = |index: usize| saves.0.get(index).map(|save| save.dir_name.as_str());
+
+    for (_, RenameSaveButton(index)) in rename_buttons.iter().filter(|(i, _)| pressed(i)) {
+        if let Some(name) = save_name(*index) {
+            spawn_dialog(
+                &mut commands,
+                &font_assets,
+                SaveDialog::Rename(*index),
+                name,
+            );
+        }
+    }
+
+    for (_, DeleteSaveButton(index)) in delete_buttons.iter().filter(|(i, _)| pressed(i)) {
+        if let Some(name) = save_name(*index) {
+            spawn_dialog(
+                &mut commands,
+                &font_assets,
+                SaveDialog::Delete(*index),
+                name,
+            );
+        }
+    }
+
+    // Copying doesn't need confirmation, the copy gets a free name
+    for (_, DuplicateSaveButton(index)) in duplicate_buttons.iter().filter(|(i, _)| pressed(i)) {
+        if let Some(name) = save_name(*index) {
+            let saves_dir = SavesDir::main();
+            if let Err(err) = saves_dir.duplicate(name, &saves_dir.copy_name(name)) {
+                error!("failed to copy world \"{name}\": {err}");
+                toasts.send(ToastEvent(format!("Failed to copy world: {err}")));
+            }
+            refresh_events.send(RefreshLoadWorldMenu);
+        }
+    }
+}
+
+fn on_pressed_confirm_dialog_system(
+    mut commands: Commands,
+    saves: Res<SaveList>,
+    mut toasts: EventWriter<ToastEvent>,
+    mut refresh_events: EventWriter<RefreshLoadWorldMenu>,
+    dialog: Query<(Entity, &SaveDialog)>,
+    rename_text: Query<&TextValue, With<RenameValueMarker>>,
+) {
+    let Ok((entity, dialog)) = dialog.get_single() else {
+        return;
+    };
+
+    let saves_dir = SavesDir::main();
+    let result = match *dialog {
+        SaveDialog::Rename(index) => {
+            let new_name = rename_text
+                .get_single()
+                .map(|text| text.get().trim())
+                .unwrap_or_default();
+            saves.0.get(index).map(|save| {
+                saves_dir
+                    .rename(&save.dir_name, new_name)
+                    .map_err(|err| format!("Failed to rename world: {err}"))
+            })
+        }
+        SaveDialog::Delete(index) => saves.0.get(index).map(|save| {
+            saves_dir
+                .delete(&save.dir_name)
+                .map_err(|err| format!("Failed to delete world: {err}"))
+        }),
+    };
+
+    match result {
+        Some(Err(message)) => {
+            // Leave the dialog open so the player can try another name
+            error!("{message}");
+            toasts.send(ToastEvent(message));
+        }
+        _ => {
+            commands.entity(entity).despawn_recursive();
+            refresh_events.send(RefreshLoadWorldMenu);
+        }
+    }
+}
+
+fn on_pressed_cancel_dialog_system(
+    mut commands: Commands,
+    dialog: Query<Entity, With<SaveDialog>>,
+) {
+    for entity in dialog.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn format_time_ago(unix_time: u64) -> String {
+    let seconds = unix_now().saturating_sub(unix_time);
+    match seconds {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{} min ago", seconds / 60),
+        3600..=86399 => format!("{} h ago", seconds / 3600),
+        _ => format!("{} days ago", seconds / 86400),
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{bytes} B"),
+        1024..=1048575 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / (1024.0 * 1024.0)),
+    }
+}
@@ -18,6 +18,10 @@ impl Plugin for MainMenuPlugin {
                         MenuState::MainMenu,
                         MenuState::NewWorldMenu,
                     ),
+                    update_state_button::<LoadWorldButton, _>(
+                        MenuState::MainMenu,
+                        MenuState::LoadWorldMenu,
+                    ),
                     update_event_button::<ExitButton, _>(AppExit)
                         .run_if(in_state(MenuState::MainMenu)),
                 ),
@@ -63,7 +67,7 @@ fn spawn_game_menu_system(mut commands: Commands, font_assets: Res<FontAssets>)
                     &font_assets,
                     "Load World",
                     Some(LoadWorldButton),
-                    false,
+                    true,
                 );
 
                 make_btn(
@@ -1,17 +1,21 @@
 mod debug_ui;
+mod load_world;
 mod loading_screen;
 mod main_menu;
 mod new_world;
 mod pause_menu;
 mod pause_settings_menu;
 pub mod text_input;
+mod toast;
 
 pub use debug_ui::*;
+pub use load_world::*;
 pub use loading_screen::*;
 pub use main_menu::*;
 pub use new_world::*;
 pub use pause_menu::*;
 pub use pause_settings_menu::*;
+pub use toast::*;
 
 use crate::plugin::{
     asset::{AssetState, FontAssets},
@@ -42,9 +46,11 @@ impl Plugin for GameGuiPlugin {
                 GameDebugUIPlugin,
                 MainMenuPlugin,
                 NewWorldMenuPlugin,
+                LoadWorldMenuPlugin,
                 LoadingScreenPlugin,
                 PauseMenuPlugin,
                 PauseSettingsMenuPlugin,
+                ToastPlugin,
             ))
             .add_systems(
                 OnEnter(AssetState::Ready),
@@ -90,6 +96,7 @@ pub enum MenuState {
     None,
     MainMenu,
     NewWorldMenu,
+    LoadWorldMenu,
     LoadingScreen,
     PauseSettings,
     Paused,
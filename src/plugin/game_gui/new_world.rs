@@ -1,13 +1,17 @@
 use super::{
     input_text_bundle, label_bundle, make_btn, menu_node, menu_title_text_bundle,
     menu_wrapper_node, update_state_button, was_button_just_pressed, ActiveMenuButton, MenuState,
-    FULL_BACK_COVER_COLOR,
+    ToastEvent, FULL_BACK_COVER_COLOR,
 };
-use crate::plugin::{
-    asset::FontAssets,
-    control::pause::PauseState,
-    game_gui::text_input::TextValue,
-    voxel_world::{world_info::WorldInfo, world_state::WorldState},
+use crate::{
+    io::SavesDir,
+    plugin::{
+        asset::FontAssets,
+        control::pause::PauseState,
+        game_gui::text_input::TextValue,
+        voxel_world::{world_info::WorldInfo, world_state::WorldState},
+    },
+    voxel::{GeneratorPreset, WorldGenConfig},
 };
 use bevy::prelude::*;
 use stable_hash::fast_stable_hash;
@@ -34,6 +38,7 @@ impl Plugin for NewWorldMenuPlugin {
                 on_pressed_create_button_system
                     .run_if(was_button_just_pressed::<CreateWorldButton>()),
                 toggle_new_world_button_system,
+                cycle_generator_system.run_if(was_button_just_pressed::<GeneratorPickerButton>()),
             ),
         );
     }
@@ -54,20 +59,52 @@ struct WorldSeedValueMarker;
 #[derive(Component)]
 struct ReturnToMainMenuButton;
 
+/// Cycles through the generator presets when pressed. Holds the index of the
+/// currently picked one in [`GeneratorPreset::all`].
+#[derive(Component, Default)]
+struct GeneratorPickerButton(usize);
+
+fn generator_button_text(index: usize) -> String {
+    let presets = GeneratorPreset::all();
+    format!(
+        "Generator: {}",
+        presets[index % presets.len()].display_name()
+    )
+}
+
+fn cycle_generator_system(
+    mut picker: Query<(&mut GeneratorPickerButton, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    let Ok((mut picker, children)) = picker.get_single_mut() else {
+        return;
+    };
+    picker.0 = (picker.0 + 1) % GeneratorPreset::all().len();
+    for child in children.iter() {
+        if let Ok(mut text) = texts.get_mut(*child) {
+            text.sections[0].value = generator_button_text(picker.0);
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
 fn toggle_new_world_button_system(
     mut commands: Commands,
-    input: Query<&TextValue, With<WorldNameValueMarker>>,
+    input: Query<&TextValue, (With<WorldNameValueMarker>, Changed<TextValue>)>,
     button: Query<(Entity, Option<&ActiveMenuButton>), With<CreateWorldButton>>,
 ) {
     if let (Ok(input), Ok((btn_entity, active))) = (
         input.get_single().map(|i| i.get().trim()),
         button.get_single(),
     ) {
-        match (active.is_some(), input.is_empty()) {
-            (true, true) => {
+        // A taken name would load the existing world instead of making a new
+        // one
+        let valid = SavesDir::main().check_new_name(input).is_ok();
+        match (active.is_some(), valid) {
+            (true, false) => {
                 commands.entity(btn_entity).remove::<ActiveMenuButton>();
             }
-            (false, false) => {
+            (false, true) => {
                 commands.entity(btn_entity).insert(ActiveMenuButton);
             }
             _ => {}
@@ -106,6 +143,15 @@ fn spawn_new_world_menu_system(mut commands: Commands, font_assets: Res<FontAsse
                     input_text_bundle(&font_assets.fira_code_regular),
                 ));
 
+                // Generator picker
+                make_btn(
+                    commands,
+                    &font_assets,
+                    generator_button_text(0),
+                    Some(GeneratorPickerButton::default()),
+                    true,
+                );
+
                 // Buttons
                 make_btn(
                     commands,
@@ -131,6 +177,7 @@ fn despawn_new_world_menu_system(mut commands: Commands, query: Query<Entity, Wi
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn on_pressed_create_button_system(
     mut commands: Commands,
     mut next_menu_state: ResMut<NextState<MenuState>>,
@@ -138,6 +185,9 @@ fn on_pressed_create_button_system(
     mut next_world_state: ResMut<NextState<WorldState>>,
     world_name_text: Query<&TextValue, With<WorldNameValueMarker>>,
     world_seed_text: Query<&TextValue, With<WorldSeedValueMarker>>,
+    generator_picker: Query<&GeneratorPickerButton>,
+    worldgen: Res<WorldGenConfig>,
+    mut toasts: EventWriter<ToastEvent>,
 ) {
     let Ok(name) = world_name_text
         .get_single()
@@ -145,6 +195,11 @@ fn on_pressed_create_button_system(
     else {
         return;
     };
+    if let Err(err) = SavesDir::main().check_new_name(&name) {
+        error!("can't create world: {err}");
+        toasts.send(ToastEvent(format!("Can't create world: {err}")));
+        return;
+    }
 
     // If seed is empty, 42069 is the default value! At some point make this
     // random!
@@ -155,7 +210,13 @@ fn on_pressed_create_button_system(
             .unwrap_or("42069"),
     ) as u32;
 
-    commands.insert_resource(WorldInfo::new(name, seed));
+    let generator = generator_picker
+        .get_single()
+        .ok()
+        .and_then(|picker| GeneratorPreset::all().into_iter().nth(picker.0))
+        .unwrap_or_default();
+
+    commands.insert_resource(WorldInfo::new(name, seed, generator, worldgen.clone()));
 
     next_menu_state.set(MenuState::LoadingScreen);
     next_pause_state.set(PauseState::Playing);
@@ -44,7 +44,7 @@ const CURSOR_HANDLE: Handle<Font> = Handle::weak_from_u128(10482756907980398621)
 /// Examples:
 /// ```rust
 /// # use bevy::prelude::*;
-/// use bevy_simple_text_input::TextInputBundle;
+/// use cjs_whole_new_world::plugin::game_gui::text_input::TextInputBundle;
 /// fn setup(mut commands: Commands) {
 ///     commands.spawn((NodeBundle::default(), TextInputBundle::default()));
 /// }
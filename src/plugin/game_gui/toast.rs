@@ -0,0 +1,120 @@
+use crate::plugin::{
+    asset::{AssetState, FontAssets},
+    voxel_world::region_saver::SaveErrorEvent,
+};
+use bevy::prelude::*;
+
+pub const TOAST_DURATION_SECS: f32 = 5.0;
+pub const TOAST_BG_COLOR: Color = Color::rgba(0.5, 0.1, 0.1, 0.85);
+
+pub struct ToastPlugin;
+
+impl Plugin for ToastPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ToastEvent>()
+            .add_systems(OnEnter(AssetState::Ready), spawn_toast_container_system)
+            .add_systems(
+                Update,
+                (
+                    save_error_toast_system,
+                    spawn_toasts_system,
+                    update_toasts_system,
+                )
+                    .chain()
+                    .run_if(in_state(AssetState::Ready)),
+            );
+    }
+}
+
+/// Send this to briefly show a message in the corner of the screen.
+#[derive(Event)]
+pub struct ToastEvent(pub String);
+
+/// The node in the corner of the screen that toasts are stacked in.
+#[derive(Component)]
+struct ToastContainer;
+
+/// A message that gets despawned once its timer finishes.
+#[derive(Component)]
+struct Toast(Timer);
+
+fn spawn_toast_container_system(mut commands: Commands) {
+    commands.spawn((
+        ToastContainer,
+        NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                right: Val::Px(20.0),
+                bottom: Val::Px(20.0),
+                max_width: Val::Percent(40.0),
+                flex_direction: FlexDirection::ColumnReverse,
+                row_gap: Val::Px(10.0),
+                ..default()
+            },
+            // Show above the menus
+            z_index: ZIndex::Global(100),
+            ..default()
+        },
+    ));
+}
+
+fn save_error_toast_system(
+    mut save_errors: EventReader<SaveErrorEvent>,
+    mut toasts: EventWriter<ToastEvent>,
+) {
+    toasts.send_batch(
+        save_errors
+            .read()
+            .map(|SaveErrorEvent(err)| ToastEvent(format!("Failed to save world: {err}"))),
+    );
+}
+
+fn spawn_toasts_system(
+    mut commands: Commands,
+    font_assets: Res<FontAssets>,
+    mut toasts: EventReader<ToastEvent>,
+    container: Query<Entity, With<ToastContainer>>,
+) {
+    let Ok(container) = container.get_single() else {
+        return;
+    };
+
+    for ToastEvent(message) in toasts.read() {
+        commands.entity(container).with_children(|commands| {
+            commands
+                .spawn((
+                    Toast(Timer::from_seconds(TOAST_DURATION_SECS, TimerMode::Once)),
+                    NodeBundle {
+                        style: Style {
+                            padding: UiRect::all(Val::Px(10.0)),
+                            ..default()
+                        },
+                        background_color: TOAST_BG_COLOR.into(),
+                        ..default()
+                    },
+                ))
+                .with_children(|commands| {
+                    commands.spawn(TextBundle::from_section(
+                        message,
+                        TextStyle {
+                            font: Handle::clone(&font_assets.fira_sans_regular),
+                            font_size: 18.0,
+                            color: Color::WHITE,
+                        },
+                    ));
+                });
+        });
+    }
+}
+
+fn update_toasts_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut toasts: Query<(Entity, &mut Toast)>,
+) {
+    for (entity, mut toast) in toasts.iter_mut() {
+        if toast.0.tick(time.delta()).finished() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
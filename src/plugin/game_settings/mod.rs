@@ -11,10 +11,14 @@ impl Plugin for GameSettingsPlugin {
 #[derive(Resource)]
 pub struct GameSettings {
     pub load_radius: u32,
+    pub physics_radius: u32,
 }
 
 impl Default for GameSettings {
     fn default() -> Self {
-        Self { load_radius: 4 }
+        Self {
+            load_radius: 4,
+            physics_radius: 2,
+        }
     }
 }
@@ -1,24 +1,28 @@
 use crate::{
+    io::SaveError,
     plugin::{
         control::controller_2::CharControl2,
         game_settings::GameSettings,
         voxel_world::{
-            chunk_loader::ChunkLoader, region_saver::RegionHandlerRes,
-            voxel_material::ChunkMaterialRes, world_info::WorldInfo,
+            chunk_loader::ChunkLoader,
+            region_saver::{RegionHandlerRes, SaveErrorEvent},
+            voxel_material::ChunkMaterialRes,
+            world_info::WorldInfo,
         },
     },
     voxel::{
-        world_noise::{Chunk2dNoiseValues, WorldNoiseSettings},
-        Chunk, ChunkPos, InRegionChunkPos, NeighborChunkSlices, CHUNK_WIDTH, SLICE_DIRECTIONS,
+        apply_writes, generate_chunk_with, generate_collider, light_chunk, relight_voxel,
+        spread_into_chunk, Chunk, ChunkMap, ChunkMeshes, ChunkPos, ColumnNoiseCache, MeshLayer,
+        MeshNeighbors, PendingWrite, PendingWrites, RegionHandler, VoxelPos, VoxelRegistry,
+        WorldGeneratorRes, SLICE_DIRECTIONS,
     },
 };
 use bevy::{
     diagnostic::{Diagnostic, DiagnosticId, Diagnostics, RegisterDiagnostic},
     ecs::system::EntityCommands,
     prelude::*,
-    render::primitives::Aabb,
     tasks::{block_on, AsyncComputeTaskPool, Task},
-    utils::HashMap,
+    utils::{HashMap, HashSet},
 };
 use bevy_rapier3d::{dynamics::RigidBody, prelude::Collider};
 use futures_lite::future::poll_once;
@@ -37,6 +41,7 @@ pub const DIAG_RENDERED_CHUNKS: DiagnosticId =
 pub const DIAG_VISIBLE_CHUNKS: DiagnosticId = DiagnosticId::from_u128(71159199863847276575201272);
 pub const DIAG_DIRTY_CHUNKS: DiagnosticId = DiagnosticId::from_u128(1071412727699475159529421);
 pub const DIAG_NON_CULLED_CHUNKS: DiagnosticId = DiagnosticId::from_u128(1181181887682219941);
+pub const DIAG_VOXEL_MEMORY: DiagnosticId = DiagnosticId::from_u128(8436120775913042781150923);
 
 pub const MAX_RENDERS_PER_FRAME: usize = 1;
 
@@ -75,6 +80,9 @@ impl Plugin for BeefPlugin {
                 "non_culled_chunks",
                 2,
             ))
+            .register_diagnostic(
+                Diagnostic::new(DIAG_VOXEL_MEMORY, "voxel_memory", 2).with_suffix("KiB"),
+            )
             // Update systems
             .add_systems(
                 Update,
@@ -86,10 +94,13 @@ impl Plugin for BeefPlugin {
                         update_diagnostics,
                         start_loading,
                         check_queue,
+                        update_colliders,
+                        collect_colliders,
                     )
                         .chain()
                         .run_if(resource_exists::<FixedChunkWorld>())
-                        .run_if(resource_exists::<WorldNoiseSettings>()),
+                        .run_if(resource_exists::<WorldGeneratorRes>())
+                        .run_if(resource_exists::<VoxelRegistry>()),
                     update_loader_radius.run_if(resource_changed::<GameSettings>()),
                 ),
             );
@@ -98,7 +109,11 @@ impl Plugin for BeefPlugin {
 
 // TODO: ALLOW MARKING INDIVIDUAL SIDES AS DIRTY
 /// System to check for any chunks that need their edges updated.
-fn check_dirty_edges(mut commands: Commands, mut chunk_world: ResMut<FixedChunkWorld>) {
+fn check_dirty_edges(
+    mut commands: Commands,
+    registry: Res<VoxelRegistry>,
+    mut chunk_world: ResMut<FixedChunkWorld>,
+) {
     let mut dirty_edge_chunks = vec![];
 
     // Loop through all currently loaded chunks
@@ -107,7 +122,7 @@ fn check_dirty_edges(mut commands: Commands, mut chunk_world: ResMut<FixedChunkW
         if let Some(chunk) = &mut loaded_chunk.chunk {
             // Update edges
             if chunk.edges_dirty {
-                chunk.update_edge_slice_bits();
+                chunk.update_edge_slice_bits(&registry);
                 dirty_edge_chunks.push(*pos);
             }
         }
@@ -137,18 +152,25 @@ fn update_diagnostics(
     mut diagnostics: Diagnostics,
     chunk_world: Res<FixedChunkWorld>,
     dirty_chunks: Query<(), With<DirtyChunk>>,
-    visible_mesh_chunks: Query<&ViewVisibility, (With<ChunkEntity>, With<Handle<Mesh>>)>,
+    sub_meshes: Query<(&ViewVisibility, &Parent), With<ChunkSubMesh>>,
 ) {
     let mut generating_count = 0;
     let mut rendering_count = 0;
     let mut generated_count = 0;
     let mut rendered_count = 0;
     let dirty_count = dirty_chunks.iter().count();
-    let visible_count = visible_mesh_chunks
-        .iter()
-        .map(|v| v.get())
-        .collect::<Vec<_>>();
-    let non_culled_count = visible_count.iter().filter(|b| **b).count();
+    // A chunk isn't culled if any of its sub-meshes aren't
+    let mut visible_count = HashMap::<Entity, bool>::new();
+    for (visibility, parent) in sub_meshes.iter() {
+        *visible_count.entry(parent.get()).or_default() |= visibility.get();
+    }
+    let non_culled_count = visible_count.values().filter(|b| **b).count();
+    let voxel_memory = chunk_world
+        .chunks
+        .values()
+        .filter_map(|loaded_chunk| loaded_chunk.chunk.as_ref())
+        .map(|chunk| chunk.voxels.memory_usage() + chunk.light.memory_usage())
+        .sum::<usize>();
 
     for state in chunk_world.chunks.values().map(|chunk| chunk.state) {
         match state {
@@ -167,6 +189,7 @@ fn update_diagnostics(
     diagnostics.add_measurement(DIAG_DIRTY_CHUNKS, || dirty_count as f64);
     diagnostics.add_measurement(DIAG_VISIBLE_CHUNKS, || visible_count.len() as f64);
     diagnostics.add_measurement(DIAG_NON_CULLED_CHUNKS, || non_culled_count as f64);
+    diagnostics.add_measurement(DIAG_VOXEL_MEMORY, || voxel_memory as f64 / 1024.0);
 }
 
 /// System to perform immediate updates on chunks currently marked as dirty.
@@ -176,19 +199,23 @@ fn update_dirty_chunks_system(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     material: Res<ChunkMaterialRes>,
-    chunk_world: Res<FixedChunkWorld>,
+    registry: Res<VoxelRegistry>,
+    mut chunk_world: ResMut<FixedChunkWorld>,
     dirty_chunks: Query<(Entity, &ChunkEntity), With<DirtyChunk>>,
 ) {
     for (entity, chunk_pos) in dirty_chunks
         .iter()
         .map(|(entity, ChunkEntity(pos))| (entity, *pos))
     {
+        let mut rebuilt_collider = false;
+
         // Check if this dirty chunk is already generated. Otherwise, not sure how we got here.
         // Also retrieve this chunk's neighbors to render this one
         if let (
             Some(LoadedChunk {
                 state: ChunkState::Rendered,
                 chunk: Some(chunk_voxels),
+                collider,
                 ..
             }),
             Some(neighbors),
@@ -200,21 +227,28 @@ fn update_dirty_chunks_system(
             let mut cmds = commands.entity(entity);
             // Remove the dirty marker
             cmds.remove::<DirtyChunk>();
-            // Remove the marker for chunks that do *not* need to be saved
-            // Generate the mesh
-            if let Some((collider, mesh)) = crate::voxel::generate_mesh(chunk_voxels, neighbors) {
-                // Insert the mesh bundle; this is the same function that is
-                // called when a chunk has finished rendering asynchronously,
-                // in the case that this chunk previously did not have a mesh;
-                // in that case, this entity would not have a material handle,
-                // so we just insert all of this just in case.
-                make_mesh_bundle(&mut cmds, chunk_pos, &mut meshes, &material, collider, mesh);
-            } else {
-                // If the mesh generation returned None, it means the mesh is
-                // empty, so we can go ahead and remove any mesh handle that
-                // may or may not exist on this entity to ensure removing the
-                // last voxel in a chunk doesn't just leave the voxel ghost.
-                cmds.remove::<Handle<Mesh>>();
+            // Chunks with a collider need it rebuilt right away too, or you
+            // could walk into the hole you just dug. Any collider still being
+            // built is already out of date.
+            if *collider != ColliderState::None {
+                cmds.remove::<ColliderTask>();
+                match generate_collider(chunk_voxels, &neighbors, &registry) {
+                    Some(collider) => cmds.insert(collider),
+                    None => cmds.remove::<Collider>(),
+                };
+                rebuilt_collider = true;
+            }
+            // Generate the mesh and insert it; this is the same function
+            // that is called when a chunk has finished rendering
+            // asynchronously, and it also takes care of removing meshes that
+            // are now empty.
+            let chunk_meshes = crate::voxel::generate_mesh(chunk_voxels, neighbors, &registry);
+            make_mesh_bundle(&mut cmds, &mut meshes, &material, chunk_meshes);
+        }
+
+        if rebuilt_collider {
+            if let Some(loaded_chunk) = chunk_world.chunks.get_mut(&ChunkPos(chunk_pos)) {
+                loaded_chunk.collider = ColliderState::Built;
             }
         }
     }
@@ -232,29 +266,32 @@ fn update_loader_states(
     // If the settings have been changed, check for state changes
     if game_settings.is_changed() {
         // Only one chunk loader for now
-        for (loader_pos, ChunkLoader { radius }) in loaders.iter() {
+        for (loader_pos, ChunkLoader { radius, .. }) in loaders.iter() {
             chunks.update_needed_chunk_states(&mut commands, *loader_pos, *radius as usize);
         }
     } else {
         // If the settings haven't been changed, check for changed loader
         // positions.
-        for (loader_pos, ChunkLoader { radius }) in changed_loaders.iter() {
+        for (loader_pos, ChunkLoader { radius, .. }) in changed_loaders.iter() {
             chunks.update_needed_chunk_states(&mut commands, *loader_pos, *radius as usize);
         }
     }
 }
 
 /// System to queue loading for necessary chunks.
+#[allow(clippy::too_many_arguments)]
 fn start_loading(
     mut diagnostics: Diagnostics,
     mut commands: Commands,
     world_info: Res<WorldInfo>,
     mut chunks: ResMut<FixedChunkWorld>,
     region_handler: Res<RegionHandlerRes>,
-    noise: Res<WorldNoiseSettings>,
+    generator: Res<WorldGeneratorRes>,
+    registry: Res<VoxelRegistry>,
+    mut save_errors: EventWriter<SaveErrorEvent>,
     loaders: Query<(&ChunkPos, &ChunkLoader)>,
 ) {
-    if let Ok((loader_pos, ChunkLoader { radius })) = loaders.get_single() {
+    if let Ok((loader_pos, ChunkLoader { radius, .. })) = loaders.get_single() {
         // Determine which chunks have states that need to change
         let state_changes = chunks.required_state_changes(*loader_pos, *radius as usize);
         // Start executing the state changes
@@ -263,18 +300,24 @@ fn start_loading(
             &mut commands,
             world_info.name(),
             &region_handler,
-            &noise,
+            &generator,
+            &registry,
+            &mut save_errors,
             state_changes,
         );
     }
 }
 
 /// System to check for any finished async generation/render tasks.
+#[allow(clippy::too_many_arguments)]
 fn check_queue(
     mut commands: Commands,
     material: Res<ChunkMaterialRes>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut chunks: ResMut<FixedChunkWorld>,
+    region_handler: Res<RegionHandlerRes>,
+    registry: Res<VoxelRegistry>,
+    mut save_errors: EventWriter<SaveErrorEvent>,
     mut generate_query: Query<(Entity, &mut GenerateTask), Without<RenderTask>>,
     mut render_query: Query<(Entity, &mut RenderTask), Without<GenerateTask>>,
     loader: Query<&ChunkPos, With<ChunkLoader>>,
@@ -283,23 +326,82 @@ fn check_queue(
         &mut commands,
         &material,
         &mut meshes,
+        &region_handler,
+        &registry,
+        &mut save_errors,
         &mut generate_query,
         &mut render_query,
         &loader,
     );
 }
 
+/// System to start building colliders for chunks near a chunk loader, and
+/// to take them away from chunks that aren't anymore. Nothing ever touches
+/// most of the rendered chunks, so there's no point giving them colliders.
+fn update_colliders(
+    mut commands: Commands,
+    mut chunks: ResMut<FixedChunkWorld>,
+    registry: Res<VoxelRegistry>,
+    loaders: Query<(&ChunkPos, &ChunkLoader)>,
+) {
+    let loaders = loaders
+        .iter()
+        .map(|(pos, loader)| (*pos, loader.physics_radius))
+        .collect::<Vec<_>>();
+    chunks.update_colliders(&mut commands, &registry, &loaders);
+}
+
+/// System to insert the colliders from any finished collider tasks.
+fn collect_colliders(
+    mut commands: Commands,
+    mut chunks: ResMut<FixedChunkWorld>,
+    mut collider_query: Query<(Entity, &mut ColliderTask)>,
+) {
+    for (entity, mut task) in collider_query.iter_mut() {
+        let Some(collider) = block_on(poll_once(&mut task.1)) else {
+            continue;
+        };
+
+        let mut e = commands.entity(entity);
+        e.remove::<ColliderTask>();
+        match collider {
+            Some(collider) => e.insert(collider),
+            None => e.remove::<Collider>(),
+        };
+        if let Some(loaded_chunk) = chunks.chunks.get_mut(&ChunkPos(task.0)) {
+            loaded_chunk.collider = ColliderState::Built;
+        }
+    }
+}
+
 #[derive(Debug, Component, Copy, Clone, Eq, PartialEq)]
 pub struct ChunkEntity(pub IVec3);
 
+/// One of a chunk's meshes, spawned as a child of its [`ChunkEntity`] so
+/// each layer can have its own material.
+#[derive(Debug, Component, Copy, Clone, Eq, PartialEq)]
+pub struct ChunkSubMesh(pub MeshLayer);
+
 #[derive(Component)]
 pub struct DirtyChunk;
 
+/// The output of a generation task.
+struct GeneratedChunk {
+    chunk: Chunk,
+    /// Set if loading this chunk's region failed.
+    save_error: Option<SaveError>,
+    /// Decoration voxels that belong in other chunks.
+    decorations: PendingWrites,
+}
+
+#[derive(Component)]
+struct GenerateTask(IVec3, Task<GeneratedChunk>);
+
 #[derive(Component)]
-struct GenerateTask(IVec3, Task<(Chunk, Option<Chunk2dNoiseValues>)>);
+struct RenderTask(IVec3, Task<ChunkMeshes>);
 
 #[derive(Component)]
-struct RenderTask(IVec3, Task<Option<(Collider, Mesh)>>);
+struct ColliderTask(IVec3, Task<Option<Collider>>);
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum NeededStateChange {
@@ -326,20 +428,59 @@ pub enum NeededChunkState {
     Rendered,
 }
 
+/// Whether a chunk has a collider. Only chunks near a chunk loader get one,
+/// see [`ChunkLoader::physics_radius`].
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ColliderState {
+    #[default]
+    None,
+    Building,
+    Built,
+}
+
 pub(crate) struct LoadedChunk {
     pub entity: Entity,
     pub chunk: Option<Chunk>,
     pub state: ChunkState,
     pub needed_state: NeededChunkState,
+    pub collider: ColliderState,
     #[allow(unused)]
     pub pos: IVec3,
 }
 
+impl LoadedChunk {
+    /// Get the chunk if it has been modified since it was last saved,
+    /// clearing its modified flag.
+    pub fn take_modified(&mut self) -> Option<&Chunk> {
+        match &mut self.chunk {
+            Some(chunk) if chunk.modified => {
+                chunk.modified = false;
+                Some(chunk)
+            }
+            _ => None,
+        }
+    }
+}
+
 #[allow(unused)]
 #[derive(Default, Resource)]
 pub struct FixedChunkWorld {
     pub(crate) chunks: HashMap<ChunkPos, LoadedChunk>,
-    pub(crate) heightmaps: HashMap<IVec2, Chunk2dNoiseValues>,
+    pub(crate) column_noise: ColumnNoiseCache,
+}
+
+impl ChunkMap for FixedChunkWorld {
+    fn chunk(&self, pos: ChunkPos) -> Option<&Chunk> {
+        self.chunks
+            .get(&pos)
+            .and_then(|loaded| loaded.chunk.as_ref())
+    }
+
+    fn chunk_mut(&mut self, pos: ChunkPos) -> Option<&mut Chunk> {
+        self.chunks
+            .get_mut(&pos)
+            .and_then(|loaded| loaded.chunk.as_mut())
+    }
 }
 
 impl FixedChunkWorld {
@@ -359,13 +500,16 @@ impl FixedChunkWorld {
                 entity: commands
                     .spawn((
                         ChunkEntity(chunk.0),
-                        Aabb::from_min_max(Vec3::ZERO, UVec3::splat(CHUNK_WIDTH).as_vec3()),
+                        // The meshes are all children, so the chunk itself
+                        // only needs a transform and visibility.
+                        SpatialBundle::from_transform(chunk.transform()),
                         RigidBody::Fixed,
                     ))
                     .id(),
                 chunk: None,
                 state: ChunkState::Empty,
                 needed_state,
+                collider: ColliderState::None,
                 pos: chunk.0,
             });
     }
@@ -394,6 +538,78 @@ impl FixedChunkWorld {
         }
     }
 
+    /// Mark chunks for remeshing, if they've been meshed already. Chunks
+    /// that are still rendering get marked too, since their task started
+    /// with what's now out of date.
+    pub fn mark_dirty(&self, commands: &mut Commands, chunks: impl IntoIterator<Item = ChunkPos>) {
+        for pos in chunks {
+            if let Some(LoadedChunk {
+                entity,
+                state: ChunkState::Rendering | ChunkState::Rendered,
+                ..
+            }) = self.chunks.get(&pos)
+            {
+                commands.entity(*entity).insert(DirtyChunk);
+            }
+        }
+    }
+
+    /// Start building colliders for rendered chunks within the physics radius
+    /// of any of the loaders, and remove them from chunks outside all of
+    /// them.
+    fn update_colliders(
+        &mut self,
+        commands: &mut Commands,
+        registry: &VoxelRegistry,
+        loaders: &[(ChunkPos, u32)],
+    ) {
+        let mut needs_collider = vec![];
+        for (pos, loaded_chunk) in self.chunks.iter_mut() {
+            let in_range = loaders.iter().any(|(loader_pos, physics_radius)| {
+                (pos.0 - loader_pos.0).abs().max_element() <= *physics_radius as i32
+            });
+            match (in_range, loaded_chunk.collider) {
+                (true, ColliderState::None) => {
+                    if loaded_chunk.state == ChunkState::Rendered {
+                        needs_collider.push(*pos);
+                    }
+                }
+                (false, ColliderState::Building | ColliderState::Built) => {
+                    loaded_chunk.collider = ColliderState::None;
+                    commands
+                        .entity(loaded_chunk.entity)
+                        .remove::<(Collider, ColliderTask)>();
+                }
+                _ => {}
+            }
+        }
+
+        let async_pool = AsyncComputeTaskPool::get();
+        for pos in needs_collider {
+            // Rendered chunks always have their neighbors
+            let Some(neighbors) = self.neighbors(pos.0) else {
+                continue;
+            };
+            let Some(LoadedChunk {
+                entity,
+                chunk: Some(chunk),
+                collider,
+                ..
+            }) = self.chunks.get_mut(&pos)
+            else {
+                continue;
+            };
+
+            *collider = ColliderState::Building;
+            let chunk = chunk.clone();
+            let registry = registry.clone();
+            commands.entity(*entity).insert(ColliderTask(
+                pos.0,
+                async_pool.spawn(async move { generate_collider(&chunk, &neighbors, &registry) }),
+            ));
+        }
+    }
+
     /// Determine which states need to change based on the current state and
     /// the needed state.
     fn required_state_changes(
@@ -461,7 +677,7 @@ impl FixedChunkWorld {
 
     /// Get the solid face bitmap for each chunk neighboring the provided one
     /// to determine whether sides of edge voxels should be culled.
-    fn neighbors(&self, chunk: IVec3) -> Option<NeighborChunkSlices> {
+    fn neighbors(&self, chunk: IVec3) -> Option<MeshNeighbors> {
         // Get the chunks in each direction
         let slice_dirs = SLICE_DIRECTIONS.map(|direction| {
             let norm = direction.normal();
@@ -469,7 +685,7 @@ impl FixedChunkWorld {
             (direction, self.chunks.get(&ChunkPos(chunk_pos)))
         });
 
-        let mut output = NeighborChunkSlices::default();
+        let mut output = MeshNeighbors::default();
 
         for (direction, loaded_chunk) in slice_dirs {
             // Make sure this chunk is already generated.
@@ -477,10 +693,14 @@ impl FixedChunkWorld {
                 chunk: Some(chunk), ..
             }) = loaded_chunk
             {
-                *output.get_in_direction_mut(direction.normal()) = chunk
-                    .edge_slice_bits
-                    .get_in_direction(direction.normal().negate())
-                    .clone();
+                let edges = &chunk.edge_slice_bits;
+                let (normal, facing) = (direction.normal(), direction.normal().negate());
+                *output.solid.get_in_direction_mut(normal) =
+                    edges.solid.get_in_direction(facing).clone();
+                *output.translucent.get_in_direction_mut(normal) =
+                    edges.translucent.get_in_direction(facing).clone();
+                *output.light.get_in_direction_mut(normal) =
+                    chunk.get_light_slice(direction, 0).unwrap();
             } else {
                 // We need to return none now, not all neighboring chunks have
                 // been generated.
@@ -492,13 +712,16 @@ impl FixedChunkWorld {
     }
 
     /// Spawn the tasks to perform the state changes required.
+    #[allow(clippy::too_many_arguments)]
     fn execute_state_changes(
         &mut self,
         diagnostics: &mut Diagnostics,
         commands: &mut Commands,
         name: &str,
         region_handler_res: &RegionHandlerRes,
-        noise: &WorldNoiseSettings,
+        generator: &WorldGeneratorRes,
+        registry: &VoxelRegistry,
+        save_errors: &mut EventWriter<SaveErrorEvent>,
         changes: Vec<(ChunkPos, Entity, NeededStateChange)>,
     ) {
         let async_pool = AsyncComputeTaskPool::get();
@@ -516,44 +739,57 @@ impl FixedChunkWorld {
                     // Update the current state
                     chunk.state = ChunkState::Generating;
                     // Make clones to send to task
-                    let noise = noise.clone();
+                    let generator = Arc::clone(&generator.0);
+                    let registry = registry.clone();
                     let name = name.to_string();
 
                     // Insert the task into the chunk entity
                     let region_handler_inner = Arc::clone(&region_handler_res.0);
-                    let chunk_noise = self.heightmaps.get(&IVec2::new(pos.0.x, pos.0.z)).cloned();
+                    let column_noise_cache = self.column_noise.clone();
                     commands.entity(entity).insert(GenerateTask(
                         pos.0,
                         async_pool.spawn(async move {
-                            let needed_new_noise = chunk_noise.is_none();
-                            let new_noise = chunk_noise.unwrap_or_else(|| {
-                                noise.generate_chunk_2d_noise(IVec2::new(pos.0.x, pos.0.z))
-                            });
+                            let column_noise =
+                                column_noise_cache.get_or_generate(pos.0.xz(), generator.as_ref());
 
-                            match region_handler_inner.write() {
+                            // If the region can't be read, we still generate
+                            // the chunk so the player isn't stuck in a hole,
+                            // but we pass the error along.
+                            let (existing_chunk, save_error) = match region_handler_inner.write() {
                                 Ok(mut region_handler) => {
                                     match region_handler.check_for_chunk(&name, pos) {
-                                        // Load from disk
-                                        Some(existing_chunk) => (
-                                            Chunk::from_container(existing_chunk.clone()),
-                                            match needed_new_noise {
-                                                true => Some(new_noise),
-                                                false => None,
-                                            },
-                                        ),
-                                        // Generate with noise
-                                        None => (
-                                            noise.generate_chunk_from_noise(pos.0.y, &new_noise),
-                                            match needed_new_noise {
-                                                true => Some(new_noise),
-                                                false => None,
-                                            },
-                                        ),
+                                        Ok(existing_chunk) => (existing_chunk.cloned(), None),
+                                        Err(err) => (None, Some(err)),
                                     }
                                 }
-                                Err(_) => {
-                                    panic!("u done fucked up");
+                                Err(_) => (None, Some(SaveError::LockPoisoned)),
+                            };
+
+                            let (chunk, decorations) = match existing_chunk {
+                                // Load from disk
+                                Some(existing_chunk) => {
+                                    let mut chunk =
+                                        Chunk::from_container(existing_chunk, &registry);
+                                    light_chunk(
+                                        &mut chunk,
+                                        &registry,
+                                        generator.open_sky_above(pos, column_noise.as_deref()),
+                                    );
+                                    (chunk, PendingWrites::default())
                                 }
+                                // Generate a new one
+                                None => generate_chunk_with(
+                                    generator.as_ref(),
+                                    pos,
+                                    column_noise.as_deref(),
+                                    &registry,
+                                ),
+                            };
+
+                            GeneratedChunk {
+                                chunk,
+                                decorations,
+                                save_error,
                             }
                         }),
                     ));
@@ -572,11 +808,12 @@ impl FixedChunkWorld {
                         *state = ChunkState::Rendering;
                         // Clone the chunk to send to the task
                         let cloned_chunk = voxels.clone();
+                        let registry = registry.clone();
                         // Insert the task into the chunk entity
                         commands.entity(entity).insert(RenderTask(
                             pos.0,
                             async_pool.spawn(async move {
-                                crate::voxel::generate_mesh(&cloned_chunk, neighbors)
+                                crate::voxel::generate_mesh(&cloned_chunk, neighbors, &registry)
                             }),
                         ));
                     }
@@ -588,21 +825,22 @@ impl FixedChunkWorld {
                         chunk: Some(chunk), ..
                     }) = self.chunks.remove(&pos)
                     {
-                        // Try to save this chunk before it's deleted
+                        // Hand the chunk over to be saved if it changed,
+                        // otherwise it's already on disk (or waiting to be
+                        // written) and we can free it.
                         match region_handler_res.0.write() {
-                            Ok(mut region_handler) => {
-                                *region_handler
-                                    .region_mut(pos.into())
-                                    .chunk_mut(InRegionChunkPos::from_world(pos)) =
-                                    Some(chunk.voxels);
-                            }
+                            Ok(mut region_handler) => match chunk.modified {
+                                true => region_handler.set_modified_chunk(pos, chunk.voxels),
+                                false => region_handler.unload_chunk(pos),
+                            },
                             Err(_) => {
-                                panic!("errrrrr")
+                                error!("failed to lock region handler to save chunk {}", pos.0);
+                                save_errors.send(SaveErrorEvent(SaveError::LockPoisoned));
                             }
                         }
                     };
-                    // Despawn the entity
-                    commands.entity(entity).despawn();
+                    // Despawn the entity, along with its sub-meshes
+                    commands.entity(entity).despawn_recursive();
                     delete_count += 1;
                 }
             }
@@ -611,13 +849,58 @@ impl FixedChunkWorld {
         diagnostics.add_measurement(DIAG_DELETE_REQUIRED, || delete_count as f64);
     }
 
+    /// Write decorations into the chunks they belong to if those are loaded,
+    /// otherwise queue them up in the region handler for when they are.
+    fn place_decorations(
+        &mut self,
+        commands: &mut Commands,
+        region_handler: &mut RegionHandler,
+        registry: &VoxelRegistry,
+        decorations: PendingWrites,
+    ) {
+        for (chunk_pos, writes) in decorations.into_chunks() {
+            if self.chunk(chunk_pos).is_some() {
+                self.apply_writes_lit(commands, chunk_pos, &writes, registry);
+            } else {
+                region_handler.add_pending_writes(chunk_pos, writes);
+            }
+        }
+    }
+
+    /// Apply writes to a loaded chunk one voxel at a time, so the light can
+    /// be fixed up around each one.
+    fn apply_writes_lit(
+        &mut self,
+        commands: &mut Commands,
+        chunk_pos: ChunkPos,
+        writes: &[PendingWrite],
+        registry: &VoxelRegistry,
+    ) {
+        let origin = VoxelPos::from(chunk_pos).0;
+        let mut changed = HashSet::default();
+        for write in writes {
+            let Some(chunk) = self.chunk_mut(chunk_pos) else {
+                return;
+            };
+            if apply_writes(chunk, std::slice::from_ref(write)) {
+                changed.insert(chunk_pos);
+                changed.extend(relight_voxel(self, origin + write.pos.as_ivec3(), registry));
+            }
+        }
+        self.mark_dirty(commands, changed);
+    }
+
     //noinspection DuplicatedCode
     /// Search for finished generation and render tasks.
+    #[allow(clippy::too_many_arguments)]
     fn collect_finished_tasks(
         &mut self,
         commands: &mut Commands,
         material: &ChunkMaterialRes,
         meshes: &mut Assets<Mesh>,
+        region_handler_res: &RegionHandlerRes,
+        registry: &VoxelRegistry,
+        save_errors: &mut EventWriter<SaveErrorEvent>,
         generate_query: &mut Query<(Entity, &mut GenerateTask), Without<RenderTask>>,
         render_query: &mut Query<(Entity, &mut RenderTask), Without<GenerateTask>>,
         loader: &Query<&ChunkPos, With<ChunkLoader>>,
@@ -627,18 +910,45 @@ impl FixedChunkWorld {
             .iter_mut()
             .filter_map(|(entity, mut task)| {
                 if commands.get_entity(entity).is_some() {
-                    block_on(poll_once(&mut task.1))
-                        .map(|(chunk, heightmap)| (task.0, entity, chunk, heightmap))
+                    block_on(poll_once(&mut task.1)).map(|generated| (task.0, entity, generated))
                 } else {
                     None
                 }
             })
             .collect::<Vec<_>>();
 
-        for (pos, entity, chunk, heightmap) in generated_chunks {
-            if let Some(new_heightmap) = heightmap {
-                self.heightmaps
-                    .insert(IVec2::new(pos.x, pos.z), new_heightmap);
+        for (
+            pos,
+            entity,
+            GeneratedChunk {
+                chunk,
+                save_error,
+                decorations,
+            },
+        ) in generated_chunks
+        {
+            // Remove the task from this entity, it can't be polled again
+            commands.entity(entity).remove::<GenerateTask>();
+
+            if let Some(err) = save_error {
+                save_errors.send(SaveErrorEvent(err));
+            }
+
+            // Hand decorations over to their chunks, and pick up any that
+            // were left for this one. This happens even if the chunk has
+            // been unloaded in the meantime so nothing gets lost. Without
+            // the region handler the chunk is still worth keeping, just
+            // without its decorations.
+            let mut region_handler = match region_handler_res.0.write() {
+                Ok(region_handler) => Some(region_handler),
+                Err(_) => {
+                    error!("failed to lock region handler to place decorations");
+                    save_errors.send(SaveErrorEvent(SaveError::LockPoisoned));
+                    None
+                }
+            };
+            if let Some(region_handler) = region_handler.as_mut() {
+                self.place_decorations(commands, region_handler, registry, decorations);
             }
 
             // Make sure the chunk is still loaded
@@ -650,8 +960,16 @@ impl FixedChunkWorld {
             wrapper.chunk = Some(chunk);
             wrapper.state = ChunkState::Generated;
 
-            // Remove the task from this entity
-            commands.entity(entity).remove::<GenerateTask>();
+            // Let light in from the neighbors and out into them
+            let changed = spread_into_chunk(self, ChunkPos(pos), registry);
+            self.mark_dirty(commands, changed);
+
+            if let Some(writes) = region_handler
+                .as_mut()
+                .and_then(|region_handler| region_handler.take_pending_writes(ChunkPos(pos)))
+            {
+                self.apply_writes_lit(commands, ChunkPos(pos), &writes, registry);
+            }
         }
 
         // Perform up to the maximum number of chunk meshes added to the
@@ -669,7 +987,7 @@ impl FixedChunkWorld {
         'render_loop: for (entity, mut task) in render_positions {
             let pos = task.0;
 
-            if let Some(optional_chunk) = block_on(poll_once(&mut task.1)) {
+            if let Some(chunk_meshes) = block_on(poll_once(&mut task.1)) {
                 // Make sure this chunk is still loaded
                 let Some(wrapper) = self.chunks.get_mut(&ChunkPos(pos)) else {
                     continue;
@@ -683,8 +1001,8 @@ impl FixedChunkWorld {
                 e.remove::<RenderTask>();
 
                 // Insert the mesh information if it is not empty
-                if let Some((collider, mesh)) = optional_chunk {
-                    make_mesh_bundle(&mut e, pos, meshes, material, collider, mesh);
+                if !chunk_meshes.is_empty() {
+                    make_mesh_bundle(&mut e, meshes, material, chunk_meshes);
 
                     rendered_count += 1;
                     if rendered_count >= MAX_RENDERS_PER_FRAME {
@@ -697,24 +1015,28 @@ impl FixedChunkWorld {
 }
 
 /// Insert the necessary components for rendering into the provided chunk
-/// entity.
+/// entity, removing any that are now empty so removing the last voxel in a
+/// chunk doesn't just leave the voxel ghost. Each mesh goes on a
+/// [`ChunkSubMesh`] child entity, since they all need different materials.
 fn make_mesh_bundle(
     commands: &mut EntityCommands,
-    pos: IVec3,
     meshes: &mut Assets<Mesh>,
     material: &ChunkMaterialRes,
-    collider: Collider,
-    mesh: Mesh,
+    chunk_meshes: ChunkMeshes,
 ) {
-    commands.insert((
-        MaterialMeshBundle {
-            mesh: meshes.add(mesh),
-            material: Handle::clone(&material.0),
-            transform: ChunkPos(pos).transform(),
-            ..default()
-        },
-        collider,
-    ));
+    commands.despawn_descendants();
+    commands.with_children(|parent| {
+        for (layer, mesh) in chunk_meshes.into_layers() {
+            parent.spawn((
+                ChunkSubMesh(layer),
+                MaterialMeshBundle {
+                    mesh: meshes.add(mesh),
+                    material: Handle::clone(material.for_layer(layer)),
+                    ..default()
+                },
+            ));
+        }
+    });
 }
 
 /// System to update the main character controller's loader radius whenever the
@@ -725,5 +1047,6 @@ fn update_loader_radius(
 ) {
     if let Ok(mut loader) = loader.get_single_mut() {
         loader.radius = settings.load_radius;
+        loader.physics_radius = settings.physics_radius;
     }
 }
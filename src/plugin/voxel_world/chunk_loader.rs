@@ -3,10 +3,16 @@ use bevy::prelude::*;
 #[derive(Default, Debug, Component, Copy, Clone, Eq, PartialEq)]
 pub struct ChunkLoader {
     pub radius: u32,
+    /// Chunks this close get colliders. Only rendered chunks can have one,
+    /// so anything past `radius - 1` doesn't make a difference.
+    pub physics_radius: u32,
 }
 
 impl ChunkLoader {
-    pub fn new(radius: u32) -> Self {
-        Self { radius }
+    pub fn new(radius: u32, physics_radius: u32) -> Self {
+        Self {
+            radius,
+            physics_radius,
+        }
     }
 }
@@ -1,11 +1,18 @@
 use crate::{
-    io::write_regions_to_file,
-    plugin::voxel_world::{beef::FixedChunkWorld, world_info::WorldInfo},
+    io::{write_regions_to_file, write_world_file, SaveError, SavedPlayer},
+    plugin::{
+        control::{controller_2::CharControl2, PlyCamRot},
+        voxel_world::{beef::FixedChunkWorld, world_info::WorldInfo},
+    },
     voxel::RegionHandler,
 };
 use bevy::{
-    app::AppExit, prelude::*, tasks::AsyncComputeTaskPool, time::common_conditions::on_timer,
+    app::AppExit,
+    prelude::*,
+    tasks::{block_on, AsyncComputeTaskPool, Task},
+    time::common_conditions::on_timer,
 };
+use futures_lite::future::poll_once;
 use std::{
     sync::{Arc, RwLock},
     time::Duration,
@@ -15,32 +22,54 @@ pub struct RegionSaverPlugin;
 
 impl Plugin for RegionSaverPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(
-            Update,
-            async_ish_save_regions_system
-                .run_if(resource_exists::<FixedChunkWorld>())
-                .run_if(resource_exists::<RegionHandlerRes>())
-                .run_if(on_timer(Duration::from_secs(300))),
-        )
-        .add_systems(Last, save_regions_on_exit_system);
+        app.add_event::<SaveErrorEvent>()
+            .init_resource::<PendingSaveTasks>()
+            .add_systems(
+                Update,
+                (
+                    async_ish_save_regions_system
+                        .run_if(resource_exists::<FixedChunkWorld>())
+                        .run_if(resource_exists::<RegionHandlerRes>())
+                        .run_if(on_timer(Duration::from_secs(300))),
+                    check_pending_saves_system,
+                ),
+            )
+            .add_systems(Last, save_regions_on_exit_system);
     }
 }
 
 #[derive(Default, Resource)]
 pub struct RegionHandlerRes(pub Arc<RwLock<RegionHandler>>);
 
+/// Sent whenever reading or writing part of the world save fails.
+#[derive(Event)]
+pub struct SaveErrorEvent(pub SaveError);
+
+/// Background save tasks that haven't finished yet.
+#[derive(Default, Resource)]
+struct PendingSaveTasks(Vec<Task<Vec<SaveError>>>);
+
 fn save_regions_on_exit_system(
     exit_reader: EventReader<AppExit>,
     world_info: Option<Res<WorldInfo>>,
     region_handler: Option<Res<RegionHandlerRes>>,
-    chunk_world: Option<Res<FixedChunkWorld>>,
+    chunk_world: Option<ResMut<FixedChunkWorld>>,
+    player: Query<(&Transform, &PlyCamRot), With<CharControl2>>,
 ) {
     if !exit_reader.is_empty() {
         debug!("exiting game, checking if we need to save regions");
-        if let (Some(world_info), Some(region_handler), Some(chunk_world)) =
+        if let (Some(world_info), Some(region_handler), Some(mut chunk_world)) =
             (world_info, region_handler, chunk_world)
         {
-            force_sync_regions_save(&world_info, &region_handler, &chunk_world);
+            // Nothing is left to show these to the player, so just log them
+            for err in force_sync_regions_save(
+                &world_info,
+                saved_player(&player),
+                &region_handler,
+                &mut chunk_world,
+            ) {
+                error!("failed to save world on exit: {err}");
+            }
         }
     }
 }
@@ -48,59 +77,114 @@ fn save_regions_on_exit_system(
 fn async_ish_save_regions_system(
     world_info: Res<WorldInfo>,
     region_handler: Res<RegionHandlerRes>,
-    chunk_world: Res<FixedChunkWorld>,
+    mut chunk_world: ResMut<FixedChunkWorld>,
+    mut pending_saves: ResMut<PendingSaveTasks>,
+    mut save_errors: EventWriter<SaveErrorEvent>,
+    player: Query<(&Transform, &PlyCamRot), With<CharControl2>>,
 ) {
     match region_handler.0.write() {
         Ok(mut region_handler) => {
             info!("saving world!");
             debug!("extracting chunks into region handler");
-            region_handler.extract_chunks(&chunk_world);
+            region_handler.extract_chunks(&mut chunk_world);
         }
         Err(_) => {
             error!("FAILED TO LOCK REGION HANDLER TO EXTRACT WORLD!!!");
+            save_errors.send(SaveErrorEvent(SaveError::LockPoisoned));
+            return;
         }
     }
 
     let region_handler_inner = Arc::clone(&region_handler.0);
-    let world_name = world_info.name().to_string();
-    AsyncComputeTaskPool::get()
-        .spawn(async move {
-            match region_handler_inner.read() {
-                Ok(region_handler) => {
+    let world_file = world_info.to_file(saved_player(&player));
+    pending_saves
+        .0
+        .push(AsyncComputeTaskPool::get().spawn(async move {
+            let mut errors = match region_handler_inner.write() {
+                Ok(mut region_handler) => {
                     debug!("saving regions to disk");
-                    write_regions_to_file(&world_name, &region_handler);
-                    info!("world saved!");
+                    write_regions_to_file(&world_file.name, &mut region_handler)
                 }
                 Err(_) => {
                     error!("FAILED TO LOCK REGION HANDLER TO SAVE REGIONS!!!");
+                    vec![SaveError::LockPoisoned]
                 }
+            };
+
+            // Keep the world file up to date too, so the world can still be
+            // loaded if the game crashes before it's saved on exit
+            if let Err(err) = write_world_file(&world_file) {
+                error!("{err}");
+                errors.push(err);
+            }
+
+            if errors.is_empty() {
+                info!("world saved!");
+            }
+            errors
+        }));
+}
+
+/// System to report the errors from any finished background saves.
+fn check_pending_saves_system(
+    mut pending_saves: ResMut<PendingSaveTasks>,
+    mut save_errors: EventWriter<SaveErrorEvent>,
+) {
+    pending_saves
+        .0
+        .retain_mut(|task| match block_on(poll_once(task)) {
+            Some(errors) => {
+                save_errors.send_batch(errors.into_iter().map(SaveErrorEvent));
+                false
             }
+            None => true,
+        });
+}
+
+/// The player's current position and look direction, to be stored in the
+/// world file.
+pub fn saved_player(
+    player: &Query<(&Transform, &PlyCamRot), With<CharControl2>>,
+) -> Option<SavedPlayer> {
+    player
+        .get_single()
+        .ok()
+        .map(|(transform, cam_rot)| SavedPlayer {
+            transform: *transform,
+            cam_rot: *cam_rot,
         })
-        .detach();
 }
 
+/// Extract and write every region and the world file immediately, returning
+/// any errors that happened along the way.
 pub fn force_sync_regions_save(
     world_info: &WorldInfo,
+    player: Option<SavedPlayer>,
     region_handler: &RegionHandlerRes,
-    chunk_world: &FixedChunkWorld,
-) {
+    chunk_world: &mut FixedChunkWorld,
+) -> Vec<SaveError> {
     debug!("forcing world save");
-    match region_handler.0.write() {
+    let mut errors = match region_handler.0.write() {
         Ok(mut region_handler) => {
             info!("saving world!");
             region_handler.extract_chunks(chunk_world);
-        }
-        Err(_) => {
-            error!("FAILED TO LOCK REGION HANDLER TO EXTRACT WORLD!!!");
-        }
-    }
-    match region_handler.0.read() {
-        Ok(region_handler) => {
-            write_regions_to_file(world_info.name(), &region_handler);
-            info!("world saved!");
+            write_regions_to_file(world_info.name(), &mut region_handler)
         }
         Err(_) => {
             error!("FAILED TO LOCK REGION HANDLER TO SAVE REGIONS!!!");
+            vec![SaveError::LockPoisoned]
         }
+    };
+
+    // The world file is written even if some regions failed, it's the only
+    // place the seed is stored.
+    if let Err(err) = write_world_file(&world_info.to_file(player)) {
+        error!("{err}");
+        errors.push(err);
+    }
+
+    if errors.is_empty() {
+        info!("world saved!");
     }
+    errors
 }
@@ -1,3 +1,4 @@
+use crate::voxel::MeshLayer;
 use bevy::{
     pbr::{ExtendedMaterial, MaterialExtension, MaterialExtensionKey, MaterialExtensionPipeline},
     prelude::*,
@@ -23,7 +24,23 @@ impl Plugin for VoxelMaterialPlugin {
 pub type VoxelExtendedMaterial = ExtendedMaterial<StandardMaterial, VoxelChunkMaterial>;
 
 #[derive(Resource)]
-pub struct ChunkMaterialRes(pub Handle<VoxelExtendedMaterial>);
+pub struct ChunkMaterialRes {
+    pub opaque: Handle<VoxelExtendedMaterial>,
+    /// Alpha tested, for the chunks' cutout meshes.
+    pub cutout: Handle<VoxelExtendedMaterial>,
+    /// Alpha blended, for the chunks' translucent meshes.
+    pub translucent: Handle<VoxelExtendedMaterial>,
+}
+
+impl ChunkMaterialRes {
+    pub fn for_layer(&self, layer: MeshLayer) -> &Handle<VoxelExtendedMaterial> {
+        match layer {
+            MeshLayer::Opaque => &self.opaque,
+            MeshLayer::Cutout => &self.cutout,
+            MeshLayer::Translucent => &self.translucent,
+        }
+    }
+}
 
 fn add_chunk_material_system(
     mut commands: Commands,
@@ -34,21 +51,41 @@ fn add_chunk_material_system(
         s.sampler = ImageSampler::Descriptor(ImageSamplerDescriptor::nearest());
     };
 
-    let handle = materials.add(ExtendedMaterial {
+    let base = StandardMaterial {
+        base_color: Color::WHITE,
+        base_color_texture: Some(asset_server.load_with_settings("textures/voxels.png", settings)),
+        perceptual_roughness: 1.0,
+        metallic: 0.01,
+        reflectance: 0.02,
+        double_sided: true,
+        ..default()
+    };
+
+    let opaque = materials.add(ExtendedMaterial {
+        base: base.clone(),
+        extension: VoxelChunkMaterial { atlas_width: 4 },
+    });
+    let cutout = materials.add(ExtendedMaterial {
+        base: StandardMaterial {
+            alpha_mode: AlphaMode::Mask(0.5),
+            ..base.clone()
+        },
+        extension: VoxelChunkMaterial { atlas_width: 4 },
+    });
+    let translucent = materials.add(ExtendedMaterial {
         base: StandardMaterial {
-            base_color: Color::WHITE,
-            base_color_texture: Some(
-                asset_server.load_with_settings("textures/voxels.png", settings),
-            ),
-            perceptual_roughness: 1.0,
-            metallic: 0.01,
-            reflectance: 0.02,
-            double_sided: true,
-            ..default()
+            alpha_mode: AlphaMode::Blend,
+            // So the surface of the water can be seen from underneath
+            cull_mode: None,
+            ..base
         },
         extension: VoxelChunkMaterial { atlas_width: 4 },
     });
-    commands.insert_resource(ChunkMaterialRes(handle));
+    commands.insert_resource(ChunkMaterialRes {
+        opaque,
+        cutout,
+        translucent,
+    });
 }
 
 #[derive(Asset, AsBindGroup, TypePath, Debug, Clone)]
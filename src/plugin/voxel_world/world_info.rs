@@ -1,14 +1,62 @@
+use crate::{
+    io::{unix_now, SavedPlayer, WorldFile, WORLD_FORMAT_VERSION},
+    voxel::{GeneratorPreset, WorldGenConfig},
+};
 use bevy::prelude::*;
 
-#[derive(Resource, Clone, Eq, PartialEq)]
+#[derive(Resource, Clone)]
 pub struct WorldInfo {
     name: String,
     seed: u32,
+    created: u64,
+    generator: GeneratorPreset,
+    worldgen: WorldGenConfig,
+    /// Where the player was when the world was last saved.
+    saved_player: Option<SavedPlayer>,
 }
 
 impl WorldInfo {
-    pub fn new(name: String, seed: u32) -> Self {
-        Self { name, seed }
+    pub fn new(
+        name: String,
+        seed: u32,
+        generator: GeneratorPreset,
+        worldgen: WorldGenConfig,
+    ) -> Self {
+        Self {
+            name,
+            seed,
+            created: unix_now(),
+            generator,
+            worldgen,
+            saved_player: None,
+        }
+    }
+
+    pub fn from_file(world_file: WorldFile) -> Self {
+        Self {
+            name: world_file.name,
+            seed: world_file.seed,
+            created: world_file.created,
+            generator: world_file.generator,
+            worldgen: world_file.worldgen,
+            saved_player: world_file.player,
+        }
+    }
+
+    /// Create the world file to save, marking the world as just played.
+    /// Without a player, like when saving before one has spawned, the last
+    /// saved position is kept.
+    pub fn to_file(&self, player: Option<SavedPlayer>) -> WorldFile {
+        WorldFile {
+            format_version: WORLD_FORMAT_VERSION,
+            name: self.name.clone(),
+            seed: self.seed,
+            created: self.created,
+            last_played: unix_now(),
+            generator: self.generator.clone(),
+            worldgen: self.worldgen.clone(),
+            player: player.or_else(|| self.saved_player.clone()),
+        }
     }
 
     pub fn name(&self) -> &str {
@@ -18,4 +66,16 @@ impl WorldInfo {
     pub fn seed(&self) -> u32 {
         self.seed
     }
+
+    pub fn generator(&self) -> &GeneratorPreset {
+        &self.generator
+    }
+
+    pub fn worldgen(&self) -> &WorldGenConfig {
+        &self.worldgen
+    }
+
+    pub fn saved_player(&self) -> Option<&SavedPlayer> {
+        self.saved_player.as_ref()
+    }
 }
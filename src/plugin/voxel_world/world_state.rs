@@ -1,20 +1,29 @@
 use crate::{
+    io::{read_pending_writes, read_world_file, write_world_file},
     plugin::{
-        control::controller_2::CharControl2,
+        control::{controller_2::CharControl2, PlyCamRot},
         game_gui::MenuState,
         game_settings::GameSettings,
         voxel_world::{
-            beef::{ChunkEntity, ChunkState, FixedChunkWorld, LoadedChunk},
+            beef::{ChunkEntity, ChunkState, ChunkSubMesh, FixedChunkWorld, LoadedChunk},
             chunk_loader::ChunkLoader,
-            region_saver::{force_sync_regions_save, RegionHandlerRes},
+            region_saver::{
+                force_sync_regions_save, saved_player, RegionHandlerRes, SaveErrorEvent,
+            },
             world_info::WorldInfo,
         },
     },
-    voxel::{world_noise::WorldNoiseSettings, BiomeTable, ChunkPos, CHUNK_SQUARE, CHUNK_WIDTH},
+    voxel::{
+        BiomeTable, ChunkPos, MeshLayer, RegionHandler, VoxelPos, VoxelRegistry, WorldGeneratorRes,
+        CHUNK_WIDTH,
+    },
 };
 use bevy::{prelude::*, time::common_conditions::on_timer};
 use bevy_rapier3d::geometry::Collider;
-use std::time::Duration;
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
 
 pub struct WorldStatePlugin;
 
@@ -56,22 +65,72 @@ pub enum WorldState {
 // Add chunk loader to camera
 fn enter_world_loading_state_system(
     mut commands: Commands,
-    world_info: Res<WorldInfo>,
+    mut world_info: ResMut<WorldInfo>,
     game_settings: Res<GameSettings>,
+    registry: Res<VoxelRegistry>,
+    biome_table: Res<BiomeTable>,
+    mut save_errors: EventWriter<SaveErrorEvent>,
     ply_entity: Query<Entity, With<CharControl2>>,
 ) {
+    // If this world has been saved before, its world file knows the real
+    // seed (and everything else about it). The name stays the one the save
+    // was found under, so the regions are read from the same directory.
+    match read_world_file(world_info.name()) {
+        Ok(Some(mut world_file)) => {
+            world_file.name = world_info.name().to_string();
+            *world_info = WorldInfo::from_file(world_file);
+        }
+        // Brand new, so write the world file straight away. Otherwise the
+        // seed would be lost if the game crashed before the first save.
+        Ok(None) => {
+            if let Err(err) = write_world_file(&world_info.to_file(None)) {
+                error!("{err}");
+                save_errors.send(SaveErrorEvent(err));
+            }
+        }
+        Err(err) => {
+            error!("{err}");
+            save_errors.send(SaveErrorEvent(err));
+        }
+    }
+
     let name = world_info.name().to_string();
     let seed = world_info.seed();
-    info!("Creating world \"{name}\" with seed {seed}");
+    info!(
+        "Creating world \"{name}\" with seed {seed} and the {} generator",
+        world_info.generator().display_name()
+    );
 
-    commands.insert_resource(RegionHandlerRes::default());
-    commands.insert_resource(WorldNoiseSettings::new(seed, BiomeTable::new()));
+    let mut region_handler = RegionHandler::default();
+    match read_pending_writes(&name) {
+        Ok(pending_writes) => region_handler.set_pending_writes(pending_writes),
+        // Losing these just leaves a few trees cut off at chunk borders, so
+        // carry on without them
+        Err(err) => {
+            error!("{err}");
+            save_errors.send(SaveErrorEvent(err));
+        }
+    }
+    commands.insert_resource(RegionHandlerRes(Arc::new(RwLock::new(region_handler))));
+    commands.insert_resource(WorldGeneratorRes(world_info.generator().build(
+        seed,
+        world_info.worldgen(),
+        &biome_table,
+        &registry,
+    )));
     commands.insert_resource(FixedChunkWorld::default());
     if let Ok(entity) = ply_entity.get_single() {
+        // Pick up where the player left off, otherwise start near the origin
+        // until we know how high the ground is.
+        let (transform, cam_rot) = match world_info.saved_player() {
+            Some(saved) => (saved.transform, saved.cam_rot),
+            None => (Transform::from_xyz(15.5, 10.0, 15.5), PlyCamRot::default()),
+        };
         commands.entity(entity).insert((
-            Transform::from_xyz(15.5, 10.0, 15.5),
-            ChunkLoader::new(game_settings.load_radius),
-            ChunkPos::default(),
+            transform,
+            cam_rot,
+            ChunkLoader::new(game_settings.load_radius, game_settings.physics_radius),
+            ChunkPos::from(VoxelPos(transform.translation.floor().as_ivec3())),
         ));
     }
 }
@@ -80,12 +139,23 @@ fn enter_world_loading_state_system(
 fn exit_world_loading_state_system(
     world_info: Option<Res<WorldInfo>>,
     region_handler: Option<Res<RegionHandlerRes>>,
-    chunk_world: Option<Res<FixedChunkWorld>>,
+    chunk_world: Option<ResMut<FixedChunkWorld>>,
+    mut save_errors: EventWriter<SaveErrorEvent>,
+    player: Query<(&Transform, &PlyCamRot), With<CharControl2>>,
 ) {
-    if let (Some(world_info), Some(region_handler), Some(chunk_world)) =
+    if let (Some(world_info), Some(region_handler), Some(mut chunk_world)) =
         (world_info, region_handler, chunk_world)
     {
-        force_sync_regions_save(&world_info, &region_handler, &chunk_world);
+        save_errors.send_batch(
+            force_sync_regions_save(
+                &world_info,
+                saved_player(&player),
+                &region_handler,
+                &mut chunk_world,
+            )
+            .into_iter()
+            .map(SaveErrorEvent),
+        );
     }
 }
 
@@ -95,27 +165,39 @@ fn enter_world_loaded_system(mut next_menu_state: ResMut<NextState<MenuState>>)
 }
 
 // Clean up the whole world! It should be as if NO WORLD HAS BEEN LOADED
+#[allow(clippy::too_many_arguments)]
 fn exit_world_loaded_system(
     mut commands: Commands,
     world_info: Option<Res<WorldInfo>>,
     region_handler: Option<Res<RegionHandlerRes>>,
-    chunk_world: Option<Res<FixedChunkWorld>>,
+    chunk_world: Option<ResMut<FixedChunkWorld>>,
+    mut save_errors: EventWriter<SaveErrorEvent>,
+    player: Query<(&Transform, &PlyCamRot), With<CharControl2>>,
     chunk_query: Query<Entity, With<ChunkEntity>>,
     loaders_query: Query<Entity, With<ChunkLoader>>,
 ) {
-    if let (Some(world_info), Some(region_handler), Some(chunk_world)) =
+    if let (Some(world_info), Some(region_handler), Some(mut chunk_world)) =
         (world_info, region_handler, chunk_world)
     {
-        force_sync_regions_save(&world_info, &region_handler, &chunk_world);
+        save_errors.send_batch(
+            force_sync_regions_save(
+                &world_info,
+                saved_player(&player),
+                &region_handler,
+                &mut chunk_world,
+            )
+            .into_iter()
+            .map(SaveErrorEvent),
+        );
     }
 
     for chunk in chunk_query.iter() {
-        commands.entity(chunk).despawn();
+        commands.entity(chunk).despawn_recursive();
     }
     for loader in loaders_query.iter() {
         commands.entity(loader).remove::<ChunkLoader>();
     }
-    commands.remove_resource::<WorldNoiseSettings>();
+    commands.remove_resource::<WorldGeneratorRes>();
     commands.remove_resource::<FixedChunkWorld>();
     commands.remove_resource::<RegionHandlerRes>();
     commands.remove_resource::<WorldInfo>();
@@ -125,29 +207,55 @@ fn exit_world_loaded_system(
 // I'm leaving it :D
 fn check_for_world_finish_load_system(
     mut next_world_state: ResMut<NextState<WorldState>>,
+    world_info: Res<WorldInfo>,
+    generator: Res<WorldGeneratorRes>,
     chunk_world: Res<FixedChunkWorld>,
     mut ply: Query<&mut Transform, With<CharControl2>>,
-    chunks: Query<(), (With<Collider>, With<ChunkEntity>)>,
+    chunks: Query<(Has<Collider>, Option<&Children>), With<ChunkEntity>>,
+    sub_meshes: Query<&ChunkSubMesh>,
 ) {
-    if let Some(heightmap) = chunk_world.heightmaps.get(&IVec2::ZERO) {
-        let height = heightmap.heightmap[(CHUNK_SQUARE / 2) as usize] as i32 + 5;
-        if let Ok(mut transform) = ply.get_single_mut() {
+    // Saved worlds resume where the player was, new ones spawn above the
+    // ground in the middle of the origin chunk.
+    let spawn = match world_info.saved_player() {
+        Some(saved) => saved.transform.translation,
+        None => {
+            let Some(height) = generator
+                .0
+                .spawn_height(chunk_world.column_noise.get(IVec2::ZERO).as_deref())
+            else {
+                return;
+            };
+            let height = height + 5;
             // Middle of the chunk
-            transform.translation = UVec3::new(CHUNK_WIDTH, 0, CHUNK_WIDTH).as_vec3() / 2.0;
-            transform.translation.y = height as f32;
-            if let Some(LoadedChunk {
-                state: ChunkState::Rendered,
-                entity,
-                ..
-            }) = chunk_world.chunks.get(&ChunkPos(IVec3::new(
-                0,
-                height.div_euclid(CHUNK_WIDTH as i32),
-                0,
-            ))) {
-                // Make sure the chunk entity exists.
-                // Shouldn't be possible for it not to, but
-                // I need to make my `.entity()` calls safer in the future
-                if chunks.get(*entity).is_ok() {
+            let mut spawn = UVec3::new(CHUNK_WIDTH, 0, CHUNK_WIDTH).as_vec3() / 2.0;
+            spawn.y = height as f32;
+            spawn
+        }
+    };
+
+    if let Ok(mut transform) = ply.get_single_mut() {
+        // Hold the player in place so they don't fall before the ground loads
+        transform.translation = spawn;
+        if let Some(LoadedChunk {
+            state: ChunkState::Rendered,
+            entity,
+            ..
+        }) = chunk_world
+            .chunks
+            .get(&ChunkPos::from(VoxelPos(spawn.floor().as_ivec3())))
+        {
+            // Make sure the chunk entity exists.
+            // Shouldn't be possible for it not to, but
+            // I need to make my `.entity()` calls safer in the future
+            // Chunks without a solid mesh never get a collider, so don't
+            // wait for one if the player was saved in the air.
+            if let Ok((has_collider, children)) = chunks.get(*entity) {
+                let has_solid_mesh = children.is_some_and(|children| {
+                    sub_meshes
+                        .iter_many(children)
+                        .any(|ChunkSubMesh(layer)| *layer != MeshLayer::Translucent)
+                });
+                if has_collider || !has_solid_mesh {
                     next_world_state.set(WorldState::WorldLoaded);
                 }
             }
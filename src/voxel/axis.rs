@@ -1,5 +1,5 @@
 use super::CHUNK_WIDTH;
-use bevy::math::{IVec3, UVec2, UVec3};
+use bevy::math::{IVec2, IVec3, UVec2, UVec3};
 
 pub const SLICE_DIRECTIONS: [SliceDirection; 6] = [
     // Normal towards +Z
@@ -64,6 +64,15 @@ impl VoxelAxis {
     pub const fn is_positive(self) -> bool {
         matches!(self, Self::PosX | Self::PosY | Self::PosZ)
     }
+
+    /// Which of X, Y and Z the axis runs along, as 0, 1 or 2.
+    pub const fn index(self) -> usize {
+        match self {
+            Self::PosX | Self::NegX => 0,
+            Self::PosY | Self::NegY => 1,
+            Self::PosZ | Self::NegZ => 2,
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
@@ -106,9 +115,15 @@ impl SliceDirection {
     }
 
     pub fn exclusive_transform(&self, slice_depth: u32, slice_pos: UVec2) -> IVec3 {
-        let mut pos = self.right.to_ivec3() * slice_pos.x as i32
-            + self.up.to_ivec3() * slice_pos.y as i32
-            + self.normal().to_ivec3() * slice_depth as i32;
+        self.signed_transform(slice_depth as i32, slice_pos.as_ivec2())
+    }
+
+    /// Same as [`Self::exclusive_transform`], but the slice position and
+    /// depth can be outside the chunk too.
+    pub fn signed_transform(&self, slice_depth: i32, slice_pos: IVec2) -> IVec3 {
+        let mut pos = self.right.to_ivec3() * slice_pos.x
+            + self.up.to_ivec3() * slice_pos.y
+            + self.normal().to_ivec3() * slice_depth;
 
         if !self.right.is_positive() {
             pos += self.right.negate().to_ivec3() * (CHUNK_WIDTH as i32 - 1);
@@ -122,4 +137,20 @@ impl SliceDirection {
 
         pos
     }
+
+    /// The inverse of [`Self::signed_transform`], giving the slice position
+    /// and depth of a position in (or just outside) the chunk.
+    pub fn inverse_transform(&self, pos: IVec3) -> (IVec2, i32) {
+        let along = |axis: VoxelAxis| {
+            let dot = pos.dot(axis.to_ivec3());
+            match axis.is_positive() {
+                true => dot,
+                false => dot + CHUNK_WIDTH as i32 - 1,
+            }
+        };
+        (
+            IVec2::new(along(self.right), along(self.up)),
+            along(self.normal),
+        )
+    }
 }
@@ -1,9 +1,17 @@
+use bevy::prelude::*;
 use enum_iterator::{cardinality, Sequence};
 use itertools::iproduct;
 use num_enum::{FromPrimitive, IntoPrimitive};
-use std::fmt::{Display, Formatter};
+use serde::Deserialize;
+use std::{
+    fmt::{Display, Formatter},
+    sync::Arc,
+};
+use thiserror::Error;
 
-#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Sequence, IntoPrimitive, FromPrimitive)]
+#[derive(
+    Debug, Default, Copy, Clone, Eq, PartialEq, Sequence, IntoPrimitive, FromPrimitive, Deserialize,
+)]
 #[repr(u8)]
 pub enum BiomeTemperature {
     #[default]
@@ -32,7 +40,9 @@ impl Display for BiomeTemperature {
     }
 }
 
-#[derive(Debug, Default, Copy, Clone, Eq, PartialEq, Sequence, IntoPrimitive, FromPrimitive)]
+#[derive(
+    Debug, Default, Copy, Clone, Eq, PartialEq, Sequence, IntoPrimitive, FromPrimitive, Deserialize,
+)]
 #[repr(u8)]
 pub enum BiomeHumidity {
     #[default]
@@ -65,20 +75,66 @@ impl Display for BiomeHumidity {
     }
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+fn default_weight() -> f64 {
+    1.0
+}
+
+fn default_surface_block() -> String {
+    "grass".to_string()
+}
+
+fn default_filler_block() -> String {
+    "dirt".to_string()
+}
+
+fn default_filler_depth() -> u32 {
+    3
+}
+
+fn default_height_scale() -> f64 {
+    1.0
+}
+
+/// A single biome, as defined in the biome table file. It goes in every
+/// cell of the table that matches one of its temperatures and one of its
+/// humidities.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
 pub struct Biome {
     name: String,
-    temperature: BiomeTemperature,
-    humidity: BiomeHumidity,
+    temperatures: Vec<BiomeTemperature>,
+    humidities: Vec<BiomeHumidity>,
+    /// How likely this biome is compared to the others in the same
+    /// temperature/humidity cell.
+    #[serde(default = "default_weight")]
+    weight: f64,
+    /// Name of the voxel on top of each column.
+    #[serde(default = "default_surface_block")]
+    surface_block: String,
+    /// Name of the voxel between the surface and the stone.
+    #[serde(default = "default_filler_block")]
+    filler_block: String,
+    #[serde(default = "default_filler_depth")]
+    filler_depth: u32,
+    /// The base terrain height is multiplied by this...
+    #[serde(default = "default_height_scale")]
+    height_scale: f64,
+    /// ...and then this is added on.
+    #[serde(default)]
+    height_offset: f64,
 }
 
-#[allow(unused)]
 impl Biome {
     pub fn new(name: &str, temperature: BiomeTemperature, humidity: BiomeHumidity) -> Self {
         Self {
             name: name.to_string(),
-            temperature,
-            humidity,
+            temperatures: vec![temperature],
+            humidities: vec![humidity],
+            weight: default_weight(),
+            surface_block: default_surface_block(),
+            filler_block: default_filler_block(),
+            filler_depth: default_filler_depth(),
+            height_scale: default_height_scale(),
+            height_offset: 0.0,
         }
     }
 
@@ -94,59 +150,335 @@ impl Biome {
         &self.name
     }
 
-    pub fn temperature(&self) -> BiomeTemperature {
-        self.temperature
+    pub fn surface_block(&self) -> &str {
+        &self.surface_block
+    }
+
+    pub fn filler_block(&self) -> &str {
+        &self.filler_block
+    }
+
+    pub fn filler_depth(&self) -> u32 {
+        self.filler_depth
+    }
+
+    /// Apply this biome's height modifier to the base terrain height.
+    pub fn modify_height(&self, height: f64) -> f64 {
+        height * self.height_scale + self.height_offset
+    }
+}
+
+/// The format of the RON file the biome table is loaded from.
+#[derive(Debug, Deserialize)]
+pub struct BiomeTableFile {
+    pub biomes: Vec<Biome>,
+}
+
+#[derive(Debug, Error)]
+pub enum BiomeTableError {
+    #[error("biome \"{0}\" needs a weight above 0")]
+    BadWeight(String),
+    #[error("biome \"{0}\" needs at least one temperature and humidity")]
+    NoCells(String),
+}
+
+/// Climate noise rarely gets anywhere near ±1, so this is treated as the
+/// edge of the table instead. Anything past it is clamped into the
+/// outermost cells.
+const NOISE_RANGE: f64 = 0.6;
+
+/// How much of a cell's variant range is spent fading between neighboring
+/// biomes in the same cell, either side of the border.
+const VARIANT_BLEND: f64 = 0.05;
+
+/// The biomes affecting a single column, found with [`BiomeTable::blend`].
+#[derive(Debug, Clone)]
+pub struct BiomeBlend {
+    /// Index of the biome the column is actually in, which decides the
+    /// blocks.
+    pub dominant: usize,
+    /// Indices of the nearby biomes and how much each affects the column's
+    /// height. The weights add up to 1.
+    pub weights: Vec<(usize, f64)>,
+}
+
+/// The biomes in one temperature/humidity cell of a [`BiomeTable`].
+#[derive(Debug, Copy, Clone)]
+pub struct BiomeCell<'a> {
+    pub temperature: BiomeTemperature,
+    pub humidity: BiomeHumidity,
+    table: &'a BiomeTable,
+    entries: &'a [usize],
+}
+
+impl<'a> BiomeCell<'a> {
+    /// Pick one of the cell's biomes with raw noise from the variant
+    /// channel.
+    pub fn choose(&self, variant: f64) -> &'a Biome {
+        self.table.biome(self.variant_weights(variant).0)
     }
 
-    pub fn humidity(&self) -> BiomeHumidity {
-        self.humidity
+    /// The biome the variant noise lands in, and the weights of the biomes
+    /// blended into the column.
+    fn variant_weights(&self, variant: f64) -> (usize, Vec<(usize, f64)>) {
+        let weights = self
+            .entries
+            .iter()
+            .map(|index| self.table.biome(*index).weight)
+            .collect::<Vec<_>>();
+        let total = weights.iter().sum::<f64>();
+        let smallest = weights.iter().copied().fold(f64::INFINITY, f64::min);
+        let band = (VARIANT_BLEND * total).min(smallest / 2.0);
+        let position = BiomeTable::normalize(variant) * total;
+
+        let mut start = 0.0;
+        for (i, weight) in weights.iter().enumerate() {
+            let end = start + weight;
+            let is_last = i == weights.len() - 1;
+            if position < end || is_last {
+                let index = self.entries[i];
+                // Fade into the biome on whichever side is close enough
+                let blended = if i > 0 && position < start + band {
+                    let t = (position - (start - band)) / (2.0 * band);
+                    vec![(self.entries[i - 1], 1.0 - t), (index, t)]
+                } else if !is_last && position > end - band {
+                    let t = (position - (end - band)) / (2.0 * band);
+                    vec![(index, 1.0 - t), (self.entries[i + 1], t)]
+                } else {
+                    vec![(index, 1.0)]
+                };
+                return (index, blended);
+            }
+            start = end;
+        }
+        unreachable!("biome cells always have at least one biome")
     }
 }
 
-#[allow(unused)]
-#[derive(Clone)]
-pub struct BiomeTable {
+#[derive(Debug)]
+struct BiomeTableInner {
     biomes: Vec<Biome>,
+    /// Indices into `biomes` for every temperature/humidity cell.
+    cells: Vec<Vec<usize>>,
     temperature_max: u8,
     humidity_max: u8,
 }
 
-#[allow(unused)]
+/// Every biome, sorted into cells by temperature and humidity. Cheap to
+/// clone, so it can be sent to generation tasks.
+#[derive(Debug, Clone, Asset, Resource, TypePath)]
+pub struct BiomeTable(Arc<BiomeTableInner>);
+
 impl BiomeTable {
-    pub fn new() -> Self {
+    /// Cells without any biomes are filled with a placeholder.
+    pub fn new(mut biomes: Vec<Biome>) -> Result<Self, BiomeTableError> {
         let temp_max = cardinality::<BiomeTemperature>();
         let humid_max = cardinality::<BiomeHumidity>();
         let temp_max_b = temp_max as u8;
         let humid_max_b = humid_max as u8;
-        let mut biomes = Vec::with_capacity(temp_max * humid_max);
+
+        let mut cells = vec![vec![]; temp_max * humid_max];
+        for (index, biome) in biomes.iter().enumerate() {
+            if biome.weight.is_nan() || biome.weight <= 0.0 {
+                return Err(BiomeTableError::BadWeight(biome.name.clone()));
+            }
+            if biome.temperatures.is_empty() || biome.humidities.is_empty() {
+                return Err(BiomeTableError::NoCells(biome.name.clone()));
+            }
+            for (&temp, &humid) in iproduct!(&biome.temperatures, &biome.humidities) {
+                let temp: u8 = temp.into();
+                let humid: u8 = humid.into();
+                let cell = &mut cells[temp as usize * humid_max + humid as usize];
+                // Listing the same temperature or humidity twice doesn't
+                // make the biome any more likely
+                if !cell.contains(&index) {
+                    cell.push(index);
+                }
+            }
+        }
+
         for (temp, humid) in iproduct!(0..temp_max_b, 0..humid_max_b) {
-            biomes.push(Biome::empty_at(temp.into(), humid.into()));
+            let cell = &mut cells[temp as usize * humid_max + humid as usize];
+            if cell.is_empty() {
+                cell.push(biomes.len());
+                biomes.push(Biome::empty_at(temp.into(), humid.into()));
+            }
         }
-        Self {
+
+        Ok(Self(Arc::new(BiomeTableInner {
             biomes,
+            cells,
             temperature_max: temp_max_b,
             humidity_max: humid_max_b,
-        }
+        })))
     }
 
-    /// Will overwrite existing biomes in this position.
-    /// Todo: Keep multiple per index?
-    pub fn insert(&mut self, biome: Biome) {
-        let temp_max = self.temperature_max;
-        let temp: u8 = biome.temperature.into();
+    pub fn biome(&self, index: usize) -> &Biome {
+        &self.0.biomes[index]
+    }
 
-        if temp < temp_max {
-            let humid_max = self.humidity_max;
-            let humid: u8 = biome.humidity.into();
+    pub fn biomes(&self) -> &[Biome] {
+        &self.0.biomes
+    }
 
-            if humid < humid_max {
-                let temp_u = temp as usize;
-                let humid_u = humid as usize;
-                let humid_max_u = humid_max as usize;
-                let index = temp_u * humid_max_u + humid_u;
+    /// Find the cell for raw temperature and humidity noise.
+    pub fn lookup(&self, temperature: f64, humidity: f64) -> BiomeCell<'_> {
+        let temp = Self::bucket(temperature, self.0.temperature_max);
+        let humid = Self::bucket(humidity, self.0.humidity_max);
+        self.cell(temp, humid)
+    }
 
-                self.biomes[index] = biome;
+    /// Find the biomes for a column from its raw temperature, humidity and
+    /// variant noise. The column's height is bilinearly blended between the
+    /// centers of the nearest cells in the table (and faded between biomes
+    /// in the same cell), so the terrain changes smoothly across biome
+    /// borders instead of jumping straight to the new height.
+    pub fn blend(&self, temperature: f64, humidity: f64, variant: f64) -> BiomeBlend {
+        let temp_max = self.0.temperature_max;
+        let humid_max = self.0.humidity_max;
+        let dominant = self
+            .lookup(temperature, humidity)
+            .variant_weights(variant)
+            .0;
+
+        // Cells are centered on n + 0.5, so shift over to find the pair of
+        // centers on either side of the column.
+        let neighbors = |noise: f64, max: u8| {
+            let coord = Self::normalize(noise) * max as f64;
+            let shifted = (coord - 0.5).clamp(0.0, (max - 1) as f64);
+            let low = shifted.floor() as u8;
+            let high = (low + 1).min(max - 1);
+            (low, high, shifted - low as f64)
+        };
+        let (temp_low, temp_high, temp_t) = neighbors(temperature, temp_max);
+        let (humid_low, humid_high, humid_t) = neighbors(humidity, humid_max);
+
+        let mut weights = vec![];
+        for (temp, humid, cell_weight) in [
+            (temp_low, humid_low, (1.0 - temp_t) * (1.0 - humid_t)),
+            (temp_low, humid_high, (1.0 - temp_t) * humid_t),
+            (temp_high, humid_low, temp_t * (1.0 - humid_t)),
+            (temp_high, humid_high, temp_t * humid_t),
+        ] {
+            let (_, cell_weights) = self.cell(temp, humid).variant_weights(variant);
+            for (index, weight) in cell_weights {
+                weights.push((index, weight * cell_weight));
             }
         }
+
+        BiomeBlend { dominant, weights }
+    }
+
+    /// Blend the height modifiers of the biomes affecting a column.
+    pub fn blend_height(&self, blend: &BiomeBlend, height: f64) -> f64 {
+        blend
+            .weights
+            .iter()
+            .map(|(index, weight)| self.biome(*index).modify_height(height) * weight)
+            .sum()
+    }
+
+    fn cell(&self, temp: u8, humid: u8) -> BiomeCell<'_> {
+        let index = temp as usize * self.0.humidity_max as usize + humid as usize;
+        BiomeCell {
+            temperature: temp.into(),
+            humidity: humid.into(),
+            table: self,
+            entries: &self.0.cells[index],
+        }
+    }
+
+    /// Map raw climate noise onto `0.0..=1.0`.
+    pub fn normalize(noise: f64) -> f64 {
+        ((noise / NOISE_RANGE + 1.0) / 2.0).clamp(0.0, 1.0)
+    }
+
+    fn bucket(noise: f64, max: u8) -> u8 {
+        ((Self::normalize(noise) * max as f64) as u8).min(max - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(biomes: &str) -> BiomeTable {
+        let file: BiomeTableFile = ron::de::from_str(biomes).unwrap();
+        BiomeTable::new(file.biomes).unwrap()
+    }
+
+    #[test]
+    fn lookup_clamps_to_table_edges() {
+        let table = table(
+            r#"(biomes: [
+                (name: "Cold", temperatures: [AlvarPolar], humidities: [SuperArid]),
+                (name: "Hot", temperatures: [PreMontane], humidities: [SuperHumid]),
+            ])"#,
+        );
+
+        for noise in [-NOISE_RANGE, -1.0, -100.0] {
+            let cell = table.lookup(noise, noise);
+            assert_eq!(cell.temperature, BiomeTemperature::AlvarPolar);
+            assert_eq!(cell.humidity, BiomeHumidity::SuperArid);
+            assert_eq!(cell.choose(0.0).name(), "Cold");
+        }
+        for noise in [NOISE_RANGE, 1.0, 100.0] {
+            let cell = table.lookup(noise, noise);
+            assert_eq!(cell.temperature, BiomeTemperature::PreMontane);
+            assert_eq!(cell.humidity, BiomeHumidity::SuperHumid);
+            assert_eq!(cell.choose(0.0).name(), "Hot");
+        }
+
+        // The other corners only have placeholders
+        let cell = table.lookup(-NOISE_RANGE, NOISE_RANGE);
+        assert_eq!(cell.humidity, BiomeHumidity::SuperHumid);
+        assert_eq!(cell.choose(0.0).name(), "Polar SuperHumid Place");
+    }
+
+    #[test]
+    fn choose_splits_by_weight() {
+        let table = table(
+            r#"(biomes: [
+                (name: "Common", temperatures: [Montane], humidities: [Arid], weight: 3.0),
+                (name: "Rare", temperatures: [Montane], humidities: [Arid]),
+            ])"#,
+        );
+        let cell = table.cell(BiomeTemperature::Montane.into(), BiomeHumidity::Arid.into());
+
+        // Evenly spread variant noise lands in each biome in proportion to
+        // its weight
+        let steps = 1000;
+        let chosen = (0..steps)
+            .map(|i| {
+                let t = (i as f64 + 0.5) / steps as f64;
+                cell.choose((t * 2.0 - 1.0) * NOISE_RANGE).name()
+            })
+            .collect::<Vec<_>>();
+        let common = chosen.iter().filter(|name| **name == "Common").count();
+        assert_eq!(common, 750);
+        // Lower noise always picks the earlier biome
+        assert!(chosen[..common].iter().all(|name| *name == "Common"));
+    }
+
+    #[test]
+    fn biomes_fill_every_listed_cell() {
+        let table = table(
+            r#"(biomes: [
+                (name: "Wide", temperatures: [Alpine, SubAlpine], humidities: [Arid, Humid]),
+            ])"#,
+        );
+        for (temp, humid) in iproduct!(0..6, 0..8) {
+            let cell = table.cell(temp, humid);
+            let expected = matches!(
+                (cell.temperature, cell.humidity),
+                (
+                    BiomeTemperature::Alpine | BiomeTemperature::SubAlpine,
+                    BiomeHumidity::Arid | BiomeHumidity::Humid
+                )
+            );
+            assert_eq!(cell.choose(0.0).name() == "Wide", expected);
+        }
+        // Only one copy of the biome itself, plus placeholders for the rest
+        assert_eq!(table.biomes().len(), 1 + 6 * 8 - 4);
     }
 }
@@ -1,6 +1,6 @@
 use crate::voxel::{
-    InChunkPos, NeighborChunkSlices, SliceDirection, Voxel, VoxelContainer, CHUNK_CUBE,
-    CHUNK_WIDTH, SLICE_DIRECTIONS,
+    ChunkBitmask, InChunkPos, LightContainer, MeshLayer, MeshNeighbors, SliceDirection, Voxel,
+    VoxelContainer, VoxelLight, VoxelRegistry, CHUNK_SQUARE, CHUNK_WIDTH, SLICE_DIRECTIONS,
 };
 use bevy::prelude::*;
 use bitvec::prelude::BitVec;
@@ -9,30 +9,45 @@ use itertools::iproduct;
 #[derive(Default, Debug, Clone)]
 pub struct Chunk {
     pub(crate) voxels: VoxelContainer,
+    /// Filled in by [`light_chunk`](crate::voxel::light_chunk) and kept up
+    /// to date by the rest of the lighting functions, setting voxels doesn't
+    /// touch it.
+    pub(crate) light: LightContainer,
     pub definitely_empty: bool,
     /// Make sure you call the update method if the voxels change.
-    pub(crate) edge_slice_bits: NeighborChunkSlices,
+    pub(crate) edge_slice_bits: MeshNeighbors,
     pub edges_dirty: bool,
+    /// Set whenever a voxel changes, and cleared once the chunk has been
+    /// handed over to the region handler to be saved.
+    pub modified: bool,
 }
 
 impl Chunk {
-    pub fn from_container(voxels: VoxelContainer) -> Self {
+    pub fn from_container(voxels: VoxelContainer, registry: &VoxelRegistry) -> Self {
         let mut this_chunk = Self {
             voxels,
             ..default()
         };
-        this_chunk.update_edge_slice_bits();
+        this_chunk.update_edge_slice_bits(registry);
         this_chunk
     }
 
-    #[allow(unused)]
     pub fn at(&self, pos: InChunkPos) -> Voxel {
         self.voxels.at(pos)
     }
 
+    pub fn light_at(&self, pos: InChunkPos) -> VoxelLight {
+        self.light.at(pos)
+    }
+
     pub fn set(&mut self, pos: InChunkPos, voxel: Voxel) {
+        // Don't dirty the chunk (and get it saved again) for nothing
+        if self.voxels.at(pos) == voxel {
+            return;
+        }
         self.voxels.set(pos, voxel);
-        if voxel != Voxel::Air {
+        self.modified = true;
+        if !voxel.is_air() {
             self.definitely_empty = false;
         }
         if !self.edges_dirty && (pos.min_element() == 0 || pos.max_element() == CHUNK_WIDTH - 1) {
@@ -40,36 +55,104 @@ impl Chunk {
         }
     }
 
-    pub fn as_slice(&self) -> &[Voxel] {
-        &self.voxels.0
-    }
-
-    pub fn update_edge_slice_bits(&mut self) {
+    pub fn update_edge_slice_bits(&mut self, registry: &VoxelRegistry) {
+        let solid = ChunkBitmask::new(self, |voxel| registry.does_cull_as_solid(voxel));
+        let translucent = ChunkBitmask::new(self, |voxel| registry.is_translucent(voxel));
         for slice_dir in SLICE_DIRECTIONS {
-            let new_bits = self.get_solid_bits_slice(slice_dir, 0).unwrap();
-            let bits_at = self
+            let direction = slice_dir.normal().negate();
+            *self.edge_slice_bits.solid.get_in_direction_mut(direction) =
+                solid.slice_bits(slice_dir, 0);
+            *self
                 .edge_slice_bits
-                .get_in_direction_mut(slice_dir.normal().negate());
-            *bits_at = new_bits;
+                .translucent
+                .get_in_direction_mut(direction) = translucent.slice_bits(slice_dir, 0);
         }
         self.edges_dirty = false;
     }
 
-    // TODO: THIS IS A VERY HOT FUNCTION!
-    //       IT'S TAKING ABOUT 33% OF ALL TIME!
+    /// Good for the odd slice, but anything going through a lot of slices
+    /// should build a [`ChunkBitmask`] once instead.
     pub fn get_solid_bits_slice(
+        &self,
+        registry: &VoxelRegistry,
+        slice_direction: SliceDirection,
+        slice_depth: u32,
+    ) -> Option<BitVec> {
+        self.get_bits_slice(slice_direction, slice_depth, |voxel| {
+            registry.does_cull_as_solid(voxel)
+        })
+    }
+
+    pub fn get_translucent_bits_slice(
+        &self,
+        registry: &VoxelRegistry,
+        slice_direction: SliceDirection,
+        slice_depth: u32,
+    ) -> Option<BitVec> {
+        self.get_bits_slice(slice_direction, slice_depth, |voxel| {
+            registry.is_translucent(voxel)
+        })
+    }
+
+    fn get_bits_slice(
         &self,
         slice_direction: SliceDirection,
         slice_depth: u32,
+        bit: impl Fn(Voxel) -> bool,
     ) -> Option<BitVec> {
-        let mut bit_slice = BitVec::repeat(false, CHUNK_CUBE as usize);
+        let mut bit_slice = BitVec::repeat(false, CHUNK_SQUARE as usize);
         for (y, x) in iproduct!(0..CHUNK_WIDTH, 0..CHUNK_WIDTH) {
             let voxel = self.voxels.at(InChunkPos::new(
                 slice_direction.transform(slice_depth, UVec2::new(x, y))?,
             )?);
             let slice_index = y * CHUNK_WIDTH + x;
-            bit_slice.set(slice_index as usize, voxel.does_cull_as_solid());
+            bit_slice.set(slice_index as usize, bit(voxel));
         }
         Some(bit_slice)
     }
+
+    /// The light in a slice, laid out the same way as the bit slices.
+    pub fn get_light_slice(
+        &self,
+        slice_direction: SliceDirection,
+        slice_depth: u32,
+    ) -> Option<Vec<VoxelLight>> {
+        let mut light_slice = Vec::with_capacity(CHUNK_SQUARE as usize);
+        for (y, x) in iproduct!(0..CHUNK_WIDTH, 0..CHUNK_WIDTH) {
+            light_slice.push(self.light.at(InChunkPos::new(
+                slice_direction.transform(slice_depth, UVec2::new(x, y))?,
+            )?));
+        }
+        Some(light_slice)
+    }
+
+    /// Whether any voxel in the chunk might be drawn in this layer. Only
+    /// checks the palette, so it can be wrong in the safe direction.
+    pub fn may_have_layer(&self, registry: &VoxelRegistry, layer: MeshLayer) -> bool {
+        self.voxels
+            .palette()
+            .iter()
+            .any(|voxel| !voxel.is_air() && registry.mesh_layer(*voxel) == layer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_changes_mark_chunks_modified() {
+        let mut chunk = Chunk::default();
+        let pos = InChunkPos::new(UVec3::new(3, 4, 5)).unwrap();
+
+        chunk.set(pos, Voxel::AIR);
+        assert!(!chunk.modified);
+
+        chunk.set(pos, Voxel(1));
+        assert!(chunk.modified);
+        chunk.modified = false;
+        chunk.set(pos, Voxel(1));
+        assert!(!chunk.modified);
+        assert_eq!(chunk.at(pos), Voxel(1));
+    }
 }
@@ -0,0 +1,181 @@
+use crate::voxel::{Chunk, SliceDirection, Voxel, CHUNK_SQUARE, CHUNK_WIDTH};
+use bevy::math::{IVec2, UVec3};
+use bitvec::prelude::BitVec;
+
+// A whole column of voxels has to fit in one `u32`
+const _: () = assert!(CHUNK_WIDTH <= u32::BITS);
+
+/// Every bit of a slice row set.
+pub const FULL_ROW: u32 = u32::MAX >> (u32::BITS - CHUNK_WIDTH);
+
+/// One row of bits for each `y` in a slice, with bit `x` of a row being the
+/// slice position `(x, y)`.
+pub type SliceRows = [u32; CHUNK_WIDTH as usize];
+
+/// One bit for every voxel in a chunk, saying whether it matches some
+/// condition. The bits are stored three times over, as columns running
+/// along each axis, so any row of any slice can be read as a single `u32`
+/// instead of going through [`SliceDirection::transform`] for every voxel.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkBitmask {
+    /// Indexed by axis, then by [`Self::column_index`]. Bit `i` of a column
+    /// is the voxel `i` voxels along the axis.
+    columns: [Box<[u32]>; 3],
+}
+
+impl ChunkBitmask {
+    pub fn new(chunk: &Chunk, bit: impl Fn(Voxel) -> bool) -> Self {
+        // Only ask about each voxel type once
+        let palette_bits = chunk
+            .voxels
+            .palette()
+            .iter()
+            .map(|voxel| bit(*voxel))
+            .collect::<Vec<_>>();
+        if let Some(&first) = palette_bits.first() {
+            if palette_bits.iter().all(|bit| *bit == first) {
+                return Self::splat(first);
+            }
+        }
+
+        let mut mask = Self::splat(false);
+        for (index, palette_index) in chunk.voxels.palette_indices().enumerate() {
+            if palette_bits[palette_index] {
+                let index = index as u32;
+                mask.set(UVec3::new(
+                    index % CHUNK_WIDTH,
+                    index / CHUNK_WIDTH % CHUNK_WIDTH,
+                    index / CHUNK_SQUARE,
+                ));
+            }
+        }
+        mask
+    }
+
+    pub fn splat(bit: bool) -> Self {
+        let column = match bit {
+            true => FULL_ROW,
+            false => 0,
+        };
+        Self {
+            columns: std::array::from_fn(|_| vec![column; CHUNK_SQUARE as usize].into()),
+        }
+    }
+
+    /// Which column along the axis the position is in, from its other two
+    /// coordinates.
+    fn column_index(axis: usize, pos: UVec3) -> usize {
+        let (u, v) = match axis {
+            0 => (pos.y, pos.z),
+            1 => (pos.x, pos.z),
+            _ => (pos.x, pos.y),
+        };
+        (v * CHUNK_WIDTH + u) as usize
+    }
+
+    fn set(&mut self, pos: UVec3) {
+        for axis in 0..3 {
+            self.columns[axis][Self::column_index(axis, pos)] |= 1 << pos[axis];
+        }
+    }
+
+    /// The bits along the slice's right axis, for row `y` of the slice at
+    /// this depth.
+    pub fn row(&self, slice_direction: SliceDirection, slice_depth: u32, y: u32) -> u32 {
+        let start = slice_direction
+            .signed_transform(slice_depth as i32, IVec2::new(0, y as i32))
+            .as_uvec3();
+        let axis = slice_direction.right.index();
+        let column = self.columns[axis][Self::column_index(axis, start)];
+        match slice_direction.right.is_positive() {
+            true => column,
+            // The slice starts from the far end of the column
+            false => column.reverse_bits() >> (u32::BITS - CHUNK_WIDTH),
+        }
+    }
+
+    pub fn rows(&self, slice_direction: SliceDirection, slice_depth: u32) -> SliceRows {
+        std::array::from_fn(|y| self.row(slice_direction, slice_depth, y as u32))
+    }
+
+    /// The same slice as [`Chunk::get_solid_bits_slice`] and friends would
+    /// give for this bitmask's condition.
+    pub fn slice_bits(&self, slice_direction: SliceDirection, slice_depth: u32) -> BitVec {
+        let mut bit_slice = BitVec::repeat(false, CHUNK_SQUARE as usize);
+        for (y, row) in self
+            .rows(slice_direction, slice_depth)
+            .into_iter()
+            .enumerate()
+        {
+            for x in 0..CHUNK_WIDTH as usize {
+                if row & (1 << x) != 0 {
+                    bit_slice.set(y * CHUNK_WIDTH as usize + x, true);
+                }
+            }
+        }
+        bit_slice
+    }
+}
+
+/// Turn a slice of bits, laid out like [`ChunkBitmask::slice_bits`], back
+/// into rows.
+pub fn slice_rows(bit_slice: &BitVec) -> SliceRows {
+    let mut rows = [0; CHUNK_WIDTH as usize];
+    for index in bit_slice.iter_ones() {
+        rows[index / CHUNK_WIDTH as usize] |= 1 << (index % CHUNK_WIDTH as usize);
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::{InChunkPos, VoxelContainer, VoxelRegistry, SLICE_DIRECTIONS};
+    use itertools::iproduct;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    #[test]
+    fn slices_match_chunk_slices() {
+        let registry = VoxelRegistry::builtin();
+        let voxels = ["air", "stone", "water", "dirt"].map(|name| registry.by_name(name).unwrap());
+        let mut rng = StdRng::seed_from_u64(31);
+        let mut chunk = Chunk::default();
+        for (x, y, z) in iproduct!(0..CHUNK_WIDTH, 0..CHUNK_WIDTH, 0..CHUNK_WIDTH) {
+            chunk.set(
+                InChunkPos::new(UVec3::new(x, y, z)).unwrap(),
+                voxels[rng.gen_range(0..voxels.len())],
+            );
+        }
+
+        let solid = ChunkBitmask::new(&chunk, |voxel| registry.does_cull_as_solid(voxel));
+        let translucent = ChunkBitmask::new(&chunk, |voxel| registry.is_translucent(voxel));
+        for (slice_direction, depth) in iproduct!(SLICE_DIRECTIONS, 0..CHUNK_WIDTH) {
+            let solid_bits = chunk
+                .get_solid_bits_slice(&registry, slice_direction, depth)
+                .unwrap();
+            assert_eq!(solid.slice_bits(slice_direction, depth), solid_bits);
+            assert_eq!(slice_rows(&solid_bits), solid.rows(slice_direction, depth));
+            assert_eq!(
+                translucent.slice_bits(slice_direction, depth),
+                chunk
+                    .get_translucent_bits_slice(&registry, slice_direction, depth)
+                    .unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn uniform_chunks_are_splatted() {
+        let registry = VoxelRegistry::builtin();
+        let stone = Chunk::from_container(
+            VoxelContainer::from_voxel(registry.by_name("stone").unwrap()),
+            &registry,
+        );
+        let solid = ChunkBitmask::new(&stone, |voxel| registry.does_cull_as_solid(voxel));
+        assert_eq!(solid, ChunkBitmask::splat(true));
+        assert!(solid
+            .rows(SLICE_DIRECTIONS[0], 0)
+            .iter()
+            .all(|row| *row == FULL_ROW));
+    }
+}
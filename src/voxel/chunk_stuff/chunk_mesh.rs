@@ -1,41 +1,111 @@
 use crate::{
     plugin::voxel_world::voxel_material::ATTRIBUTE_HACK_VERT,
     voxel::{
-        Chunk, InChunkPos, NeighborChunkSlices, SliceDirection, Voxel, CHUNK_SQUARE, CHUNK_WIDTH,
+        slice_rows, Chunk, ChunkBitmask, InChunkPos, MeshNeighbors, SliceDirection, SliceRows,
+        Voxel, VoxelAxis, VoxelLight, VoxelRegistry, CHUNK_SQUARE, CHUNK_WIDTH, FULL_ROW,
         SLICE_DIRECTIONS,
     },
 };
 use bevy::{
-    math::{UVec2, Vec3},
+    math::{IVec2, IVec3, UVec2, UVec3, Vec3},
     prelude::Mesh,
     render::mesh::{Indices, PrimitiveTopology},
 };
-use bevy_rapier3d::prelude::Collider;
 use bitvec::prelude::BitVec;
 use itertools::iproduct;
 
+/// What a single voxel face looks like. Faces are keyed by their texture
+/// rather than by voxel type, so different voxels that look the same on a
+/// given side can be merged, as long as they're shaded the same too.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct QuadFace {
+    pub atlas_index: u32,
+    /// Ambient occlusion at each corner, in the same order as the vertices
+    /// from [`TmpChunkMesh::build_hack_verts`]. See [`vertex_ao`].
+    pub ao: [u8; 4],
+    /// The light in the voxel the face looks out into.
+    pub light: VoxelLight,
+}
+
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub struct Quad {
     pub start: UVec2,
     pub end_excl: UVec2,
-    pub voxel: Voxel,
+    pub face: QuadFace,
 }
 
 impl Quad {
-    pub fn new(pos: UVec2, voxel: Voxel) -> Self {
+    pub fn new(pos: UVec2, face: QuadFace) -> Self {
         Self {
             start: pos,
             end_excl: pos + UVec2::ONE,
-            voxel,
+            face,
         }
     }
 }
 
+/// Which of a chunk's meshes a voxel is drawn in.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MeshLayer {
+    Opaque,
+    /// Solid, but with see-through holes in the texture, like glass.
+    Cutout,
+    /// Blended with whatever is behind it, like water.
+    Translucent,
+}
+
+/// Everything needed to display a chunk. Any part can be missing if there's
+/// nothing to put in it. Colliders are built separately, see
+/// [`generate_collider`](crate::voxel::generate_collider).
 #[derive(Default)]
+pub struct ChunkMeshes {
+    pub opaque: Option<Mesh>,
+    pub cutout: Option<Mesh>,
+    pub translucent: Option<Mesh>,
+}
+
+impl ChunkMeshes {
+    pub fn is_empty(&self) -> bool {
+        self.opaque.is_none() && self.cutout.is_none() && self.translucent.is_none()
+    }
+
+    /// Each mesh that isn't empty, along with its layer.
+    pub fn into_layers(self) -> impl Iterator<Item = (MeshLayer, Mesh)> {
+        [
+            (MeshLayer::Opaque, self.opaque),
+            (MeshLayer::Cutout, self.cutout),
+            (MeshLayer::Translucent, self.translucent),
+        ]
+        .into_iter()
+        .filter_map(|(layer, mesh)| mesh.map(|mesh| (layer, mesh)))
+    }
+}
+
+#[derive(Default, Debug, PartialEq)]
 pub struct TmpChunkMesh {
     verts: Vec<Vec3>,
     hacks: Vec<UVec2>,
-    inds: Vec<u16>,
+    /// Always `u32` while building, and only shrunk down to `u16` if the
+    /// mesh turns out small enough.
+    inds: Vec<u32>,
+}
+
+/// Where a corner of a quad in this slice ends up in the chunk. Corners are
+/// between voxels, so they run from 0 to `CHUNK_WIDTH` inclusive.
+pub fn quad_corner(slice_dir: SliceDirection, slice_depth: u32, corner: UVec2) -> UVec3 {
+    let mut pos = slice_dir.exclusive_transform(slice_depth, corner);
+
+    if !slice_dir.right.is_positive() {
+        pos -= slice_dir.right.to_ivec3();
+    }
+    if !slice_dir.up.is_positive() {
+        pos -= slice_dir.up.to_ivec3();
+    }
+    if slice_dir.normal().is_positive() {
+        pos += slice_dir.normal().to_ivec3()
+    }
+
+    pos.as_uvec3()
 }
 
 fn iter_to_array<Element, const N: usize>(mut iter: impl Iterator<Item = Element>) -> [Element; N] {
@@ -57,7 +127,10 @@ impl TmpChunkMesh {
     /// 3x6 bits = 0-32** for each position component
     /// 3 bits for each axis of normal, 1 bit for negative.
     /// 2x5 bits = 0-31 for quad size to determine UV
-    /// 4 bytes for U32 to represent atlas index
+    ///
+    /// A second U32 holds the atlas index in its low 22 bits, then the
+    /// face's block light and sky light (4 bits each), and the vertex's
+    /// ambient occlusion (0-3) in the top 2.
     pub fn build_hack_verts(
         slice_dir: SliceDirection,
         slice_depth: u32,
@@ -104,19 +177,7 @@ impl TmpChunkMesh {
             ]
             .into_iter()
             .map(|(v, uv)| {
-                let mut pos = slice_dir.exclusive_transform(slice_depth, v);
-
-                if !slice_dir.right.is_positive() {
-                    pos -= slice_dir.right.to_ivec3();
-                }
-                if !slice_dir.up.is_positive() {
-                    pos -= slice_dir.up.to_ivec3();
-                }
-                if slice_dir.normal().is_positive() {
-                    pos += slice_dir.normal().to_ivec3()
-                }
-
-                let pos = pos.as_uvec3();
+                let pos = quad_corner(slice_dir, slice_depth, v);
 
                 let mut hack = (pos.x << 26) | (pos.y << 20) | (pos.z << 14);
                 if normal_neg {
@@ -131,77 +192,315 @@ impl TmpChunkMesh {
     }
 
     pub fn add_quad(&mut self, slice_dir: SliceDirection, slice_depth: u32, quad: Quad) {
-        let start_ind = self.hacks.len() as u16;
+        let start_ind = self.hacks.len() as u32;
 
         let verts_hacks = Self::build_hack_verts(slice_dir, slice_depth, quad);
+        let QuadFace {
+            atlas_index,
+            ao,
+            light,
+        } = quad.face;
+        let face_bits = atlas_index | ((light.bits() as u32) << 22);
 
         self.verts
             .append(&mut verts_hacks.map(|(vert, _)| vert).to_vec());
 
         self.hacks.append(
             &mut verts_hacks
-                .map(|(_, hack)| UVec2::new(hack, quad.voxel.atlas_index()))
-                .to_vec(),
-        );
-
-        // Add indices to make a quad
-        self.inds.append(
-            &mut [0u16, 1, 3, 0, 2, 1]
                 .into_iter()
-                .map(|i| start_ind + i)
+                .zip(ao)
+                .map(|((_, hack), ao)| UVec2::new(hack, face_bits | ((ao as u32) << 30)))
                 .collect(),
         );
+
+        // Add indices to make a quad. The quad is split along whichever
+        // diagonal is lighter, otherwise the occlusion of one corner gets
+        // smeared across the whole quad.
+        let indices = match ao[0] + ao[1] < ao[2] + ao[3] {
+            true => [2u32, 3, 0, 2, 1, 3],
+            false => [0u32, 1, 3, 0, 2, 1],
+        };
+        self.inds
+            .append(&mut indices.into_iter().map(|i| start_ind + i).collect());
     }
 
-    pub fn build(self) -> Option<(Collider, Mesh)> {
+    pub fn build(self) -> Option<Mesh> {
         let Self { verts, inds, hacks } = self;
+        if inds.is_empty() {
+            return None;
+        }
+
+        // A chunk full of single exposed voxels, like a 3D checkerboard,
+        // needs more vertices than `u16` can index
+        let indices = match verts.len() <= u16::MAX as usize + 1 {
+            true => Indices::U16(inds.iter().map(|&i| i as u16).collect()),
+            false => Indices::U32(inds),
+        };
+
+        Some(
+            Mesh::new(PrimitiveTopology::TriangleList)
+                .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, verts)
+                .with_inserted_attribute(ATTRIBUTE_HACK_VERT, hacks)
+                .with_indices(Some(indices)),
+        )
+    }
+}
+
+/// The meshes for each layer, before they're turned into real meshes.
+#[derive(Default, Debug, PartialEq)]
+struct LayerMeshes {
+    opaque: TmpChunkMesh,
+    cutout: TmpChunkMesh,
+    translucent: TmpChunkMesh,
+}
 
-        let mut collider_inds = Vec::with_capacity(inds.len() / 3);
-        for i in 0..collider_inds.capacity() {
-            collider_inds.push([
-                inds[3 * i] as u32,
-                inds[3 * i + 1] as u32,
-                inds[3 * i + 2] as u32,
-            ]);
+impl LayerMeshes {
+    fn layer_mut(&mut self, layer: MeshLayer) -> &mut TmpChunkMesh {
+        match layer {
+            MeshLayer::Opaque => &mut self.opaque,
+            MeshLayer::Cutout => &mut self.cutout,
+            MeshLayer::Translucent => &mut self.translucent,
         }
+    }
 
-        match inds.is_empty() {
-            true => None,
-            false => Some((
-                Collider::trimesh(verts.clone(), collider_inds),
-                Mesh::new(PrimitiveTopology::TriangleList)
-                    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, verts)
-                    .with_inserted_attribute(ATTRIBUTE_HACK_VERT, hacks)
-                    .with_indices(Some(Indices::U16(inds))),
-            )),
+    fn build(self) -> ChunkMeshes {
+        ChunkMeshes {
+            opaque: self.opaque.build(),
+            cutout: self.cutout.build(),
+            translucent: self.translucent.build(),
         }
     }
 }
 
-pub fn generate_mesh(chunk: &Chunk, neighbors: NeighborChunkSlices) -> Option<(Collider, Mesh)> {
-    let mut tmp_mesh = TmpChunkMesh::default();
+pub fn generate_mesh(
+    chunk: &Chunk,
+    neighbors: MeshNeighbors,
+    registry: &VoxelRegistry,
+) -> ChunkMeshes {
+    mesh_layers(chunk, &neighbors, registry).build()
+}
+
+/// Works out which faces are visible a whole slice row at a time from
+/// [`ChunkBitmask`]s, then merges them into quads exactly the same way as
+/// [`generate_mesh_by_slices`] does.
+fn mesh_layers(chunk: &Chunk, neighbors: &MeshNeighbors, registry: &VoxelRegistry) -> LayerMeshes {
+    let mut meshes = LayerMeshes::default();
+    if chunk.definitely_empty {
+        return meshes;
+    }
+
+    let neighborhood = SolidNeighborhood {
+        chunk,
+        neighbors,
+        registry,
+    };
+    let solid = ChunkBitmask::new(chunk, |voxel| registry.does_cull_as_solid(voxel));
+
+    for layer in [MeshLayer::Opaque, MeshLayer::Cutout, MeshLayer::Translucent] {
+        if !chunk.may_have_layer(registry, layer) {
+            continue;
+        }
+        let in_layer = ChunkBitmask::new(chunk, |voxel| {
+            !voxel.is_air() && registry.mesh_layer(voxel) == layer
+        });
+        // Translucent faces are also hidden by other translucent voxels, so
+        // we don't mesh the inside of every lake.
+        let translucent = (layer == MeshLayer::Translucent)
+            .then(|| ChunkBitmask::new(chunk, |voxel| registry.is_translucent(voxel)));
+
+        for dir in SLICE_DIRECTIONS {
+            // The last slice is hidden by the edge of the next chunk over
+            let mut neighbor_hidden = slice_rows(neighbors.solid.get_in_direction(dir.normal()));
+            if translucent.is_some() {
+                let neighbor_translucent =
+                    slice_rows(neighbors.translucent.get_in_direction(dir.normal()));
+                for (hidden, translucent) in neighbor_hidden.iter_mut().zip(neighbor_translucent) {
+                    *hidden |= translucent;
+                }
+            }
+
+            for z in 0..CHUNK_WIDTH {
+                let hidden = match z < CHUNK_WIDTH - 1 {
+                    true => {
+                        let mut hidden = solid.rows(dir, z + 1);
+                        if let Some(translucent) = &translucent {
+                            for (hidden, translucent) in
+                                hidden.iter_mut().zip(translucent.rows(dir, z + 1))
+                            {
+                                *hidden |= translucent;
+                            }
+                        }
+                        hidden
+                    }
+                    false => neighbor_hidden,
+                };
+
+                let mut visible = in_layer.rows(dir, z);
+                for (visible, hidden) in visible.iter_mut().zip(hidden) {
+                    *visible &= !hidden;
+                }
+                greedy_mesh_slice(
+                    &neighborhood,
+                    layer,
+                    dir,
+                    z,
+                    visible,
+                    meshes.layer_mut(layer),
+                );
+            }
+        }
+    }
+
+    meshes
+}
 
+/// Merge the visible faces in a slice into quads. Each quad starts at the
+/// first face left, runs along its row for as long as the faces look the
+/// same, and is then grown upwards for as long as the whole run matches.
+fn greedy_mesh_slice(
+    neighborhood: &SolidNeighborhood,
+    layer: MeshLayer,
+    slice_direction: SliceDirection,
+    slice_depth: u32,
+    mut visible: SliceRows,
+    mesh: &mut TmpChunkMesh,
+) {
+    let face_at = |x: u32, y: u32| {
+        voxel_face(
+            neighborhood,
+            layer,
+            slice_direction,
+            slice_depth,
+            UVec2::new(x, y),
+        )
+    };
+
+    for y in 0..CHUNK_WIDTH {
+        while visible[y as usize] != 0 {
+            let row = visible[y as usize];
+            let start_x = row.trailing_zeros();
+            let Some(face) = face_at(start_x, y) else {
+                visible[y as usize] &= !(1 << start_x);
+                continue;
+            };
+
+            let mut end_x = start_x + 1;
+            while end_x < CHUNK_WIDTH && row & (1 << end_x) != 0 && face_at(end_x, y) == Some(face)
+            {
+                end_x += 1;
+            }
+            let run = (FULL_ROW >> (CHUNK_WIDTH - (end_x - start_x))) << start_x;
+            visible[y as usize] &= !run;
+
+            // Only look at the faces once the bits say the whole run is
+            // there, working out their occlusion isn't free
+            let mut end_y = y + 1;
+            while end_y < CHUNK_WIDTH
+                && visible[end_y as usize] & run == run
+                && (start_x..end_x).all(|x| face_at(x, end_y) == Some(face))
+            {
+                visible[end_y as usize] &= !run;
+                end_y += 1;
+            }
+
+            mesh.add_quad(
+                slice_direction,
+                slice_depth,
+                Quad {
+                    start: UVec2::new(start_x, y),
+                    end_excl: UVec2::new(end_x, end_y),
+                    face,
+                },
+            );
+        }
+    }
+}
+
+/// The original mesher, which builds every slice's bits voxel by voxel.
+/// It's much slower than [`generate_mesh`], but simpler, so it's kept
+/// around to check and benchmark that against.
+pub fn generate_mesh_by_slices(
+    chunk: &Chunk,
+    neighbors: MeshNeighbors,
+    registry: &VoxelRegistry,
+) -> ChunkMeshes {
+    mesh_layers_by_slices(chunk, &neighbors, registry).build()
+}
+
+fn mesh_layers_by_slices(
+    chunk: &Chunk,
+    neighbors: &MeshNeighbors,
+    registry: &VoxelRegistry,
+) -> LayerMeshes {
+    let mut meshes = LayerMeshes::default();
+    let neighborhood = SolidNeighborhood {
+        chunk,
+        neighbors,
+        registry,
+    };
     if !chunk.definitely_empty {
+        let has_cutout = chunk.may_have_layer(registry, MeshLayer::Cutout);
+        let has_translucent = chunk.may_have_layer(registry, MeshLayer::Translucent);
+
         for (dir, z) in iproduct!(SLICE_DIRECTIONS, 0..CHUNK_WIDTH) {
+            let solid_bits = if z < CHUNK_WIDTH - 1 {
+                chunk.get_solid_bits_slice(registry, dir, z + 1)
+            } else {
+                Some(neighbors.solid.get_in_direction(dir.normal()).clone())
+            };
+
+            if has_translucent {
+                // Translucent faces are also hidden by other translucent
+                // voxels, so we don't mesh the inside of every lake.
+                let translucent_bits = if z < CHUNK_WIDTH - 1 {
+                    chunk.get_translucent_bits_slice(registry, dir, z + 1)
+                } else {
+                    Some(neighbors.translucent.get_in_direction(dir.normal()).clone())
+                };
+                let hidden_bits = match (&solid_bits, translucent_bits) {
+                    (Some(solid), Some(translucent)) => Some(translucent | solid.as_bitslice()),
+                    (solid, translucent) => solid.clone().or(translucent),
+                };
+                mesh_slice(
+                    &neighborhood,
+                    MeshLayer::Translucent,
+                    dir,
+                    z,
+                    meshes.layer_mut(MeshLayer::Translucent),
+                    hidden_bits,
+                );
+            }
+
+            if has_cutout {
+                // Only hidden by solid voxels, you can see other cutout
+                // voxels through the holes
+                mesh_slice(
+                    &neighborhood,
+                    MeshLayer::Cutout,
+                    dir,
+                    z,
+                    meshes.layer_mut(MeshLayer::Cutout),
+                    solid_bits.clone(),
+                );
+            }
+
             mesh_slice(
-                chunk,
+                &neighborhood,
+                MeshLayer::Opaque,
                 dir,
                 z,
-                &mut tmp_mesh,
-                if z < CHUNK_WIDTH - 1 {
-                    chunk.get_solid_bits_slice(dir, z + 1)
-                } else {
-                    Some(neighbors.get_in_direction(dir.normal()).clone())
-                },
+                meshes.layer_mut(MeshLayer::Opaque),
+                solid_bits,
             );
         }
     }
 
-    tmp_mesh.build()
+    meshes
 }
 
 fn mesh_slice(
-    chunk: &Chunk,
+    neighborhood: &SolidNeighborhood,
+    layer: MeshLayer,
     slice_direction: SliceDirection,
     slice_depth: u32,
     mesh: &mut TmpChunkMesh,
@@ -215,14 +514,6 @@ fn mesh_slice(
         for x in 0..CHUNK_WIDTH {
             let slice_index = (slice_row_index + x) as usize;
 
-            let pos = InChunkPos::new(
-                slice_direction
-                    .transform(slice_depth, UVec2::new(x, y))
-                    .unwrap(),
-            )
-            .unwrap();
-            let voxel = chunk.at(pos);
-
             // If the slice bit for this pos is `true`
             if slice_bits[slice_index]
                 || previous_slice_bits
@@ -238,10 +529,11 @@ fn mesh_slice(
                 if let Some(quad) = current_quad.take() {
                     // Perform quad emit.
                     emit_quad(
+                        neighborhood,
+                        layer,
                         slice_direction,
                         slice_depth,
                         quad,
-                        chunk.as_slice(),
                         &mut slice_bits,
                         &previous_slice_bits,
                         mesh,
@@ -254,10 +546,20 @@ fn mesh_slice(
             // Set slice bit for this pos to `true`
             slice_bits.set(slice_index, true);
 
+            // Only look at the face once we know it isn't hidden, working out
+            // its occlusion isn't free
+            let face = voxel_face(
+                neighborhood,
+                layer,
+                slice_direction,
+                slice_depth,
+                UVec2::new(x, y),
+            );
+
             // If the current quad voxel is `Some`
             if let Some(mut quad) = current_quad.take() {
-                // If the voxel at this position is the same type as the current quad
-                if quad.voxel == voxel {
+                // If the face at this position looks the same as the current quad
+                if face == Some(quad.face) {
                     // Increment quad max end x
                     quad.end_excl.x += 1;
                     // Put the current quad back
@@ -266,35 +568,37 @@ fn mesh_slice(
                 } else {
                     // Perform quad emit.
                     emit_quad(
+                        neighborhood,
+                        layer,
                         slice_direction,
                         slice_depth,
                         quad,
-                        chunk.as_slice(),
                         &mut slice_bits,
                         &previous_slice_bits,
                         mesh,
                     );
 
                     // If the voxel at this position is not air
-                    if voxel != Voxel::Air {
-                        // Set current voxel to `Some` with this type
-                        current_quad = Some(Quad::new(UVec2::new(x, y), voxel));
+                    if let Some(face) = face {
+                        // Set current quad to `Some` with this face
+                        current_quad = Some(Quad::new(UVec2::new(x, y), face));
                     }
                 }
-            } else if voxel != Voxel::Air {
+            } else if let Some(face) = face {
                 // If no current quad and this voxel isn't air, make a new
                 // one.
-                current_quad = Some(Quad::new(UVec2::new(x, y), voxel));
+                current_quad = Some(Quad::new(UVec2::new(x, y), face));
             }
         }
 
         // After the X loop, emit the current quad if it is `Some`
         if let Some(quad) = current_quad.take() {
             emit_quad(
+                neighborhood,
+                layer,
                 slice_direction,
                 slice_depth,
                 quad,
-                chunk.as_slice(),
                 &mut slice_bits,
                 &previous_slice_bits,
                 mesh,
@@ -303,18 +607,20 @@ fn mesh_slice(
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn emit_quad(
+    neighborhood: &SolidNeighborhood,
+    layer: MeshLayer,
     slice_direction: SliceDirection,
     slice_depth: u32,
     mut quad: Quad,
-    voxels: &[Voxel],
     slice_bits: &mut BitVec,
     previous_slice_bits: &Option<BitVec>,
     mesh: &mut TmpChunkMesh,
 ) {
     // Loop through each y value between y+1 and CHUNK_WIDTH:
     //   Loop from quad.start.x up to quad.end.x:
-    //     If any voxels are not equal to the quad's voxel type:
+    //     If any faces don't look the same as the quad:
     //       Break the outer loop
     //
     //   Set the slice_bits to true between quad.start.x and
@@ -325,18 +631,18 @@ fn emit_quad(
         let slice_row_index = y_check * CHUNK_WIDTH;
         for x_check in quad.start.x..quad.end_excl.x {
             let slice_index = (slice_row_index + x_check) as usize;
-            let in_pos = InChunkPos::new(
-                slice_direction
-                    .transform(slice_depth, UVec2::new(x_check, y_check))
-                    .unwrap(),
-            )
-            .unwrap();
-            if voxels[in_pos.index()] != quad.voxel
-                || slice_bits[slice_index]
+            if slice_bits[slice_index]
                 || previous_slice_bits
                     .as_ref()
                     .map(|b| b[slice_index])
                     .unwrap_or(false)
+                || voxel_face(
+                    neighborhood,
+                    layer,
+                    slice_direction,
+                    slice_depth,
+                    UVec2::new(x_check, y_check),
+                ) != Some(quad.face)
             {
                 break 'outer;
             }
@@ -351,3 +657,549 @@ fn emit_quad(
 
     mesh.add_quad(slice_direction, slice_depth, quad);
 }
+
+/// The texture of the voxel's face in this slice direction, or `None` for
+/// air, which has no faces, and for voxels drawn in a different layer.
+fn face_atlas_index(
+    registry: &VoxelRegistry,
+    layer: MeshLayer,
+    voxel: Voxel,
+    slice_direction: SliceDirection,
+) -> Option<u32> {
+    match voxel.is_air() || registry.mesh_layer(voxel) != layer {
+        true => None,
+        false => Some(registry.atlas_index(voxel, slice_direction.normal())),
+    }
+}
+
+/// What the face of the voxel at this slice position looks like, if it has
+/// one in this layer.
+fn voxel_face(
+    neighborhood: &SolidNeighborhood,
+    layer: MeshLayer,
+    slice_direction: SliceDirection,
+    slice_depth: u32,
+    slice_pos: UVec2,
+) -> Option<QuadFace> {
+    let pos = InChunkPos::new(slice_direction.transform(slice_depth, slice_pos)?)?;
+    let atlas_index = face_atlas_index(
+        neighborhood.registry,
+        layer,
+        neighborhood.chunk.at(pos),
+        slice_direction,
+    )?;
+    let front = slice_direction.signed_transform(slice_depth as i32 + 1, slice_pos.as_ivec2());
+    Some(QuadFace {
+        atlas_index,
+        ao: face_ao(neighborhood, slice_direction, slice_depth, slice_pos),
+        light: neighborhood.light(front),
+    })
+}
+
+/// The voxels in a chunk, plus the edges of the chunks next to it.
+struct SolidNeighborhood<'a> {
+    chunk: &'a Chunk,
+    neighbors: &'a MeshNeighbors,
+    registry: &'a VoxelRegistry,
+}
+
+/// Where a position up to one voxel outside the chunk ends up.
+enum NeighborhoodPos {
+    Inside(InChunkPos),
+    /// In the edge slice of the neighbor in this direction, at this index.
+    Edge(VoxelAxis, usize),
+    /// In one of the chunks diagonal to this one, which aren't known.
+    Diagonal,
+}
+
+impl SolidNeighborhood<'_> {
+    fn locate(pos: IVec3) -> NeighborhoodPos {
+        let inside = pos.clamp(IVec3::ZERO, IVec3::splat(CHUNK_WIDTH as i32 - 1));
+        let outside = pos - inside;
+        if outside == IVec3::ZERO {
+            return NeighborhoodPos::Inside(InChunkPos::new(inside.as_uvec3()).unwrap());
+        }
+
+        let Some(direction) = VoxelAxis::from_ivec3(outside) else {
+            return NeighborhoodPos::Diagonal;
+        };
+        let slice_direction = SLICE_DIRECTIONS
+            .into_iter()
+            .find(|slice_direction| slice_direction.normal() == direction)
+            .unwrap();
+        // The neighbor's edge is stored as a slice in the same direction
+        let (slice_pos, _) = slice_direction.inverse_transform(pos);
+        NeighborhoodPos::Edge(
+            direction,
+            (slice_pos.y * CHUNK_WIDTH as i32 + slice_pos.x) as usize,
+        )
+    }
+
+    /// Whether the voxel at this position hides the faces next to it. The
+    /// position can be up to one voxel outside the chunk. Only the chunks
+    /// sharing a face with this one are known, so anything in the chunks
+    /// diagonal to it counts as empty.
+    fn is_solid(&self, pos: IVec3) -> bool {
+        match Self::locate(pos) {
+            NeighborhoodPos::Inside(pos) => self.registry.does_cull_as_solid(self.chunk.at(pos)),
+            NeighborhoodPos::Edge(direction, index) => {
+                self.neighbors.solid.get_in_direction(direction)[index]
+            }
+            NeighborhoodPos::Diagonal => false,
+        }
+    }
+
+    /// The light at this position, which can be outside the chunk the same
+    /// way as for [`Self::is_solid`]. Anything unknown is dark.
+    fn light(&self, pos: IVec3) -> VoxelLight {
+        match Self::locate(pos) {
+            NeighborhoodPos::Inside(pos) => self.chunk.light_at(pos),
+            NeighborhoodPos::Edge(direction, index) => self
+                .neighbors
+                .light
+                .get_in_direction(direction)
+                .get(index)
+                .copied()
+                .unwrap_or_default(),
+            NeighborhoodPos::Diagonal => VoxelLight::DARK,
+        }
+    }
+}
+
+/// Light at a face corner, from 0 (tucked into a corner) to 3 (nothing
+/// around it), based on the two voxels next to the corner and the one
+/// diagonal from it. With both sides blocked the corner can't see past
+/// them, so the diagonal doesn't matter.
+pub fn vertex_ao(side_1: bool, side_2: bool, corner: bool) -> u8 {
+    match side_1 && side_2 {
+        true => 0,
+        false => 3 - (side_1 as u8 + side_2 as u8 + corner as u8),
+    }
+}
+
+/// The ambient occlusion at each corner of a voxel's face, found from the
+/// voxels in front of the face.
+fn face_ao(
+    neighborhood: &SolidNeighborhood,
+    slice_direction: SliceDirection,
+    slice_depth: u32,
+    slice_pos: UVec2,
+) -> [u8; 4] {
+    let slice_pos = slice_pos.as_ivec2();
+    let front = slice_depth as i32 + 1;
+    let solid = |offset: IVec2| {
+        neighborhood.is_solid(slice_direction.signed_transform(front, slice_pos + offset))
+    };
+
+    // Same order as the vertices: start, end, low right, high left
+    [
+        IVec2::new(-1, -1),
+        IVec2::new(1, 1),
+        IVec2::new(1, -1),
+        IVec2::new(-1, 1),
+    ]
+    .map(|corner| {
+        vertex_ao(
+            solid(IVec2::new(corner.x, 0)),
+            solid(IVec2::new(0, corner.y)),
+            solid(corner),
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::MAX_LIGHT;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    /// The game's voxels, plus some glass for the cutout layer.
+    fn registry() -> VoxelRegistry {
+        VoxelRegistry::builtin_with(
+            "(voxels: [(id: 100, name: \"glass\", transparent: true, textures: All(0))])",
+        )
+    }
+
+    fn chunk_with(registry: &VoxelRegistry, stone: &[UVec3]) -> Chunk {
+        let mut chunk = Chunk::default();
+        for pos in stone {
+            chunk.set(
+                InChunkPos::new(*pos).unwrap(),
+                registry.by_name("stone").unwrap(),
+            );
+        }
+        chunk.update_edge_slice_bits(registry);
+        chunk
+    }
+
+    fn slice_direction(normal: VoxelAxis) -> SliceDirection {
+        SLICE_DIRECTIONS
+            .into_iter()
+            .find(|slice_direction| slice_direction.normal() == normal)
+            .unwrap()
+    }
+
+    /// The face's corners, sorted so tests don't depend on vertex order.
+    fn sorted_ao(neighborhood: &SolidNeighborhood, normal: VoxelAxis, pos: UVec3) -> [u8; 4] {
+        let slice_direction = slice_direction(normal);
+        let (slice_pos, depth) = slice_direction.inverse_transform(pos.as_ivec3());
+        let mut ao = face_ao(
+            neighborhood,
+            slice_direction,
+            depth as u32,
+            slice_pos.as_uvec2(),
+        );
+        ao.sort();
+        ao
+    }
+
+    #[test]
+    fn vertex_ao_counts_neighbors() {
+        assert_eq!(vertex_ao(false, false, false), 3);
+        assert_eq!(vertex_ao(true, false, false), 2);
+        assert_eq!(vertex_ao(false, false, true), 2);
+        assert_eq!(vertex_ao(false, true, true), 1);
+        assert_eq!(vertex_ao(true, true, false), 0);
+        assert_eq!(vertex_ao(true, true, true), 0);
+    }
+
+    #[test]
+    fn open_face_is_unoccluded() {
+        let registry = registry();
+        let chunk = chunk_with(&registry, &[UVec3::new(5, 5, 5)]);
+        let neighbors = MeshNeighbors::default();
+        let neighborhood = SolidNeighborhood {
+            chunk: &chunk,
+            neighbors: &neighbors,
+            registry: &registry,
+        };
+
+        for normal in SLICE_DIRECTIONS.map(|dir| dir.normal()) {
+            assert_eq!(
+                sorted_ao(&neighborhood, normal, UVec3::new(5, 5, 5)),
+                [3; 4]
+            );
+        }
+    }
+
+    #[test]
+    fn wall_darkens_nearby_corners() {
+        let registry = registry();
+        let chunk = chunk_with(&registry, &[UVec3::new(5, 5, 5), UVec3::new(6, 6, 5)]);
+        let neighbors = MeshNeighbors::default();
+        let neighborhood = SolidNeighborhood {
+            chunk: &chunk,
+            neighbors: &neighbors,
+            registry: &registry,
+        };
+
+        assert_eq!(
+            sorted_ao(&neighborhood, VoxelAxis::PosY, UVec3::new(5, 5, 5)),
+            [2, 2, 3, 3]
+        );
+        // The bottom face has nothing in front of it
+        assert_eq!(
+            sorted_ao(&neighborhood, VoxelAxis::NegY, UVec3::new(5, 5, 5)),
+            [3; 4]
+        );
+    }
+
+    #[test]
+    fn inner_corner_is_fully_occluded() {
+        let registry = registry();
+        let chunk = chunk_with(
+            &registry,
+            &[
+                UVec3::new(5, 5, 5),
+                UVec3::new(6, 6, 5),
+                UVec3::new(5, 6, 6),
+            ],
+        );
+        let neighbors = MeshNeighbors::default();
+        let neighborhood = SolidNeighborhood {
+            chunk: &chunk,
+            neighbors: &neighbors,
+            registry: &registry,
+        };
+
+        assert_eq!(
+            sorted_ao(&neighborhood, VoxelAxis::PosY, UVec3::new(5, 5, 5)),
+            [0, 2, 2, 3]
+        );
+    }
+
+    #[test]
+    fn neighbor_chunk_occludes_edge_faces() {
+        let registry = registry();
+        let edge = CHUNK_WIDTH - 1;
+        let chunk = chunk_with(&registry, &[UVec3::new(edge, 5, 5)]);
+        // A voxel in the +X chunk, diagonally above the edge voxel
+        let neighbor = chunk_with(&registry, &[UVec3::new(0, 6, 5)]);
+        let mut neighbors = MeshNeighbors::default();
+        *neighbors.solid.get_in_direction_mut(VoxelAxis::PosX) = neighbor
+            .edge_slice_bits
+            .solid
+            .get_in_direction(VoxelAxis::NegX)
+            .clone();
+        let neighborhood = SolidNeighborhood {
+            chunk: &chunk,
+            neighbors: &neighbors,
+            registry: &registry,
+        };
+
+        assert_eq!(
+            sorted_ao(&neighborhood, VoxelAxis::PosY, UVec3::new(edge, 5, 5)),
+            [2, 2, 3, 3]
+        );
+    }
+
+    #[test]
+    fn faces_are_lit_by_the_voxel_in_front() {
+        let registry = registry();
+        let edge = CHUNK_WIDTH - 1;
+        let mut chunk = chunk_with(&registry, &[UVec3::new(5, 5, 5), UVec3::new(edge, 5, 5)]);
+        let lit = VoxelLight::new(12, 3);
+        chunk
+            .light
+            .set(InChunkPos::new(UVec3::new(5, 6, 5)).unwrap(), lit);
+        // The +X neighbor is lit by a torch or something
+        let neighbor_lit = VoxelLight::new(0, 7);
+        let mut neighbors = MeshNeighbors::default();
+        *neighbors.light.get_in_direction_mut(VoxelAxis::PosX) =
+            vec![neighbor_lit; CHUNK_SQUARE as usize];
+        let neighborhood = SolidNeighborhood {
+            chunk: &chunk,
+            neighbors: &neighbors,
+            registry: &registry,
+        };
+
+        let face_light = |normal: VoxelAxis, pos: UVec3| {
+            let slice_direction = slice_direction(normal);
+            let (slice_pos, depth) = slice_direction.inverse_transform(pos.as_ivec3());
+            voxel_face(
+                &neighborhood,
+                MeshLayer::Opaque,
+                slice_direction,
+                depth as u32,
+                slice_pos.as_uvec2(),
+            )
+            .unwrap()
+            .light
+        };
+        assert_eq!(face_light(VoxelAxis::PosY, UVec3::new(5, 5, 5)), lit);
+        assert_eq!(
+            face_light(VoxelAxis::NegY, UVec3::new(5, 5, 5)),
+            VoxelLight::DARK
+        );
+        assert_eq!(
+            face_light(VoxelAxis::PosX, UVec3::new(edge, 5, 5)),
+            neighbor_lit
+        );
+
+        // The light is packed in between the atlas index and the occlusion
+        let mut mesh = TmpChunkMesh::default();
+        let face = QuadFace {
+            atlas_index: 5,
+            ao: [1; 4],
+            light: lit,
+        };
+        mesh.add_quad(
+            slice_direction(VoxelAxis::PosY),
+            0,
+            Quad::new(UVec2::ZERO, face),
+        );
+        for hack in &mesh.hacks {
+            assert_eq!(hack.y & 0x3fffff, 5);
+            assert_eq!((hack.y >> 22) & 0xff, lit.bits() as u32);
+            assert_eq!(hack.y >> 30, 1);
+        }
+    }
+
+    #[test]
+    fn quads_with_different_ao_are_not_merged() {
+        let registry = registry();
+        let floor = iproduct!(0..CHUNK_WIDTH, 0..CHUNK_WIDTH)
+            .map(|(x, z)| UVec3::new(x, 0, z))
+            .collect::<Vec<_>>();
+        let top = slice_direction(VoxelAxis::PosY);
+        let neighbors = MeshNeighbors::default();
+
+        let mesh_top = |chunk: &Chunk| {
+            let mut mesh = TmpChunkMesh::default();
+            mesh_slice(
+                &SolidNeighborhood {
+                    chunk,
+                    neighbors: &neighbors,
+                    registry: &registry,
+                },
+                MeshLayer::Opaque,
+                top,
+                0,
+                &mut mesh,
+                chunk.get_solid_bits_slice(&registry, top, 1),
+            );
+            mesh
+        };
+
+        // A bare floor is a single quad
+        let mesh = mesh_top(&chunk_with(&registry, &floor));
+        assert_eq!(mesh.hacks.len(), 4);
+        assert!(mesh.hacks.iter().all(|hack| hack.y >> 30 == 3));
+
+        // Putting something on it splits the floor up around it
+        let mut stone = floor.clone();
+        stone.push(UVec3::new(10, 1, 10));
+        let chunk = chunk_with(&registry, &stone);
+        let mesh = mesh_top(&chunk);
+        assert!(mesh.hacks.len() > 4);
+        let neighborhood = SolidNeighborhood {
+            chunk: &chunk,
+            neighbors: &neighbors,
+            registry: &registry,
+        };
+
+        let mut area = 0;
+        for (verts, hacks) in mesh.verts.chunks(4).zip(mesh.hacks.chunks(4)) {
+            let min = verts.iter().copied().reduce(Vec3::min).unwrap().as_uvec3();
+            let max = verts.iter().copied().reduce(Vec3::max).unwrap().as_uvec3();
+            let quad_ao = hacks
+                .iter()
+                .map(|hack| (hack.y >> 30) as u8)
+                .collect::<Vec<_>>();
+
+            // Every face merged into the quad has to be shaded the same
+            for (x, z) in iproduct!(min.x..max.x, min.z..max.z) {
+                let (slice_pos, depth) = top.inverse_transform(IVec3::new(x as i32, 0, z as i32));
+                let face = face_ao(&neighborhood, top, depth as u32, slice_pos.as_uvec2());
+                assert_eq!(face.to_vec(), quad_ao, "face at {x}, {z}");
+                area += 1;
+            }
+        }
+        // Everything but the voxel under the stone is still covered
+        assert_eq!(area, CHUNK_SQUARE - 1);
+    }
+
+    #[test]
+    fn checkerboard_needs_u32_indices() {
+        let registry = registry();
+        // Every stone voxel is surrounded by air, so nothing gets merged
+        let stone = iproduct!(0..CHUNK_WIDTH, 0..CHUNK_WIDTH, 0..CHUNK_WIDTH)
+            .filter(|(x, y, z)| (x + y + z) % 2 == 0)
+            .map(|(x, y, z)| UVec3::new(x, y, z))
+            .collect::<Vec<_>>();
+        let chunk = chunk_with(&registry, &stone);
+
+        let mesh = generate_mesh(&chunk, MeshNeighbors::default(), &registry)
+            .opaque
+            .unwrap();
+        let vertex_count = mesh.count_vertices();
+        assert_eq!(vertex_count, stone.len() * 6 * 4);
+        assert!(vertex_count > u16::MAX as usize + 1);
+
+        let indices = mesh.indices().unwrap();
+        assert!(matches!(indices, Indices::U32(_)));
+        assert_eq!(indices.len(), stone.len() * 6 * 6);
+        assert!(indices.iter().all(|i| i < vertex_count));
+    }
+
+    #[test]
+    fn small_meshes_use_u16_indices() {
+        let registry = registry();
+        let chunk = chunk_with(&registry, &[UVec3::new(5, 5, 5)]);
+
+        let mesh = generate_mesh(&chunk, MeshNeighbors::default(), &registry)
+            .opaque
+            .unwrap();
+        assert_eq!(mesh.count_vertices(), 6 * 4);
+        assert!(matches!(mesh.indices(), Some(Indices::U16(_))));
+    }
+
+    #[test]
+    fn voxels_are_split_into_layers() {
+        let registry = registry();
+        let glass = registry.by_name("glass").unwrap();
+        let water = registry.by_name("water").unwrap();
+        assert_eq!(registry.mesh_layer(glass), MeshLayer::Cutout);
+        assert_eq!(registry.mesh_layer(water), MeshLayer::Translucent);
+
+        // Stone and glass side by side, with water on top of the stone
+        let mut chunk = chunk_with(&registry, &[UVec3::new(5, 5, 5)]);
+        chunk.set(InChunkPos::new(UVec3::new(6, 5, 5)).unwrap(), glass);
+        chunk.set(InChunkPos::new(UVec3::new(5, 6, 5)).unwrap(), water);
+        chunk.update_edge_slice_bits(&registry);
+
+        let meshes = generate_mesh(&chunk, MeshNeighbors::default(), &registry);
+        // Neither glass nor water hide the stone
+        assert_eq!(meshes.opaque.unwrap().count_vertices(), 6 * 4);
+        // Stone does hide the glass face touching it
+        assert_eq!(meshes.cutout.unwrap().count_vertices(), 5 * 4);
+        // The water's bottom face sits on the stone
+        assert_eq!(meshes.translucent.unwrap().count_vertices(), 5 * 4);
+    }
+
+    /// A chunk of every kind of voxel, randomly lit, next to random edges.
+    fn random_chunk(registry: &VoxelRegistry, seed: u64) -> (Chunk, MeshNeighbors) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let voxels = ["air", "stone", "dirt", "grass", "water", "glass"]
+            .map(|name| registry.by_name(name).unwrap());
+        let random_light = |rng: &mut StdRng| {
+            VoxelLight::new(rng.gen_range(0..=MAX_LIGHT), rng.gen_range(0..=MAX_LIGHT))
+        };
+
+        let mut chunk = Chunk::default();
+        for (x, y, z) in iproduct!(0..CHUNK_WIDTH, 0..CHUNK_WIDTH, 0..CHUNK_WIDTH) {
+            let pos = InChunkPos::new(UVec3::new(x, y, z)).unwrap();
+            // Mostly air and stone, so there are big faces to merge
+            let voxel = match rng.gen_range(0..4) {
+                0 => voxels[rng.gen_range(0..voxels.len())],
+                1 => voxels[1],
+                _ => voxels[0],
+            };
+            chunk.set(pos, voxel);
+            // Mostly dark, otherwise hardly anything would merge
+            if rng.gen_ratio(1, 8) {
+                chunk.light.set(pos, random_light(&mut rng));
+            }
+        }
+        chunk.update_edge_slice_bits(registry);
+
+        let mut neighbors = MeshNeighbors::default();
+        for dir in SLICE_DIRECTIONS.map(|dir| dir.normal()) {
+            *neighbors.solid.get_in_direction_mut(dir) =
+                (0..CHUNK_SQUARE).map(|_| rng.gen_bool(0.5)).collect();
+            *neighbors.translucent.get_in_direction_mut(dir) =
+                (0..CHUNK_SQUARE).map(|_| rng.gen_bool(0.2)).collect();
+            *neighbors.light.get_in_direction_mut(dir) =
+                (0..CHUNK_SQUARE).map(|_| random_light(&mut rng)).collect();
+        }
+        (chunk, neighbors)
+    }
+
+    #[test]
+    fn bitmask_mesher_matches_slice_mesher() {
+        let registry = registry();
+        let checkerboard = iproduct!(0..CHUNK_WIDTH, 0..CHUNK_WIDTH, 0..CHUNK_WIDTH)
+            .filter(|(x, y, z)| (x + y + z) % 2 == 0)
+            .map(|(x, y, z)| UVec3::new(x, y, z))
+            .collect::<Vec<_>>();
+        let ground = iproduct!(0..CHUNK_WIDTH, 0..CHUNK_WIDTH / 2, 0..CHUNK_WIDTH)
+            .map(|(x, y, z)| UVec3::new(x, y, z))
+            .collect::<Vec<_>>();
+
+        let mut cases = vec![
+            (Chunk::default(), MeshNeighbors::default()),
+            (
+                chunk_with(&registry, &checkerboard),
+                MeshNeighbors::default(),
+            ),
+            (chunk_with(&registry, &ground), MeshNeighbors::default()),
+        ];
+        cases.extend((0..4).map(|seed| random_chunk(&registry, seed)));
+
+        for (i, (chunk, neighbors)) in cases.iter().enumerate() {
+            let by_slices = mesh_layers_by_slices(chunk, neighbors, &registry);
+            let by_bitmasks = mesh_layers(chunk, neighbors, &registry);
+            assert!(by_slices == by_bitmasks, "meshes differ for case {i}");
+        }
+    }
+}
@@ -3,7 +3,10 @@ use bevy::{
     math::UVec3,
     prelude::{Component, IVec3, Transform},
 };
-use std::ops::Deref;
+use std::{
+    fmt::{Display, Formatter},
+    ops::Deref,
+};
 
 pub struct VoxelPos(pub IVec3);
 
@@ -32,6 +35,12 @@ impl ChunkPos {
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub struct RegionPos(pub IVec3);
 
+impl Display for RegionPos {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
 impl From<RegionPos> for ChunkPos {
     fn from(value: RegionPos) -> Self {
         Self(value.0 * REGION_WIDTH as i32)
@@ -84,6 +93,16 @@ impl InRegionChunkPos {
         }
     }
 
+    /// The inverse of [`Self::index`].
+    pub fn from_index(index: usize) -> Self {
+        let index = index as u32;
+        Self(UVec3::new(
+            index % REGION_WIDTH,
+            index / REGION_WIDTH % REGION_WIDTH,
+            index / REGION_SQUARE,
+        ))
+    }
+
     pub fn from_world(world_chunk_pos: ChunkPos) -> Self {
         Self(
             world_chunk_pos
@@ -0,0 +1,252 @@
+use crate::voxel::{
+    quad_corner, slice_rows, Chunk, ChunkBitmask, MeshNeighbors, SliceRows, VoxelRegistry,
+    CHUNK_WIDTH, FULL_ROW, SLICE_DIRECTIONS,
+};
+use bevy::math::UVec2;
+use bevy_rapier3d::prelude::Collider;
+
+/// Build the chunk's collider. Unlike the render meshes, faces are merged
+/// by whether they're solid and nothing else, so a hillside of grass, dirt
+/// and stone is a handful of big quads instead of one for every texture
+/// and shade of light.
+pub fn generate_collider(
+    chunk: &Chunk,
+    neighbors: &MeshNeighbors,
+    registry: &VoxelRegistry,
+) -> Option<Collider> {
+    if chunk.definitely_empty {
+        return None;
+    }
+
+    let solid = ChunkBitmask::new(chunk, |voxel| registry.is_solid(voxel));
+    let mut verts = vec![];
+    let mut inds = vec![];
+    for dir in SLICE_DIRECTIONS {
+        // Only the voxels that hide faces are known past the edge, so faces
+        // against something like glass in the next chunk are kept. They're
+        // never touched, it just costs a couple of triangles.
+        let neighbor_solid = slice_rows(neighbors.solid.get_in_direction(dir.normal()));
+
+        for z in 0..CHUNK_WIDTH {
+            let front = match z < CHUNK_WIDTH - 1 {
+                true => solid.rows(dir, z + 1),
+                false => neighbor_solid,
+            };
+            let mut faces = solid.rows(dir, z);
+            for (faces, front) in faces.iter_mut().zip(front) {
+                *faces &= !front;
+            }
+
+            for (start, end) in merge_faces(faces) {
+                let start_ind = verts.len() as u32;
+                // Same corner order and winding as the render meshes
+                verts.extend(
+                    [
+                        start,
+                        end,
+                        UVec2::new(end.x, start.y),
+                        UVec2::new(start.x, end.y),
+                    ]
+                    .map(|corner| quad_corner(dir, z, corner).as_vec3()),
+                );
+                inds.extend([[0, 1, 3], [0, 2, 1]].map(|tri| tri.map(|i| start_ind + i)));
+            }
+        }
+    }
+
+    match inds.is_empty() {
+        true => None,
+        false => Some(Collider::trimesh(verts, inds)),
+    }
+}
+
+/// Merge the faces in a slice into rectangles, given as their start and
+/// exclusive end. Each one runs along its row as far as it can, then grows
+/// upwards for as long as the rows above have the whole run.
+fn merge_faces(mut faces: SliceRows) -> Vec<(UVec2, UVec2)> {
+    let mut rects = vec![];
+    for y in 0..CHUNK_WIDTH {
+        while faces[y as usize] != 0 {
+            let row = faces[y as usize];
+            let start_x = row.trailing_zeros();
+            let width = (row >> start_x).trailing_ones();
+            let run = (FULL_ROW >> (CHUNK_WIDTH - width)) << start_x;
+            faces[y as usize] &= !run;
+
+            let mut end_y = y + 1;
+            while end_y < CHUNK_WIDTH && faces[end_y as usize] & run == run {
+                faces[end_y as usize] &= !run;
+                end_y += 1;
+            }
+            rects.push((UVec2::new(start_x, y), UVec2::new(start_x + width, end_y)));
+        }
+    }
+    rects
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::{generate_mesh, InChunkPos};
+    use bevy::{math::UVec3, prelude::*};
+    use itertools::iproduct;
+
+    fn triangle_count(collider: &Collider) -> usize {
+        collider.as_trimesh().unwrap().raw.indices().len()
+    }
+
+    fn collider_triangles(collider: &Collider) -> Vec<[Vec3; 3]> {
+        let trimesh = collider.as_trimesh().unwrap();
+        let verts = trimesh.raw.vertices();
+        trimesh
+            .raw
+            .indices()
+            .iter()
+            .map(|tri| {
+                tri.map(|i| {
+                    Vec3::new(
+                        verts[i as usize].x,
+                        verts[i as usize].y,
+                        verts[i as usize].z,
+                    )
+                })
+            })
+            .collect()
+    }
+
+    fn mesh_triangles(mesh: &Mesh) -> Vec<[Vec3; 3]> {
+        let verts = mesh
+            .attribute(Mesh::ATTRIBUTE_POSITION)
+            .unwrap()
+            .as_float3()
+            .unwrap();
+        let inds = mesh.indices().unwrap().iter().collect::<Vec<_>>();
+        inds.chunks(3)
+            .map(|tri| [0, 1, 2].map(|i| Vec3::from(verts[tri[i]])))
+            .collect()
+    }
+
+    fn normal(tri: &[Vec3; 3]) -> Vec3 {
+        (tri[1] - tri[0]).cross(tri[2] - tri[0])
+    }
+
+    /// Whether the point is on the triangle, and the triangle faces the
+    /// same way as `facing`.
+    fn covers(tri: &[Vec3; 3], point: Vec3, facing: Vec3) -> bool {
+        let normal = normal(tri);
+        if normal.normalize().dot(facing.normalize()) < 0.999
+            || normal.dot(point - tri[0]).abs() > 1e-4
+        {
+            return false;
+        }
+        // Same side of every edge as the inside of the triangle
+        (0..3).all(|i| {
+            let (a, b) = (tri[i], tri[(i + 1) % 3]);
+            (b - a).cross(point - a).dot(normal) >= -1e-4
+        })
+    }
+
+    fn area(tris: &[[Vec3; 3]]) -> f32 {
+        tris.iter().map(|tri| normal(tri).length() / 2.0).sum()
+    }
+
+    #[test]
+    fn merges_faces_into_rectangles() {
+        let mut faces = [0; CHUNK_WIDTH as usize];
+        faces[0] = 0b0110;
+        faces[1] = 0b1111;
+        faces[2] = 0b0001;
+        assert_eq!(
+            merge_faces(faces),
+            vec![
+                (UVec2::new(1, 0), UVec2::new(3, 2)),
+                (UVec2::new(0, 1), UVec2::new(1, 3)),
+                (UVec2::new(3, 1), UVec2::new(4, 2)),
+            ]
+        );
+
+        let full = merge_faces([FULL_ROW; CHUNK_WIDTH as usize]);
+        assert_eq!(full, vec![(UVec2::ZERO, UVec2::splat(CHUNK_WIDTH))]);
+    }
+
+    #[test]
+    fn ignores_voxel_type() {
+        let registry = VoxelRegistry::builtin();
+        // A floor of alternating stone and dirt, with water on top
+        let [stone, dirt, water] =
+            ["stone", "dirt", "water"].map(|name| registry.by_name(name).unwrap());
+        let mut chunk = Chunk::default();
+        for (x, z) in iproduct!(0..CHUNK_WIDTH, 0..CHUNK_WIDTH) {
+            let floor = match (x + z) % 2 == 0 {
+                true => stone,
+                false => dirt,
+            };
+            chunk.set(InChunkPos::new(UVec3::new(x, 0, z)).unwrap(), floor);
+            chunk.set(InChunkPos::new(UVec3::new(x, 1, z)).unwrap(), water);
+        }
+        chunk.update_edge_slice_bits(&registry);
+        let neighbors = MeshNeighbors::default();
+
+        // Every side of the floor is one quad, and the water isn't there
+        let collider = generate_collider(&chunk, &neighbors, &registry).unwrap();
+        assert_eq!(triangle_count(&collider), 6 * 2);
+
+        let render_quads = generate_mesh(&chunk, neighbors, &registry)
+            .opaque
+            .unwrap()
+            .count_vertices()
+            / 4;
+        assert!(render_quads > 6);
+    }
+
+    #[test]
+    fn matches_render_mesh_surface() {
+        let registry = VoxelRegistry::builtin();
+        let stone = registry.by_name("stone").unwrap();
+        // A lopsided staircase with a gap and a floating block, so swapped
+        // axes or faces on the wrong side of a voxel can't line up by luck
+        let mut chunk = Chunk::default();
+        for (x, y, z) in [
+            (2, 0, 1),
+            (3, 0, 1),
+            (4, 0, 1),
+            (6, 0, 1),
+            (2, 1, 1),
+            (3, 1, 1),
+            (2, 2, 1),
+            (2, 0, 2),
+            (2, 0, 3),
+            (5, 3, 7),
+        ] {
+            chunk.set(InChunkPos::new(UVec3::new(x, y, z)).unwrap(), stone);
+        }
+        chunk.update_edge_slice_bits(&registry);
+        let neighbors = MeshNeighbors::default();
+
+        let collider =
+            collider_triangles(&generate_collider(&chunk, &neighbors, &registry).unwrap());
+        let render = mesh_triangles(&generate_mesh(&chunk, neighbors, &registry).opaque.unwrap());
+
+        assert!((area(&collider) - area(&render)).abs() < 1e-3);
+        for (from, to) in [(&collider, &render), (&render, &collider)] {
+            for tri in from {
+                let center = (tri[0] + tri[1] + tri[2]) / 3.0;
+                assert!(
+                    to.iter().any(|other| covers(other, center, normal(tri))),
+                    "nothing matches the triangle {tri:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn empty_chunks_have_no_collider() {
+        let registry = VoxelRegistry::builtin();
+        let mut chunk = Chunk::default();
+        chunk.set(
+            InChunkPos::new(UVec3::splat(3)).unwrap(),
+            registry.by_name("water").unwrap(),
+        );
+        assert!(generate_collider(&chunk, &MeshNeighbors::default(), &registry).is_none());
+    }
+}
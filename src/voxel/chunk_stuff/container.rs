@@ -1,76 +1,329 @@
 use crate::voxel::{InChunkPos, Voxel, CHUNK_CUBE};
 use serde::{Deserialize, Serialize};
-use serde_with::serde_as;
-use std::ops::{Deref, DerefMut, Index, IndexMut};
+use std::mem::size_of;
 
-#[serde_as]
-#[derive(Debug, Deserialize, Serialize)]
-pub struct VoxelContainerInner(
-    #[serde_as(as = "Box<[_; CHUNK_CUBE as usize]>")] pub Box<[Voxel; CHUNK_CUBE as usize]>,
-);
+/// Palette indices packed into `u64` words. Indices never straddle two
+/// words, so the bit width is always a power of two.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+struct PackedIndices {
+    bits: u32,
+    words: Vec<u64>,
+}
 
-impl Default for VoxelContainerInner {
-    fn default() -> Self {
-        Self(Box::new([Voxel::default(); CHUNK_CUBE as usize]))
+impl PackedIndices {
+    fn new(bits: u32) -> Self {
+        Self {
+            bits,
+            words: vec![0; Self::word_count(bits)],
+        }
     }
-}
 
-impl Clone for VoxelContainerInner {
-    fn clone(&self) -> Self {
-        Self(Box::new(*self.0.as_ref()))
+    fn word_count(bits: u32) -> usize {
+        let per_word = (u64::BITS / bits) as usize;
+        (CHUNK_CUBE as usize).div_ceil(per_word)
     }
-}
 
-impl Index<usize> for VoxelContainerInner {
-    type Output = Voxel;
+    fn per_word(&self) -> usize {
+        (u64::BITS / self.bits) as usize
+    }
 
-    fn index(&self, index: usize) -> &Self::Output {
-        &self.0[index]
+    fn mask(&self) -> u64 {
+        (1 << self.bits) - 1
     }
-}
 
-impl IndexMut<usize> for VoxelContainerInner {
-    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        &mut self.0[index]
+    fn get(&self, index: usize) -> usize {
+        let per_word = self.per_word();
+        let shift = (index % per_word) as u32 * self.bits;
+        ((self.words[index / per_word] >> shift) & self.mask()) as usize
     }
-}
 
-impl Deref for VoxelContainerInner {
-    type Target = [Voxel];
+    fn set(&mut self, index: usize, value: usize) {
+        let per_word = self.per_word();
+        let shift = (index % per_word) as u32 * self.bits;
+        let mask = self.mask();
+        let word = &mut self.words[index / per_word];
+        *word = (*word & !(mask << shift)) | ((value as u64 & mask) << shift);
+    }
 
-    fn deref(&self) -> &Self::Target {
-        self.0.as_slice()
+    /// Copy these indices into a new set of indices with the provided bit
+    /// width.
+    fn resized(&self, bits: u32) -> Self {
+        let mut new_indices = Self::new(bits);
+        for index in 0..CHUNK_CUBE as usize {
+            new_indices.set(index, self.get(index));
+        }
+        new_indices
     }
 }
 
-impl DerefMut for VoxelContainerInner {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        self.0.as_mut_slice()
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "SavedVoxelStorage")]
+enum VoxelStorage {
+    /// Every voxel in the chunk is the same, so we don't store any indices.
+    Uniform(Voxel),
+    /// Each voxel is an index into the palette.
+    Palette {
+        palette: Vec<Voxel>,
+        indices: PackedIndices,
+    },
+}
+
+/// [`VoxelStorage`] straight out of a save, before checking that it makes
+/// sense. A corrupt palette would otherwise divide by zero or index out of
+/// bounds the first time the chunk is read.
+#[derive(Deserialize)]
+enum SavedVoxelStorage {
+    Uniform(Voxel),
+    Palette {
+        palette: Vec<Voxel>,
+        indices: SavedPackedIndices,
+    },
+}
+
+#[derive(Deserialize)]
+struct SavedPackedIndices {
+    bits: u32,
+    words: Vec<u64>,
+}
+
+impl TryFrom<SavedVoxelStorage> for VoxelStorage {
+    type Error = String;
+
+    fn try_from(saved: SavedVoxelStorage) -> Result<Self, Self::Error> {
+        let (palette, SavedPackedIndices { bits, words }) = match saved {
+            SavedVoxelStorage::Uniform(voxel) => return Ok(Self::Uniform(voxel)),
+            SavedVoxelStorage::Palette { palette, indices } => (palette, indices),
+        };
+        if palette.is_empty() {
+            return Err("empty voxel palette".to_string());
+        }
+        if !(1..=32).contains(&bits) || !bits.is_power_of_two() {
+            return Err(format!("invalid palette index width {bits}"));
+        }
+        let word_count = PackedIndices::word_count(bits);
+        if words.len() != word_count {
+            return Err(format!(
+                "expected {word_count} words of palette indices, found {}",
+                words.len()
+            ));
+        }
+
+        let indices = PackedIndices { bits, words };
+        if let Some(index) = (0..CHUNK_CUBE as usize)
+            .map(|index| indices.get(index))
+            .find(|index| *index >= palette.len())
+        {
+            return Err(format!(
+                "palette index {index} is past the end of a palette of {} voxels",
+                palette.len()
+            ));
+        }
+        Ok(Self::Palette { palette, indices })
     }
 }
 
-#[derive(Default, Debug, Clone, Deserialize, Serialize)]
-pub struct VoxelContainer(pub VoxelContainerInner);
+/// The voxels within one chunk. Chunks made of a single voxel type are
+/// stored as just that voxel, otherwise voxels are stored as bit-packed
+/// indices into a palette that grows as new voxel types are added.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct VoxelContainer(VoxelStorage);
+
+impl Default for VoxelContainer {
+    fn default() -> Self {
+        Self::from_voxel(Voxel::AIR)
+    }
+}
 
 impl VoxelContainer {
-    #[allow(unused)]
     pub fn from_voxel(voxel: Voxel) -> Self {
-        Self(VoxelContainerInner(Box::new([voxel; CHUNK_CUBE as usize])))
+        Self(VoxelStorage::Uniform(voxel))
     }
 
     #[allow(unused)]
     pub fn from_voxels(voxels: Vec<Voxel>) -> Option<Self> {
         match voxels.len() as u32 {
-            CHUNK_CUBE => Some(Self(VoxelContainerInner(voxels.try_into().ok()?))),
+            CHUNK_CUBE => {
+                let mut container = Self::from_voxel(voxels[0]);
+                for (index, voxel) in voxels.into_iter().enumerate() {
+                    container.set_index(index, voxel);
+                }
+                Some(container)
+            }
             _ => None,
         }
     }
 
     pub fn at(&self, pos: InChunkPos) -> Voxel {
-        self.0[pos.index()]
+        match &self.0 {
+            VoxelStorage::Uniform(voxel) => *voxel,
+            VoxelStorage::Palette { palette, indices } => palette[indices.get(pos.index())],
+        }
     }
 
     pub fn set(&mut self, pos: InChunkPos, voxel: Voxel) {
-        self.0[pos.index()] = voxel;
+        self.set_index(pos.index(), voxel);
+    }
+
+    fn set_index(&mut self, index: usize, voxel: Voxel) {
+        match &mut self.0 {
+            VoxelStorage::Uniform(existing) => {
+                let existing = *existing;
+                if existing != voxel {
+                    // Every voxel is already index 0, so one bit is enough
+                    // for the new voxel type.
+                    let mut indices = PackedIndices::new(1);
+                    indices.set(index, 1);
+                    self.0 = VoxelStorage::Palette {
+                        palette: vec![existing, voxel],
+                        indices,
+                    };
+                }
+            }
+            VoxelStorage::Palette { palette, indices } => {
+                let palette_index = match palette.iter().position(|v| *v == voxel) {
+                    Some(palette_index) => palette_index,
+                    None => {
+                        palette.push(voxel);
+                        if palette.len() > 1 << indices.bits {
+                            *indices = indices.resized(indices.bits * 2);
+                        }
+                        palette.len() - 1
+                    }
+                };
+                indices.set(index, palette_index);
+            }
+        }
+    }
+
+    /// Every voxel type that may be in the chunk. Voxel types stay in the
+    /// palette after they've been replaced, so this can include extras.
+    pub fn palette(&self) -> &[Voxel] {
+        match &self.0 {
+            VoxelStorage::Uniform(voxel) => std::slice::from_ref(voxel),
+            VoxelStorage::Palette { palette, .. } => palette,
+        }
+    }
+
+    /// The index into [`Self::palette`] of every voxel, in the same order as
+    /// [`InChunkPos::index`].
+    pub fn palette_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..CHUNK_CUBE as usize).map(|index| match &self.0 {
+            VoxelStorage::Uniform(_) => 0,
+            VoxelStorage::Palette { indices, .. } => indices.get(index),
+        })
+    }
+
+    /// Approximate number of bytes used to store these voxels, including
+    /// heap allocations.
+    pub fn memory_usage(&self) -> usize {
+        size_of::<Self>()
+            + match &self.0 {
+                VoxelStorage::Uniform(_) => 0,
+                VoxelStorage::Palette { palette, indices } => {
+                    palette.capacity() * size_of::<Voxel>()
+                        + indices.words.capacity() * size_of::<u64>()
+                }
+            }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERIAL_CONFIG: bincode::config::Configuration = bincode::config::standard();
+
+    #[test]
+    fn packed_indices_round_trip() {
+        for bits in [1, 2, 4, 8, 16, 32] {
+            let mut indices = PackedIndices::new(bits);
+            let max = (1u64 << bits) - 1;
+            let value = |index: usize| ((index as u64 * 2_654_435_761) & max) as usize;
+            for index in 0..CHUNK_CUBE as usize {
+                indices.set(index, value(index));
+            }
+            for index in 0..CHUNK_CUBE as usize {
+                assert_eq!(
+                    indices.get(index),
+                    value(index),
+                    "{bits} bits, index {index}"
+                );
+            }
+
+            // Overwriting a value leaves its neighbors alone
+            indices.set(1, 0);
+            assert_eq!(indices.get(0), value(0));
+            assert_eq!(indices.get(1), 0);
+            assert_eq!(indices.get(2), value(2));
+        }
+    }
+
+    #[test]
+    fn palette_grows_across_bit_widths() {
+        let mut container = VoxelContainer::default();
+        let voxel = |index: usize| Voxel((index % 300) as u16 + 1);
+        // Enough voxel types besides air to go from 1 bit all the way up to 16
+        for index in 0..CHUNK_CUBE as usize {
+            container.set_index(index, voxel(index));
+
+            let VoxelStorage::Palette { palette, indices } = &container.0 else {
+                panic!("expected a palette after setting index {index}");
+            };
+            assert!(palette.len() <= 1 << indices.bits);
+        }
+
+        let VoxelStorage::Palette { indices, .. } = &container.0 else {
+            unreachable!();
+        };
+        assert_eq!(indices.bits, 16);
+        for (index, palette_index) in container.palette_indices().enumerate() {
+            assert_eq!(container.palette()[palette_index], voxel(index));
+        }
+    }
+
+    #[test]
+    fn containers_survive_saving() {
+        let mut container = VoxelContainer::default();
+        for index in (0..CHUNK_CUBE as usize).step_by(7) {
+            container.set_index(index, Voxel((index % 5) as u16));
+        }
+        let bytes = bincode::serde::encode_to_vec(&container, SERIAL_CONFIG).unwrap();
+        let (loaded, _): (VoxelContainer, _) =
+            bincode::serde::decode_from_slice(&bytes, SERIAL_CONFIG).unwrap();
+        assert_eq!(loaded, container);
+    }
+
+    #[test]
+    fn corrupt_palettes_are_rejected() {
+        let load = |palette: Vec<Voxel>, indices: PackedIndices| {
+            let saved = VoxelContainer(VoxelStorage::Palette { palette, indices });
+            let bytes = bincode::serde::encode_to_vec(&saved, SERIAL_CONFIG).unwrap();
+            bincode::serde::decode_from_slice::<VoxelContainer, _>(&bytes, SERIAL_CONFIG)
+        };
+        let two_voxels = || vec![Voxel::AIR, Voxel(1)];
+
+        // Index widths that can't be used, with otherwise valid words
+        for bits in [0, 3, 64] {
+            let words = vec![0; PackedIndices::word_count(bits.clamp(1, 32))];
+            let loaded = load(two_voxels(), PackedIndices { bits, words });
+            assert!(loaded.is_err(), "{bits} bits");
+        }
+
+        // Too few words for the width
+        let indices = PackedIndices {
+            bits: 2,
+            words: vec![0; 10],
+        };
+        assert!(load(two_voxels(), indices).is_err());
+
+        // Nothing for the indices to point at
+        assert!(load(vec![], PackedIndices::new(1)).is_err());
+
+        // One index past the end of the palette
+        let mut indices = PackedIndices::new(2);
+        indices.set(1234, 2);
+        assert!(load(two_voxels(), indices.clone()).is_err());
+        indices.set(1234, 1);
+        assert!(load(two_voxels(), indices).is_ok());
     }
 }
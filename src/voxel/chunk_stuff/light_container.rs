@@ -0,0 +1,114 @@
+use crate::voxel::{InChunkPos, CHUNK_CUBE};
+use std::mem::size_of;
+
+/// The brightest any light can be.
+pub const MAX_LIGHT: u8 = 15;
+
+/// Where light comes from. Each kind spreads on its own and is stored
+/// separately, so a torch in a cave doesn't care what the sky is doing.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LightKind {
+    /// Light from the sky. Full sky light shines straight down without
+    /// getting any dimmer.
+    Sky,
+    /// Light given off by voxels.
+    Block,
+}
+
+/// The sky and block light at a voxel, each from 0 to [`MAX_LIGHT`]. Sky
+/// light is kept in the high nibble.
+#[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct VoxelLight(u8);
+
+impl VoxelLight {
+    pub const DARK: Self = Self(0);
+    pub const FULL_SKY: Self = Self(MAX_LIGHT << 4);
+
+    pub fn new(sky: u8, block: u8) -> Self {
+        Self((sky.min(MAX_LIGHT) << 4) | block.min(MAX_LIGHT))
+    }
+
+    pub fn sky(&self) -> u8 {
+        self.0 >> 4
+    }
+
+    pub fn block(&self) -> u8 {
+        self.0 & MAX_LIGHT
+    }
+
+    pub fn get(&self, kind: LightKind) -> u8 {
+        match kind {
+            LightKind::Sky => self.sky(),
+            LightKind::Block => self.block(),
+        }
+    }
+
+    /// A copy with one kind of light replaced.
+    pub fn with(&self, kind: LightKind, level: u8) -> Self {
+        match kind {
+            LightKind::Sky => Self::new(level, self.block()),
+            LightKind::Block => Self::new(self.sky(), level),
+        }
+    }
+
+    /// Both levels packed into a byte, sky in the high nibble.
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum LightStorage {
+    /// Every voxel is lit the same, which covers chunks of open sky and
+    /// chunks buried in solid rock.
+    Uniform(VoxelLight),
+    Full(Box<[VoxelLight]>),
+}
+
+/// The light level of every voxel in a chunk, kept next to its
+/// [`VoxelContainer`](crate::voxel::VoxelContainer). Light is worked out
+/// again whenever a chunk is loaded, so it never gets saved.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LightContainer(LightStorage);
+
+impl Default for LightContainer {
+    fn default() -> Self {
+        Self::from_light(VoxelLight::DARK)
+    }
+}
+
+impl LightContainer {
+    pub fn from_light(light: VoxelLight) -> Self {
+        Self(LightStorage::Uniform(light))
+    }
+
+    pub fn at(&self, pos: InChunkPos) -> VoxelLight {
+        match &self.0 {
+            LightStorage::Uniform(light) => *light,
+            LightStorage::Full(lights) => lights[pos.index()],
+        }
+    }
+
+    pub fn set(&mut self, pos: InChunkPos, light: VoxelLight) {
+        match &mut self.0 {
+            LightStorage::Uniform(existing) => {
+                if *existing != light {
+                    let mut lights = vec![*existing; CHUNK_CUBE as usize].into_boxed_slice();
+                    lights[pos.index()] = light;
+                    self.0 = LightStorage::Full(lights);
+                }
+            }
+            LightStorage::Full(lights) => lights[pos.index()] = light,
+        }
+    }
+
+    /// Approximate number of bytes used to store this light, including heap
+    /// allocations.
+    pub fn memory_usage(&self) -> usize {
+        size_of::<Self>()
+            + match &self.0 {
+                LightStorage::Uniform(_) => 0,
+                LightStorage::Full(lights) => lights.len() * size_of::<VoxelLight>(),
+            }
+    }
+}
@@ -1,5 +1,8 @@
 pub mod chunk;
+pub mod chunk_bitmask;
 pub mod chunk_mesh;
 pub mod chunk_pos;
+pub mod collision_mesh;
 pub mod container;
+pub mod light_container;
 pub mod neighbor_slice;
@@ -1,32 +1,43 @@
-use crate::voxel::{VoxelAxis, CHUNK_SQUARE};
+use crate::voxel::{VoxelAxis, VoxelLight, CHUNK_SQUARE};
 use bitvec::prelude::BitVec;
 
 #[derive(Debug, Clone)]
-pub struct NeighborChunkSlices {
-    pos_x: BitVec,
-    pos_y: BitVec,
-    pos_z: BitVec,
-    neg_x: BitVec,
-    neg_y: BitVec,
-    neg_z: BitVec,
+pub struct NeighborChunkSlices<T = BitVec> {
+    pos_x: T,
+    pos_y: T,
+    pos_z: T,
+    neg_x: T,
+    neg_y: T,
+    neg_z: T,
 }
 
 impl Default for NeighborChunkSlices {
     fn default() -> Self {
-        let empty = BitVec::repeat(false, CHUNK_SQUARE as usize);
+        Self::splat(BitVec::repeat(false, CHUNK_SQUARE as usize))
+    }
+}
+
+/// Empty light slices count as dark, so they don't cost anything until
+/// they're filled in.
+impl Default for NeighborChunkSlices<Vec<VoxelLight>> {
+    fn default() -> Self {
+        Self::splat(vec![])
+    }
+}
+
+impl<T: Clone> NeighborChunkSlices<T> {
+    fn splat(slice: T) -> Self {
         Self {
-            pos_x: empty.clone(),
-            pos_y: empty.clone(),
-            pos_z: empty.clone(),
-            neg_x: empty.clone(),
-            neg_y: empty.clone(),
-            neg_z: empty,
+            pos_x: slice.clone(),
+            pos_y: slice.clone(),
+            pos_z: slice.clone(),
+            neg_x: slice.clone(),
+            neg_y: slice.clone(),
+            neg_z: slice,
         }
     }
-}
 
-impl NeighborChunkSlices {
-    pub fn get_in_direction(&self, direction: VoxelAxis) -> &BitVec {
+    pub fn get_in_direction(&self, direction: VoxelAxis) -> &T {
         match direction {
             VoxelAxis::PosX => &self.pos_x,
             VoxelAxis::PosY => &self.pos_y,
@@ -37,7 +48,7 @@ impl NeighborChunkSlices {
         }
     }
 
-    pub fn get_in_direction_mut(&mut self, direction: VoxelAxis) -> &mut BitVec {
+    pub fn get_in_direction_mut(&mut self, direction: VoxelAxis) -> &mut T {
         match direction {
             VoxelAxis::PosX => &mut self.pos_x,
             VoxelAxis::PosY => &mut self.pos_y,
@@ -48,3 +59,16 @@ impl NeighborChunkSlices {
         }
     }
 }
+
+/// The edges of every chunk around the one being meshed.
+#[derive(Debug, Default, Clone)]
+pub struct MeshNeighbors {
+    /// Voxels that hide the faces next to them.
+    pub solid: NeighborChunkSlices,
+    /// Translucent voxels, which only hide other translucent faces.
+    pub translucent: NeighborChunkSlices,
+    /// The light just past each edge, which lights the faces on the edge.
+    /// Light changes too often to be worth caching with the rest, so a
+    /// chunk's own edges leave this empty.
+    pub light: NeighborChunkSlices<Vec<VoxelLight>>,
+}
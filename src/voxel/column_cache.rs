@@ -0,0 +1,182 @@
+use super::{world_noise::Chunk2dNoiseValues, WorldGenerator};
+use bevy::{prelude::*, utils::HashMap};
+use priority_queue::PriorityQueue;
+use std::{
+    cmp::Reverse,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+/// Enough columns for a loader radius of 10 with some room to move around.
+/// Each column is roughly 40KiB.
+pub const DEFAULT_COLUMN_CACHE_SIZE: usize = 512;
+
+struct ColumnNoiseCacheInner {
+    capacity: usize,
+    columns: HashMap<IVec2, Arc<Chunk2dNoiseValues>>,
+    /// The column used longest ago has the highest priority, so it's the one
+    /// popped when the cache is full.
+    last_used: PriorityQueue<IVec2, Reverse<u64>>,
+    tick: u64,
+}
+
+impl ColumnNoiseCacheInner {
+    fn touch(&mut self, column: IVec2) {
+        self.tick += 1;
+        self.last_used.push(column, Reverse(self.tick));
+    }
+
+    fn get(&mut self, column: IVec2) -> Option<Arc<Chunk2dNoiseValues>> {
+        let noise = self.columns.get(&column).cloned()?;
+        self.touch(column);
+        Some(noise)
+    }
+
+    fn insert(&mut self, column: IVec2, noise: Arc<Chunk2dNoiseValues>) {
+        self.columns.insert(column, noise);
+        self.touch(column);
+        while self.columns.len() > self.capacity {
+            let Some((oldest, _)) = self.last_used.pop() else {
+                break;
+            };
+            self.columns.remove(&oldest);
+        }
+    }
+}
+
+/// Column noise that has already been generated, shared between every
+/// generation task so each column only gets sampled once while it's in use.
+/// Once it's full the least recently used column is dropped. Cheap to clone.
+#[derive(Clone)]
+pub struct ColumnNoiseCache(Arc<Mutex<ColumnNoiseCacheInner>>);
+
+impl ColumnNoiseCache {
+    pub fn new(capacity: usize) -> Self {
+        Self(Arc::new(Mutex::new(ColumnNoiseCacheInner {
+            capacity: capacity.max(1),
+            columns: HashMap::default(),
+            last_used: PriorityQueue::new(),
+            tick: 0,
+        })))
+    }
+
+    /// Nothing in here is left half updated, so a panic elsewhere while the
+    /// lock was held doesn't make the cache unusable.
+    fn lock(&self) -> MutexGuard<'_, ColumnNoiseCacheInner> {
+        self.0.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    pub fn get(&self, column: IVec2) -> Option<Arc<Chunk2dNoiseValues>> {
+        self.lock().get(column)
+    }
+
+    /// Get the column's noise, asking the generator for it if it isn't cached.
+    /// The lock isn't held while generating, so tasks in other columns don't
+    /// have to wait. If two tasks race on the same column, the first one to
+    /// finish wins and both get its copy.
+    pub fn get_or_generate(
+        &self,
+        column: IVec2,
+        generator: &dyn WorldGenerator,
+    ) -> Option<Arc<Chunk2dNoiseValues>> {
+        if let Some(noise) = self.get(column) {
+            return Some(noise);
+        }
+
+        let generated = Arc::new(generator.column_noise(column)?);
+        let mut inner = self.lock();
+        match inner.get(column) {
+            Some(noise) => Some(noise),
+            None => {
+                inner.insert(column, Arc::clone(&generated));
+                Some(generated)
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.lock().columns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Default for ColumnNoiseCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_COLUMN_CACHE_SIZE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::{Chunk, ChunkPos};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Counts how many times each column gets generated.
+    #[derive(Default)]
+    struct CountingGenerator(AtomicUsize);
+
+    impl WorldGenerator for CountingGenerator {
+        fn column_noise(&self, column: IVec2) -> Option<Chunk2dNoiseValues> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Some(Chunk2dNoiseValues {
+                chunk_pos: column,
+                heightmap: vec![],
+                temperature: vec![],
+                humidity: vec![],
+                variant: vec![],
+                biomes: vec![],
+            })
+        }
+
+        fn generate_chunk(&self, _pos: ChunkPos, _column: Option<&Chunk2dNoiseValues>) -> Chunk {
+            Chunk::default()
+        }
+
+        fn spawn_height(&self, _column: Option<&Chunk2dNoiseValues>) -> Option<i32> {
+            None
+        }
+    }
+
+    #[test]
+    fn generates_each_column_once() {
+        let cache = ColumnNoiseCache::new(4);
+        let generator = CountingGenerator::default();
+
+        for _ in 0..3 {
+            let noise = cache.get_or_generate(IVec2::new(1, 2), &generator).unwrap();
+            assert_eq!(noise.chunk_pos, IVec2::new(1, 2));
+        }
+        assert_eq!(generator.0.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let cache = ColumnNoiseCache::new(2);
+        let generator = CountingGenerator::default();
+        let (a, b, c) = (IVec2::ZERO, IVec2::X, IVec2::Y);
+
+        cache.get_or_generate(a, &generator);
+        cache.get_or_generate(b, &generator);
+        // Using `a` again makes `b` the oldest
+        assert!(cache.get(a).is_some());
+        cache.get_or_generate(c, &generator);
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache.get(a).is_some());
+        assert!(cache.get(b).is_none());
+        assert!(cache.get(c).is_some());
+    }
+
+    #[test]
+    fn shared_between_clones() {
+        let cache = ColumnNoiseCache::new(4);
+        let generator = CountingGenerator::default();
+
+        cache.get_or_generate(IVec2::ZERO, &generator);
+        cache.clone().get_or_generate(IVec2::ZERO, &generator);
+        assert_eq!(generator.0.load(Ordering::SeqCst), 1);
+    }
+}
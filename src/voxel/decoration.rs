@@ -0,0 +1,287 @@
+use super::{Chunk, ChunkPos, InChunkPos, Voxel, VoxelPos, VoxelRegistry, CHUNK_WIDTH};
+use bevy::{prelude::*, utils::HashMap};
+use itertools::iproduct;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+/// A voxel waiting to be placed in a chunk that hasn't been generated or
+/// loaded yet.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingWrite {
+    pub pos: UVec3,
+    pub voxel: Voxel,
+}
+
+/// Voxels that decorations placed outside of the chunk they were generated
+/// in, keyed by the chunk they belong to.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingWrites(HashMap<IVec3, Vec<PendingWrite>>);
+
+impl PendingWrites {
+    pub fn push(&mut self, voxel_pos: IVec3, voxel: Voxel) {
+        let chunk_pos = ChunkPos::from(VoxelPos(voxel_pos));
+        let pos = (voxel_pos - VoxelPos::from(chunk_pos).0).as_uvec3();
+        self.0
+            .entry(chunk_pos.0)
+            .or_default()
+            .push(PendingWrite { pos, voxel });
+    }
+
+    pub fn extend(&mut self, chunk_pos: ChunkPos, writes: Vec<PendingWrite>) {
+        self.0.entry(chunk_pos.0).or_default().extend(writes);
+    }
+
+    pub fn take(&mut self, chunk_pos: ChunkPos) -> Option<Vec<PendingWrite>> {
+        self.0.remove(&chunk_pos.0)
+    }
+
+    pub fn into_chunks(self) -> impl Iterator<Item = (ChunkPos, Vec<PendingWrite>)> {
+        self.0
+            .into_iter()
+            .map(|(chunk_pos, writes)| (ChunkPos(chunk_pos), writes))
+    }
+}
+
+/// Place the writes in the chunk. Decorations only ever replace air, so they
+/// can't overwrite terrain or anything the player has built since. Returns
+/// whether anything changed.
+pub fn apply_writes(chunk: &mut Chunk, writes: &[PendingWrite]) -> bool {
+    let mut changed = false;
+    for write in writes {
+        let Some(pos) = InChunkPos::new(write.pos) else {
+            continue;
+        };
+        if chunk.at(pos).is_air() {
+            chunk.set(pos, write.voxel);
+            changed = true;
+        }
+    }
+    changed
+}
+
+/// How far above the ground decorations can reach, counting from the first
+/// voxel above it. Trees are the tallest.
+pub const MAX_DECORATION_HEIGHT: i32 = 6;
+
+/// Places trees, boulders and ruins on top of freshly generated terrain.
+/// Every column gets its own random generator seeded from the world seed
+/// and the column position, so the same world always decorates the same
+/// way no matter what order the chunks generate in.
+#[derive(Clone)]
+pub struct Decorator {
+    seed: u32,
+    log: Voxel,
+    leaves: Voxel,
+    stone: Voxel,
+    grass: Voxel,
+}
+
+impl Decorator {
+    pub fn new(seed: u32, registry: &VoxelRegistry) -> Self {
+        let voxel = |name: &str| {
+            registry
+                .by_name(name)
+                .unwrap_or_else(|| panic!("voxel registry is missing \"{name}\""))
+        };
+        Self {
+            seed,
+            log: voxel("log"),
+            leaves: voxel("leaves"),
+            stone: voxel("stone"),
+            grass: voxel("grass"),
+        }
+    }
+
+    /// Decorate the columns whose ground is inside this chunk. Voxels that
+    /// land inside the chunk are placed straight away, and the rest are
+    /// returned so they can be written into their own chunks.
+    pub fn decorate(
+        &self,
+        chunk: &mut Chunk,
+        chunk_pos: ChunkPos,
+        heightmap: &[f64],
+        sea_level: i32,
+    ) -> PendingWrites {
+        let origin = VoxelPos::from(chunk_pos).0;
+        let mut outside = PendingWrites::default();
+
+        for (z, x) in iproduct!(0..CHUNK_WIDTH, 0..CHUNK_WIDTH) {
+            let ground_y = heightmap[(z * CHUNK_WIDTH + x) as usize].round() as i32 - 1;
+            if ground_y < sea_level {
+                continue;
+            }
+            let Some(ground_pos) = u32::try_from(ground_y - origin.y)
+                .ok()
+                .and_then(|y| InChunkPos::new(UVec3::new(x, y, z)))
+            else {
+                continue;
+            };
+            // Caves can carve away the ground
+            let ground = chunk.at(ground_pos);
+            if ground.is_air() {
+                continue;
+            }
+
+            let anchor = origin + ground_pos.pos().as_ivec3() + IVec3::Y;
+            let mut rng = StdRng::seed_from_u64(self.column_seed(anchor.x, anchor.z));
+            let structure = match rng.gen::<f64>() {
+                roll if roll < 0.02 && ground == self.grass => self.tree(&mut rng),
+                roll if (0.02..0.026).contains(&roll) => self.boulder(&mut rng),
+                roll if (0.026..0.0265).contains(&roll) => self.ruin(&mut rng),
+                _ => continue,
+            };
+
+            for (offset, voxel) in structure {
+                let voxel_pos = anchor + offset;
+                let local = voxel_pos - origin;
+                let in_chunk = match local.min_element() >= 0 {
+                    true => InChunkPos::new(local.as_uvec3()),
+                    false => None,
+                };
+                match in_chunk {
+                    Some(pos) => {
+                        if chunk.at(pos).is_air() {
+                            chunk.set(pos, voxel);
+                        }
+                    }
+                    None => outside.push(voxel_pos, voxel),
+                }
+            }
+        }
+
+        outside
+    }
+
+    fn column_seed(&self, x: i32, z: i32) -> u64 {
+        // Just needs to mix the bits well enough that neighboring columns
+        // look unrelated
+        let mut hash = (self.seed as u64) ^ 0x9e37_79b9_7f4a_7c15;
+        for value in [x as u32 as u64, z as u32 as u64] {
+            hash = (hash ^ value).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            hash ^= hash >> 31;
+        }
+        hash
+    }
+
+    fn tree(&self, rng: &mut StdRng) -> Vec<(IVec3, Voxel)> {
+        let trunk_height = rng.gen_range(4..=6);
+        let mut voxels = (0..trunk_height)
+            .map(|y| (IVec3::new(0, y, 0), self.log))
+            .collect::<Vec<_>>();
+
+        for y in (trunk_height - 2)..=trunk_height {
+            let radius: i32 = match y < trunk_height {
+                true => 2,
+                false => 1,
+            };
+            for (dx, dz) in iproduct!(-radius..=radius, -radius..=radius) {
+                let is_corner = dx.abs() == radius && dz.abs() == radius;
+                if (dx == 0 && dz == 0 && y < trunk_height) || (is_corner && rng.gen_bool(0.5)) {
+                    continue;
+                }
+                voxels.push((IVec3::new(dx, y, dz), self.leaves));
+            }
+        }
+        voxels
+    }
+
+    fn boulder(&self, rng: &mut StdRng) -> Vec<(IVec3, Voxel)> {
+        let radius = rng.gen_range(1..=2);
+        let reach = radius as f32 + 0.3;
+        // Sunk into the ground a bit so it doesn't look dropped from the sky
+        let center = IVec3::new(0, radius - 1, 0);
+        iproduct!(-radius..=radius, -radius..=radius, -radius..=radius)
+            .map(|(x, y, z)| IVec3::new(x, y, z))
+            .filter(|offset| offset.as_vec3().length() <= reach)
+            .map(|offset| (center + offset, self.stone))
+            .collect()
+    }
+
+    fn ruin(&self, rng: &mut StdRng) -> Vec<(IVec3, Voxel)> {
+        const HALF_WIDTH: i32 = 2;
+        let mut voxels = vec![];
+        for (dx, dz) in iproduct!(-HALF_WIDTH..=HALF_WIDTH, -HALF_WIDTH..=HALF_WIDTH) {
+            // Only the walls, worn down to a random height
+            if dx.abs() != HALF_WIDTH && dz.abs() != HALF_WIDTH {
+                continue;
+            }
+            for y in 0..rng.gen_range(0..=3) {
+                voxels.push((IVec3::new(dx, y, dz), self.stone));
+            }
+        }
+        voxels
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GROUND_Y: u32 = 10;
+
+    /// Grass on top of stone, with the ground in the middle of the chunk.
+    fn grassy_chunk(registry: &VoxelRegistry) -> (Chunk, Vec<f64>) {
+        let [stone, grass] = ["stone", "grass"].map(|name| registry.by_name(name).unwrap());
+        let mut chunk = Chunk::default();
+        for (x, y, z) in iproduct!(0..CHUNK_WIDTH, 0..=GROUND_Y, 0..CHUNK_WIDTH) {
+            let voxel = match y == GROUND_Y {
+                true => grass,
+                false => stone,
+            };
+            chunk.set(InChunkPos::new(UVec3::new(x, y, z)).unwrap(), voxel);
+        }
+        let heightmap = vec![GROUND_Y as f64 + 1.0; (CHUNK_WIDTH * CHUNK_WIDTH) as usize];
+        (chunk, heightmap)
+    }
+
+    #[test]
+    fn same_seed_decorates_the_same() {
+        let registry = VoxelRegistry::builtin();
+        let (chunk, heightmap) = grassy_chunk(&registry);
+        let pos = ChunkPos(IVec3::new(3, 0, -2));
+        let decorate = |seed| {
+            let mut chunk = chunk.clone();
+            let outside = Decorator::new(seed, &registry).decorate(&mut chunk, pos, &heightmap, 0);
+            (chunk, outside)
+        };
+
+        let (first_chunk, first_outside) = decorate(42);
+        let (second_chunk, second_outside) = decorate(42);
+        assert_ne!(first_chunk.voxels, chunk.voxels);
+        assert_eq!(first_chunk.voxels, second_chunk.voxels);
+        assert_eq!(first_outside, second_outside);
+
+        let (other_chunk, _) = decorate(43);
+        assert_ne!(first_chunk.voxels, other_chunk.voxels);
+    }
+
+    #[test]
+    fn trees_spill_into_neighbors_over_air_only() {
+        let registry = VoxelRegistry::builtin();
+        let [leaves, dirt] = ["leaves", "dirt"].map(|name| registry.by_name(name).unwrap());
+        let (mut chunk, heightmap) = grassy_chunk(&registry);
+        let pos = ChunkPos(IVec3::ZERO);
+        let outside = Decorator::new(42, &registry).decorate(&mut chunk, pos, &heightmap, 0);
+
+        // Everything that didn't fit goes to the chunks right around this one
+        let neighbors = outside.into_chunks().collect::<Vec<_>>();
+        assert!(neighbors
+            .iter()
+            .all(|(chunk_pos, _)| (chunk_pos.0 - pos.0).abs().max_element() == 1));
+        let (_, writes) = neighbors
+            .iter()
+            .find(|(_, writes)| writes.iter().any(|write| write.voxel == leaves))
+            .expect("a tree near the edge should reach into the next chunk");
+
+        // Part of the neighbor is already built up, and has to stay that way
+        let mut neighbor_chunk = Chunk::default();
+        let blocked = InChunkPos::new(writes[0].pos).unwrap();
+        neighbor_chunk.set(blocked, dirt);
+        assert!(apply_writes(&mut neighbor_chunk, writes));
+        assert_eq!(neighbor_chunk.at(blocked), dirt);
+        for write in writes {
+            let voxel = neighbor_chunk.at(InChunkPos::new(write.pos).unwrap());
+            assert!(!voxel.is_air());
+        }
+    }
+}
@@ -0,0 +1,640 @@
+//! Flood fill lighting. Sky light pours in from above and block light
+//! spreads out from voxels that give it off, each losing a level for every
+//! voxel it travels through. Chunks are lit on their own as they're
+//! generated, then connected to their neighbours once they're in the world,
+//! and voxel changes are patched up incrementally after that.
+
+use crate::voxel::{
+    Chunk, ChunkPos, InChunkPos, LightContainer, LightKind, Voxel, VoxelLight, VoxelPos,
+    VoxelRegistry, CHUNK_WIDTH, MAX_LIGHT,
+};
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use itertools::iproduct;
+use std::collections::VecDeque;
+
+const NEIGHBORS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+const LIGHT_KINDS: [LightKind; 2] = [LightKind::Sky, LightKind::Block];
+
+/// The loaded chunks light can spread between.
+pub trait ChunkMap {
+    fn chunk(&self, pos: ChunkPos) -> Option<&Chunk>;
+
+    fn chunk_mut(&mut self, pos: ChunkPos) -> Option<&mut Chunk>;
+}
+
+impl ChunkMap for HashMap<ChunkPos, Chunk> {
+    fn chunk(&self, pos: ChunkPos) -> Option<&Chunk> {
+        self.get(&pos)
+    }
+
+    fn chunk_mut(&mut self, pos: ChunkPos) -> Option<&mut Chunk> {
+        self.get_mut(&pos)
+    }
+}
+
+/// Somewhere light can be flood filled through.
+trait LightVolume {
+    /// `None` if the voxel isn't loaded, light doesn't spread there.
+    fn voxel(&self, pos: IVec3) -> Option<Voxel>;
+
+    fn light(&self, pos: IVec3) -> VoxelLight;
+
+    fn set_light(&mut self, pos: IVec3, light: VoxelLight);
+}
+
+/// A single chunk on its own, in chunk coordinates.
+impl LightVolume for Chunk {
+    fn voxel(&self, pos: IVec3) -> Option<Voxel> {
+        in_chunk(pos).map(|pos| self.at(pos))
+    }
+
+    fn light(&self, pos: IVec3) -> VoxelLight {
+        in_chunk(pos)
+            .map(|pos| self.light_at(pos))
+            .unwrap_or_default()
+    }
+
+    fn set_light(&mut self, pos: IVec3, light: VoxelLight) {
+        if let Some(pos) = in_chunk(pos) {
+            self.light.set(pos, light);
+        }
+    }
+}
+
+/// Every loaded chunk, in world coordinates. Keeps track of which chunks'
+/// meshes are out of date because of light changes.
+struct WorldLight<'a, M: ChunkMap> {
+    chunks: &'a mut M,
+    changed: HashSet<ChunkPos>,
+}
+
+impl<'a, M: ChunkMap> WorldLight<'a, M> {
+    fn new(chunks: &'a mut M) -> Self {
+        Self {
+            chunks,
+            changed: HashSet::default(),
+        }
+    }
+}
+
+impl<M: ChunkMap> LightVolume for WorldLight<'_, M> {
+    fn voxel(&self, pos: IVec3) -> Option<Voxel> {
+        let (chunk_pos, pos) = split_pos(pos);
+        self.chunks.chunk(chunk_pos).map(|chunk| chunk.at(pos))
+    }
+
+    fn light(&self, pos: IVec3) -> VoxelLight {
+        let (chunk_pos, pos) = split_pos(pos);
+        self.chunks
+            .chunk(chunk_pos)
+            .map(|chunk| chunk.light_at(pos))
+            .unwrap_or_default()
+    }
+
+    fn set_light(&mut self, pos: IVec3, light: VoxelLight) {
+        let (chunk_pos, in_chunk_pos) = split_pos(pos);
+        let Some(chunk) = self.chunks.chunk_mut(chunk_pos) else {
+            return;
+        };
+        chunk.light.set(in_chunk_pos, light);
+
+        // Faces in the chunk next door are lit by voxels on this side of the
+        // border, so those need remeshing too
+        self.changed.insert(chunk_pos);
+        if in_chunk_pos.min_element() > 0 && in_chunk_pos.max_element() < CHUNK_WIDTH - 1 {
+            return;
+        }
+        for offset in NEIGHBORS {
+            let (neighbor_chunk, _) = split_pos(pos + offset);
+            if neighbor_chunk != chunk_pos {
+                self.changed.insert(neighbor_chunk);
+            }
+        }
+    }
+}
+
+fn in_chunk(pos: IVec3) -> Option<InChunkPos> {
+    match pos.min_element() >= 0 {
+        true => InChunkPos::new(pos.as_uvec3()),
+        false => None,
+    }
+}
+
+fn split_pos(pos: IVec3) -> (ChunkPos, InChunkPos) {
+    let chunk_pos = ChunkPos::from(VoxelPos(pos));
+    let in_chunk_pos = InChunkPos::new((pos - VoxelPos::from(chunk_pos).0).as_uvec3()).unwrap();
+    (chunk_pos, in_chunk_pos)
+}
+
+/// The light a voxel makes itself, which it keeps no matter what's around it.
+fn source_level(registry: &VoxelRegistry, kind: LightKind, voxel: Voxel) -> u8 {
+    match kind {
+        LightKind::Sky => 0,
+        LightKind::Block => registry.light_emission(voxel),
+    }
+}
+
+/// How bright light at `level` is once it's moved one voxel along
+/// `direction`.
+fn spread_level(kind: LightKind, level: u8, direction: IVec3) -> u8 {
+    match kind == LightKind::Sky && level == MAX_LIGHT && direction == IVec3::NEG_Y {
+        true => MAX_LIGHT,
+        false => level.saturating_sub(1),
+    }
+}
+
+/// Flood light out from every voxel in the queue into anything darker.
+fn spread(
+    volume: &mut impl LightVolume,
+    registry: &VoxelRegistry,
+    kind: LightKind,
+    queue: &mut VecDeque<IVec3>,
+) {
+    while let Some(pos) = queue.pop_front() {
+        let level = volume.light(pos).get(kind);
+        if level == 0 {
+            continue;
+        }
+
+        for direction in NEIGHBORS {
+            let next = pos + direction;
+            if !volume
+                .voxel(next)
+                .is_some_and(|voxel| registry.passes_light(voxel))
+            {
+                continue;
+            }
+
+            let next_level = spread_level(kind, level, direction);
+            let light = volume.light(next);
+            if light.get(kind) < next_level {
+                volume.set_light(next, light.with(kind, next_level));
+                queue.push_back(next);
+            }
+        }
+    }
+}
+
+/// Take away all the light that came from the voxels in `removed`, which
+/// were lit at the paired level. Anything lit from somewhere else is left
+/// alone and added to `relight`, so it can spread back into the gap.
+fn unspread(
+    volume: &mut impl LightVolume,
+    registry: &VoxelRegistry,
+    kind: LightKind,
+    removed: &mut VecDeque<(IVec3, u8)>,
+    relight: &mut VecDeque<IVec3>,
+) {
+    while let Some((pos, level)) = removed.pop_front() {
+        for direction in NEIGHBORS {
+            let next = pos + direction;
+            let Some(voxel) = volume.voxel(next) else {
+                continue;
+            };
+            let light = volume.light(next);
+            let next_level = light.get(kind);
+            if next_level == 0 {
+                continue;
+            }
+
+            let source = source_level(registry, kind, voxel);
+            let lit_from_here =
+                next_level < level || next_level == spread_level(kind, level, direction);
+            if lit_from_here && next_level > source {
+                volume.set_light(next, light.with(kind, source));
+                removed.push_back((next, next_level));
+                if source > 0 {
+                    relight.push_back(next);
+                }
+            } else {
+                relight.push_back(next);
+            }
+        }
+    }
+}
+
+/// Light a chunk on its own, as if every chunk around it was dark. With
+/// `open_sky`, full sky light shines down into it from above. Anything
+/// coming from the neighbours gets added by [`spread_into_chunk`] once the
+/// chunk is in the world.
+pub fn light_chunk(chunk: &mut Chunk, registry: &VoxelRegistry, open_sky: bool) {
+    let palette = chunk.voxels.palette();
+    let emits_light = palette
+        .iter()
+        .any(|voxel| registry.light_emission(*voxel) > 0);
+
+    // Skip the flood fill for chunks where every voxel ends up the same
+    if !emits_light {
+        if !open_sky {
+            chunk.light = LightContainer::default();
+            return;
+        }
+        if palette.iter().all(|voxel| registry.passes_light(*voxel)) {
+            chunk.light = LightContainer::from_light(VoxelLight::FULL_SKY);
+            return;
+        }
+    }
+
+    chunk.light = LightContainer::default();
+    let width = CHUNK_WIDTH as i32;
+
+    let mut sky = VecDeque::new();
+    if open_sky {
+        for (z, x) in iproduct!(0..width, 0..width) {
+            for y in (0..width).rev() {
+                let pos = IVec3::new(x, y, z);
+                if !chunk
+                    .voxel(pos)
+                    .is_some_and(|voxel| registry.passes_light(voxel))
+                {
+                    break;
+                }
+                chunk.set_light(pos, VoxelLight::FULL_SKY);
+                sky.push_back(pos);
+            }
+        }
+    }
+
+    let mut block = VecDeque::new();
+    if emits_light {
+        for (z, y, x) in iproduct!(0..width, 0..width, 0..width) {
+            let pos = IVec3::new(x, y, z);
+            let emission = chunk
+                .voxel(pos)
+                .map(|voxel| registry.light_emission(voxel))
+                .unwrap_or(0);
+            if emission > 0 {
+                let light = chunk.light(pos);
+                chunk.set_light(pos, light.with(LightKind::Block, emission));
+                block.push_back(pos);
+            }
+        }
+    }
+
+    spread(chunk, registry, LightKind::Sky, &mut sky);
+    spread(chunk, registry, LightKind::Block, &mut block);
+}
+
+/// Let light flow between a chunk that was just added to the world and the
+/// loaded chunks around it. Returns the chunks whose meshes need updating.
+pub fn spread_into_chunk(
+    chunks: &mut impl ChunkMap,
+    pos: ChunkPos,
+    registry: &VoxelRegistry,
+) -> HashSet<ChunkPos> {
+    let mut world = WorldLight::new(chunks);
+    let origin = VoxelPos::from(pos).0;
+    let last = CHUNK_WIDTH as i32 - 1;
+
+    // Chunks get lit assuming the sky is open above them if the generator
+    // says so, which can be wrong once the chunk above actually shows up.
+    // Sky light that was never really there has to go before anything
+    // spreads.
+    let mut removed = VecDeque::new();
+    let mut relight = VecDeque::new();
+    for lower in [pos.0, pos.0 - IVec3::Y] {
+        let top_of_lower = VoxelPos::from(ChunkPos(lower)).0 + IVec3::Y * last;
+        for (z, x) in iproduct!(0..=last, 0..=last) {
+            let below = top_of_lower + IVec3::new(x, 0, z);
+            let above = below + IVec3::Y;
+            let (Some(_), Some(above_voxel)) = (world.voxel(below), world.voxel(above)) else {
+                continue;
+            };
+            let below_light = world.light(below);
+            let sky_from_above =
+                registry.passes_light(above_voxel) && world.light(above).sky() == MAX_LIGHT;
+            if below_light.sky() == MAX_LIGHT && !sky_from_above {
+                world.set_light(below, below_light.with(LightKind::Sky, 0));
+                removed.push_back((below, MAX_LIGHT));
+            }
+        }
+    }
+    unspread(
+        &mut world,
+        registry,
+        LightKind::Sky,
+        &mut removed,
+        &mut relight,
+    );
+
+    // Everything on both sides of each border gets another go at spreading
+    let mut border = VecDeque::new();
+    for (a, b) in iproduct!(0..=last, 0..=last) {
+        for (inside, outside) in [
+            (IVec3::new(0, a, b), IVec3::new(-1, a, b)),
+            (IVec3::new(last, a, b), IVec3::new(last + 1, a, b)),
+            (IVec3::new(a, 0, b), IVec3::new(a, -1, b)),
+            (IVec3::new(a, last, b), IVec3::new(a, last + 1, b)),
+            (IVec3::new(a, b, 0), IVec3::new(a, b, -1)),
+            (IVec3::new(a, b, last), IVec3::new(a, b, last + 1)),
+        ] {
+            border.push_back(origin + inside);
+            border.push_back(origin + outside);
+        }
+    }
+
+    relight.extend(border.iter().copied());
+    spread(&mut world, registry, LightKind::Sky, &mut relight);
+    spread(&mut world, registry, LightKind::Block, &mut border);
+
+    world.changed
+}
+
+/// Fix up the light around a voxel that was just placed or removed. Returns
+/// the chunks whose meshes need updating.
+pub fn relight_voxel(
+    chunks: &mut impl ChunkMap,
+    pos: IVec3,
+    registry: &VoxelRegistry,
+) -> HashSet<ChunkPos> {
+    let mut world = WorldLight::new(chunks);
+    let Some(voxel) = world.voxel(pos) else {
+        return HashSet::default();
+    };
+
+    for kind in LIGHT_KINDS {
+        let light = world.light(pos);
+        let source = source_level(registry, kind, voxel);
+        let mut removed = VecDeque::from([(pos, light.get(kind))]);
+        let mut relight = VecDeque::new();
+
+        world.set_light(pos, light.with(kind, source));
+        if source > 0 {
+            relight.push_back(pos);
+        }
+        // Whatever is lit around the voxel gets queued up to spread back
+        // into it, if it lets light through now
+        unspread(&mut world, registry, kind, &mut removed, &mut relight);
+        spread(&mut world, registry, kind, &mut relight);
+    }
+
+    world.changed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The normal registry, plus a lamp.
+    fn registry() -> VoxelRegistry {
+        VoxelRegistry::builtin_with("(voxels: [(id: 100, name: \"lamp\", light: 14)])")
+    }
+
+    fn voxel(registry: &VoxelRegistry, name: &str) -> Voxel {
+        registry.by_name(name).unwrap()
+    }
+
+    /// A chunk with its lower half filled with stone.
+    fn ground_chunk(registry: &VoxelRegistry) -> Chunk {
+        let mut chunk = Chunk::default();
+        for (x, y, z) in iproduct!(0..CHUNK_WIDTH, 0..CHUNK_WIDTH / 2, 0..CHUNK_WIDTH) {
+            chunk.set(
+                InChunkPos::new(UVec3::new(x, y, z)).unwrap(),
+                voxel(registry, "stone"),
+            );
+        }
+        chunk
+    }
+
+    fn light_at(chunks: &HashMap<ChunkPos, Chunk>, pos: IVec3) -> VoxelLight {
+        let (chunk_pos, pos) = split_pos(pos);
+        chunks[&chunk_pos].light_at(pos)
+    }
+
+    /// Set a voxel in the world and relight around it.
+    fn set_voxel(
+        chunks: &mut HashMap<ChunkPos, Chunk>,
+        registry: &VoxelRegistry,
+        pos: IVec3,
+        voxel: Voxel,
+    ) -> HashSet<ChunkPos> {
+        let (chunk_pos, in_chunk_pos) = split_pos(pos);
+        chunks.get_mut(&chunk_pos).unwrap().set(in_chunk_pos, voxel);
+        relight_voxel(chunks, pos, registry)
+    }
+
+    /// Relight every chunk from scratch, for comparing against incremental
+    /// updates.
+    fn light_from_scratch(
+        chunks: &HashMap<ChunkPos, Chunk>,
+        registry: &VoxelRegistry,
+        open_sky: impl Fn(ChunkPos) -> bool,
+    ) -> HashMap<ChunkPos, Chunk> {
+        let mut relit = HashMap::default();
+        for (pos, chunk) in chunks {
+            let mut chunk = chunk.clone();
+            light_chunk(&mut chunk, registry, open_sky(*pos));
+            relit.insert(*pos, chunk);
+            spread_into_chunk(&mut relit, *pos, registry);
+        }
+        relit
+    }
+
+    fn assert_same_light(a: &HashMap<ChunkPos, Chunk>, b: &HashMap<ChunkPos, Chunk>) {
+        for (pos, chunk) in a {
+            for (x, y, z) in iproduct!(0..CHUNK_WIDTH, 0..CHUNK_WIDTH, 0..CHUNK_WIDTH) {
+                let in_chunk_pos = InChunkPos::new(UVec3::new(x, y, z)).unwrap();
+                assert_eq!(
+                    chunk.light_at(in_chunk_pos),
+                    b[pos].light_at(in_chunk_pos),
+                    "light at {:?} in chunk {}",
+                    in_chunk_pos.pos(),
+                    pos.0
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn light_levels_pack_into_a_byte() {
+        let light = VoxelLight::new(12, 3);
+        assert_eq!((light.sky(), light.block()), (12, 3));
+        assert_eq!(light.with(LightKind::Block, 9).block(), 9);
+        assert_eq!(light.with(LightKind::Sky, 0).sky(), 0);
+        assert_eq!(
+            VoxelLight::new(99, 99),
+            VoxelLight::new(MAX_LIGHT, MAX_LIGHT)
+        );
+    }
+
+    #[test]
+    fn open_chunks_stay_uniform() {
+        let registry = registry();
+        let mut chunk = Chunk::default();
+        light_chunk(&mut chunk, &registry, true);
+        assert_eq!(
+            chunk.light,
+            LightContainer::from_light(VoxelLight::FULL_SKY)
+        );
+
+        light_chunk(&mut chunk, &registry, false);
+        assert_eq!(chunk.light, LightContainer::default());
+    }
+
+    #[test]
+    fn sky_light_falls_and_fades_under_cover() {
+        let registry = registry();
+        let mut chunk = ground_chunk(&registry);
+        // A roof over one corner
+        let roof_y = CHUNK_WIDTH / 2 + 3;
+        for (x, z) in iproduct!(0..5, 0..5) {
+            chunk.set(
+                InChunkPos::new(UVec3::new(x, roof_y, z)).unwrap(),
+                voxel(&registry, "stone"),
+            );
+        }
+        light_chunk(&mut chunk, &registry, true);
+
+        let at = |x, y, z| chunk.light_at(InChunkPos::new(UVec3::new(x, y, z)).unwrap());
+        let ground = CHUNK_WIDTH / 2;
+        // Full sky light reaches all the way down in the open
+        assert_eq!(at(20, ground, 20).sky(), MAX_LIGHT);
+        // Nothing gets into the stone
+        assert_eq!(at(20, ground - 1, 20).sky(), 0);
+        // Under the roof it's lit from the side, so it's dimmer the further
+        // in you go
+        assert_eq!(at(4, ground, 0).sky(), MAX_LIGHT - 1);
+        assert_eq!(at(0, ground, 0).sky(), MAX_LIGHT - 5);
+    }
+
+    #[test]
+    fn block_light_fades_with_distance() {
+        let registry = registry();
+        let mut chunk = Chunk::default();
+        let lamp = UVec3::splat(15);
+        chunk.set(InChunkPos::new(lamp).unwrap(), voxel(&registry, "lamp"));
+        light_chunk(&mut chunk, &registry, false);
+
+        let at = |pos: UVec3| chunk.light_at(InChunkPos::new(pos).unwrap());
+        assert_eq!(at(lamp).block(), 14);
+        assert_eq!(at(lamp + UVec3::X).block(), 13);
+        assert_eq!(at(lamp + UVec3::new(3, 2, 1)).block(), 8);
+        assert_eq!(at(lamp).sky(), 0);
+    }
+
+    #[test]
+    fn light_crosses_chunk_borders() {
+        let registry = registry();
+        let mut chunks = HashMap::default();
+        let mut lit = Chunk::default();
+        let lamp = UVec3::new(CHUNK_WIDTH - 2, 5, 5);
+        lit.set(InChunkPos::new(lamp).unwrap(), voxel(&registry, "lamp"));
+        light_chunk(&mut lit, &registry, false);
+        chunks.insert(ChunkPos(IVec3::ZERO), lit);
+
+        let mut next_door = Chunk::default();
+        light_chunk(&mut next_door, &registry, false);
+        chunks.insert(ChunkPos(IVec3::X), next_door);
+        let changed = spread_into_chunk(&mut chunks, ChunkPos(IVec3::X), &registry);
+
+        let lamp = lamp.as_ivec3();
+        assert_eq!(light_at(&chunks, lamp + IVec3::new(2, 0, 0)).block(), 12);
+        assert_eq!(light_at(&chunks, lamp + IVec3::new(5, 0, 0)).block(), 9);
+        assert!(changed.contains(&ChunkPos(IVec3::X)));
+    }
+
+    #[test]
+    fn digging_lets_light_in_and_filling_takes_it_away() {
+        let registry = registry();
+        let mut chunks = HashMap::default();
+        for pos in [IVec3::ZERO, IVec3::X, IVec3::Y, IVec3::new(1, 1, 0)] {
+            let mut chunk = match pos.y {
+                0 => ground_chunk(&registry),
+                _ => Chunk::default(),
+            };
+            light_chunk(&mut chunk, &registry, pos.y == 1);
+            chunks.insert(ChunkPos(pos), chunk);
+            spread_into_chunk(&mut chunks, ChunkPos(pos), &registry);
+        }
+
+        // Dig a shaft down from the surface and a tunnel off the bottom of
+        // it, across the border into the next chunk
+        let surface = CHUNK_WIDTH as i32 / 2 - 1;
+        let shaft = (surface - 8..=surface).map(|y| IVec3::new(CHUNK_WIDTH as i32 - 4, y, 10));
+        let tunnel = (1..8).map(|x| IVec3::new(CHUNK_WIDTH as i32 - 4 + x, surface - 8, 10));
+        let dug = shaft.chain(tunnel).collect::<Vec<_>>();
+        for pos in &dug {
+            set_voxel(&mut chunks, &registry, *pos, Voxel::AIR);
+        }
+
+        let bottom = IVec3::new(CHUNK_WIDTH as i32 - 4, surface - 8, 10);
+        assert_eq!(light_at(&chunks, bottom).sky(), MAX_LIGHT);
+        assert_eq!(
+            light_at(&chunks, bottom + IVec3::X * 7).sky(),
+            MAX_LIGHT - 7
+        );
+        assert_same_light(
+            &chunks,
+            &light_from_scratch(&chunks, &registry, |pos| pos.0.y == 1),
+        );
+
+        // Cap the shaft, the tunnel goes dark
+        let changed = set_voxel(
+            &mut chunks,
+            &registry,
+            IVec3::new(CHUNK_WIDTH as i32 - 4, surface, 10),
+            voxel(&registry, "stone"),
+        );
+        assert_eq!(light_at(&chunks, bottom).sky(), 0);
+        assert_eq!(light_at(&chunks, bottom + IVec3::X * 7).sky(), 0);
+        assert!(changed.contains(&ChunkPos(IVec3::X)));
+        assert_same_light(
+            &chunks,
+            &light_from_scratch(&chunks, &registry, |pos| pos.0.y == 1),
+        );
+    }
+
+    #[test]
+    fn removing_a_lamp_leaves_other_lamps_lit() {
+        let registry = registry();
+        let mut chunks = HashMap::default();
+        let mut chunk = Chunk::default();
+        light_chunk(&mut chunk, &registry, false);
+        chunks.insert(ChunkPos(IVec3::ZERO), chunk);
+
+        let (a, b) = (IVec3::new(5, 5, 5), IVec3::new(10, 5, 5));
+        set_voxel(&mut chunks, &registry, a, voxel(&registry, "lamp"));
+        set_voxel(&mut chunks, &registry, b, voxel(&registry, "lamp"));
+        assert_eq!(light_at(&chunks, IVec3::new(7, 5, 5)).block(), 12);
+
+        set_voxel(&mut chunks, &registry, a, Voxel::AIR);
+        assert_eq!(light_at(&chunks, a).block(), 9);
+        assert_eq!(light_at(&chunks, b).block(), 14);
+        assert_same_light(&chunks, &light_from_scratch(&chunks, &registry, |_| false));
+    }
+
+    #[test]
+    fn roof_arriving_later_blocks_assumed_sky() {
+        let registry = registry();
+        let mut chunks = HashMap::default();
+        let mut below = Chunk::default();
+        light_chunk(&mut below, &registry, true);
+        chunks.insert(ChunkPos(IVec3::ZERO), below);
+
+        // The chunk above turns out to be solid stone
+        let mut above = Chunk::default();
+        for (x, y, z) in iproduct!(0..CHUNK_WIDTH, 0..CHUNK_WIDTH, 0..CHUNK_WIDTH) {
+            above.set(
+                InChunkPos::new(UVec3::new(x, y, z)).unwrap(),
+                voxel(&registry, "stone"),
+            );
+        }
+        light_chunk(&mut above, &registry, true);
+        chunks.insert(ChunkPos(IVec3::Y), above);
+        spread_into_chunk(&mut chunks, ChunkPos(IVec3::Y), &registry);
+
+        assert_eq!(light_at(&chunks, IVec3::splat(10)).sky(), 0);
+        assert_eq!(light_at(&chunks, IVec3::new(10, 0, 10)).sky(), 0);
+    }
+}
@@ -1,15 +1,30 @@
 mod axis;
 mod biome;
 mod chunk_stuff;
+mod column_cache;
+mod decoration;
+mod light;
 mod region;
+mod registry;
 mod voxels;
+mod world_generator;
 pub mod world_noise;
+mod worldgen_config;
 
 pub use axis::*;
 pub use biome::*;
-pub use chunk_stuff::{chunk::*, chunk_mesh::*, chunk_pos::*, container::*, neighbor_slice::*};
+pub use chunk_stuff::{
+    chunk::*, chunk_bitmask::*, chunk_mesh::*, chunk_pos::*, collision_mesh::*, container::*,
+    light_container::*, neighbor_slice::*,
+};
+pub use column_cache::*;
+pub use decoration::*;
+pub use light::*;
 pub use region::*;
+pub use registry::*;
 pub use voxels::*;
+pub use world_generator::*;
+pub use worldgen_config::*;
 
 pub const CHUNK_WIDTH: u32 = 31;
 pub const CHUNK_SQUARE: u32 = CHUNK_WIDTH * CHUNK_WIDTH;
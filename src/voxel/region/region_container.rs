@@ -1,4 +1,5 @@
 use crate::voxel::{InRegionChunkPos, VoxelContainer, REGION_CUBE};
+use bevy::utils::HashSet;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
 
@@ -7,12 +8,16 @@ use serde_with::serde_as;
 pub struct VoxelRegion {
     #[serde_as(as = "Box<[_; REGION_CUBE as usize]>")]
     chunks: Box<[Option<VoxelContainer>; REGION_CUBE as usize]>,
+    /// Indices of chunks that have changed since they were last written.
+    #[serde(skip)]
+    dirty: HashSet<usize>,
 }
 
 impl Default for VoxelRegion {
     fn default() -> Self {
         Self {
             chunks: Box::new(vec![None; REGION_CUBE as usize].try_into().unwrap()),
+            dirty: HashSet::default(),
         }
     }
 }
@@ -26,12 +31,35 @@ impl VoxelRegion {
         &mut self.chunks[pos.index()]
     }
 
-    #[allow(unused)]
+    /// Replace a chunk and mark it as needing to be saved.
+    pub fn set_modified_chunk(&mut self, pos: InRegionChunkPos, chunk: VoxelContainer) {
+        self.chunks[pos.index()] = Some(chunk);
+        self.dirty.insert(pos.index());
+    }
+
+    pub fn is_dirty(&self) -> bool {
+        !self.dirty.is_empty()
+    }
+
+    pub fn is_chunk_dirty(&self, pos: InRegionChunkPos) -> bool {
+        self.dirty.contains(&pos.index())
+    }
+
+    pub fn dirty_chunks(&self) -> Vec<InRegionChunkPos> {
+        self.dirty
+            .iter()
+            .map(|index| InRegionChunkPos::from_index(*index))
+            .collect()
+    }
+
+    pub fn mark_clean(&mut self, pos: InRegionChunkPos) {
+        self.dirty.remove(&pos.index());
+    }
+
     pub fn chunks(&self) -> &[Option<VoxelContainer>] {
         self.chunks.as_slice()
     }
 
-    #[allow(unused)]
     pub fn chunks_mut(&mut self) -> &mut [Option<VoxelContainer>] {
         self.chunks.as_mut_slice()
     }
@@ -1,46 +1,212 @@
 use super::VoxelRegion;
 use crate::{
-    io::read_region_from_file,
+    io::{open_or_create_region_file, open_region_file, RegionFile, SaveError},
     plugin::voxel_world::beef::FixedChunkWorld,
-    voxel::{ChunkPos, InRegionChunkPos, RegionPos, VoxelContainer},
+    voxel::{ChunkPos, InRegionChunkPos, PendingWrite, PendingWrites, RegionPos, VoxelContainer},
 };
-use bevy::utils::{hashbrown::hash_map::Iter, HashMap};
+use bevy::utils::{hashbrown::hash_map::Iter, HashMap, HashSet};
+use priority_queue::PriorityQueue;
+use std::{cmp::Reverse, fs::File};
+
+/// Most region files kept open at once. Chunk loaders only ever need a few
+/// regions at a time, so this is plenty without running out of file
+/// descriptors when flying across the world.
+const MAX_OPEN_REGION_FILES: usize = 32;
+
+/// Open region files, so each chunk read doesn't reread the offset table.
+/// Once there are too many, the least recently used file is closed.
+struct OpenRegionFiles<F> {
+    files: HashMap<RegionPos, RegionFile<F>>,
+    /// The file used longest ago has the highest priority, so it's the one
+    /// popped when there are too many open.
+    last_used: PriorityQueue<RegionPos, Reverse<u64>>,
+    tick: u64,
+}
+
+impl<F> Default for OpenRegionFiles<F> {
+    fn default() -> Self {
+        Self {
+            files: HashMap::default(),
+            last_used: PriorityQueue::new(),
+            tick: 0,
+        }
+    }
+}
+
+impl<F> OpenRegionFiles<F> {
+    /// Get the region's file, calling `open` if it isn't open yet. Returns
+    /// `Ok(None)` if `open` doesn't find a file.
+    fn get_or_open<E>(
+        &mut self,
+        region_pos: RegionPos,
+        open: impl FnOnce() -> Result<Option<RegionFile<F>>, E>,
+    ) -> Result<Option<&mut RegionFile<F>>, E> {
+        if !self.files.contains_key(&region_pos) {
+            let Some(region_file) = open()? else {
+                return Ok(None);
+            };
+            self.files.insert(region_pos, region_file);
+        }
+        self.tick += 1;
+        self.last_used.push(region_pos, Reverse(self.tick));
+
+        while self.files.len() > MAX_OPEN_REGION_FILES {
+            let Some((oldest, _)) = self.last_used.pop() else {
+                break;
+            };
+            self.files.remove(&oldest);
+        }
+        Ok(self.files.get_mut(&region_pos))
+    }
+}
 
 #[derive(Default)]
 pub struct RegionHandler {
+    /// Chunks that have been loaded from or are waiting to be written to
+    /// disk. A missing chunk just hasn't been read yet.
     regions: HashMap<RegionPos, VoxelRegion>,
+    files: OpenRegionFiles<File>,
+    /// Regions with files on disk that we failed to read. These are never
+    /// written back so the player's edits aren't replaced with freshly
+    /// generated chunks.
+    unreadable_regions: HashSet<RegionPos>,
+    /// Decoration voxels waiting for their chunks to be generated or loaded.
+    pending_writes: PendingWrites,
+    /// Whether `pending_writes` changed since it was last saved.
+    pending_writes_dirty: bool,
 }
 
 impl RegionHandler {
+    /// Reads the chunk from its region file if it isn't loaded yet. If the
+    /// region file can't be read, the region is marked as unreadable (and is
+    /// never saved) and the error is returned once; later calls for the same
+    /// region return `Ok(None)`.
     pub fn check_for_chunk(
         &mut self,
         world_name: &str,
         chunk_pos: ChunkPos,
-    ) -> Option<&VoxelContainer> {
+    ) -> Result<Option<&VoxelContainer>, SaveError> {
         let region_pos = chunk_pos.into();
-        if self.region(region_pos).is_none() {
-            let r = self.region_mut(region_pos);
-            if let Some(region) = read_region_from_file(world_name, region_pos) {
-                *r = region;
+        if self.chunk(chunk_pos).is_none() && !self.is_unreadable(region_pos) {
+            let region_file = match self
+                .files
+                .get_or_open(region_pos, || open_region_file(world_name, region_pos))
+            {
+                Ok(region_file) => region_file,
+                Err(err) => {
+                    self.unreadable_regions.insert(region_pos);
+                    return Err(SaveError::ReadRegion(region_pos, err));
+                }
+            };
+
+            if let Some(region_file) = region_file {
+                match region_file.read_chunk(InRegionChunkPos::from_world(chunk_pos)) {
+                    Ok(chunk) => *self.chunk_mut(chunk_pos) = chunk,
+                    Err(err) => {
+                        self.unreadable_regions.insert(region_pos);
+                        return Err(SaveError::ReadRegion(region_pos, err));
+                    }
+                }
             }
         }
 
-        self.chunk(chunk_pos)
+        Ok(self.chunk(chunk_pos))
+    }
+
+    /// Copy the voxels of every chunk that was modified since the last save
+    /// into its region, to be written by [`Self::write_region`].
+    pub fn extract_chunks(&mut self, chunk_world: &mut FixedChunkWorld) {
+        for (pos, loaded_chunk) in chunk_world.chunks.iter_mut() {
+            if let Some(chunk) = loaded_chunk.take_modified() {
+                self.set_modified_chunk(*pos, chunk.voxels.clone());
+            }
+        }
+    }
+
+    pub fn set_modified_chunk(&mut self, chunk_pos: ChunkPos, chunk: VoxelContainer) {
+        self.region_mut(chunk_pos.into())
+            .set_modified_chunk(InRegionChunkPos::from_world(chunk_pos), chunk);
     }
 
-    pub fn extract_chunks(&mut self, chunk_world: &FixedChunkWorld) {
-        for (pos, loaded_chunk) in chunk_world.chunks.iter() {
-            if let Some(chunk) = &loaded_chunk.chunk {
-                *self.chunk_mut(*pos) = Some(chunk.voxels.clone());
+    /// Drop a chunk from memory if it doesn't need to be saved. It will be
+    /// read from disk again if it's needed.
+    pub fn unload_chunk(&mut self, chunk_pos: ChunkPos) {
+        let pos = InRegionChunkPos::from_world(chunk_pos);
+        if let Some(region) = self.get_region_mut(chunk_pos.into()) {
+            if !region.is_chunk_dirty(pos) {
+                *region.chunk_mut(pos) = None;
             }
         }
     }
 
+    /// Write the region's modified chunks to its file. Chunks that fail to
+    /// write stay dirty so they're retried on the next save.
+    pub fn write_region(
+        &mut self,
+        world_name: &str,
+        region_pos: RegionPos,
+    ) -> Result<(), SaveError> {
+        let Some(region) = self.regions.get_mut(&region_pos) else {
+            return Ok(());
+        };
+        if !region.is_dirty() {
+            return Ok(());
+        }
+        let Some(region_file) = self.files.get_or_open(region_pos, || {
+            open_or_create_region_file(world_name, region_pos).map(Some)
+        })?
+        else {
+            return Ok(());
+        };
+
+        for pos in region.dirty_chunks() {
+            region_file
+                .write_chunk(pos, region.chunk(pos))
+                .map_err(|err| SaveError::WriteRegion(region_pos, err))?;
+            region.mark_clean(pos);
+        }
+        Ok(())
+    }
+
+    /// Replace the queue of pending writes, when it's loaded from disk.
+    pub fn set_pending_writes(&mut self, pending_writes: PendingWrites) {
+        self.pending_writes = pending_writes;
+        self.pending_writes_dirty = false;
+    }
+
+    pub fn add_pending_writes(&mut self, chunk_pos: ChunkPos, writes: Vec<PendingWrite>) {
+        self.pending_writes.extend(chunk_pos, writes);
+        self.pending_writes_dirty = true;
+    }
+
+    /// Remove the writes waiting for this chunk, so they can be applied to
+    /// it.
+    pub fn take_pending_writes(&mut self, chunk_pos: ChunkPos) -> Option<Vec<PendingWrite>> {
+        let writes = self.pending_writes.take(chunk_pos);
+        self.pending_writes_dirty |= writes.is_some();
+        writes
+    }
+
+    /// The queue of pending writes, if it changed since it was last saved.
+    pub fn dirty_pending_writes(&self) -> Option<&PendingWrites> {
+        match self.pending_writes_dirty {
+            true => Some(&self.pending_writes),
+            false => None,
+        }
+    }
+
+    pub fn mark_pending_writes_clean(&mut self) {
+        self.pending_writes_dirty = false;
+    }
+
+    pub fn is_unreadable(&self, region_pos: RegionPos) -> bool {
+        self.unreadable_regions.contains(&region_pos)
+    }
+
     pub fn region(&self, region_pos: RegionPos) -> Option<&VoxelRegion> {
         self.regions.get(&region_pos)
     }
 
-    #[allow(unused)]
     pub fn get_region_mut(&mut self, region_pos: RegionPos) -> Option<&mut VoxelRegion> {
         self.regions.get_mut(&region_pos)
     }
@@ -49,10 +215,20 @@ impl RegionHandler {
         self.regions.entry(region_pos).or_default()
     }
 
+    #[allow(unused)]
     pub fn regions(&self) -> Iter<'_, RegionPos, VoxelRegion> {
         self.regions.iter()
     }
 
+    /// Positions of the regions with chunks that need to be saved.
+    pub fn dirty_region_positions(&self) -> Vec<RegionPos> {
+        self.regions
+            .iter()
+            .filter(|(_, region)| region.is_dirty())
+            .map(|(pos, _)| *pos)
+            .collect()
+    }
+
     pub fn chunk(&self, chunk_pos: ChunkPos) -> Option<&VoxelContainer> {
         let region_pos = chunk_pos.into();
         self.region(region_pos)
@@ -73,3 +249,40 @@ impl RegionHandler {
             .chunk_mut(InRegionChunkPos::from_world(chunk_pos))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::math::IVec3;
+    use std::{convert::Infallible, io::Cursor};
+
+    fn open_empty() -> Result<Option<RegionFile<Cursor<Vec<u8>>>>, Infallible> {
+        Ok(Some(RegionFile::create(Cursor::new(vec![])).unwrap()))
+    }
+
+    #[test]
+    fn closes_least_recently_used_files() {
+        let mut files = OpenRegionFiles::default();
+        let region = |x: i32| RegionPos(IVec3::new(x, 0, 0));
+        for x in 0..MAX_OPEN_REGION_FILES as i32 {
+            files.get_or_open(region(x), open_empty).unwrap();
+        }
+        // Use the first one again so the second is now the oldest
+        files.get_or_open(region(0), open_empty).unwrap();
+        files.get_or_open(region(-1), open_empty).unwrap().unwrap();
+
+        assert_eq!(files.files.len(), MAX_OPEN_REGION_FILES);
+        assert!(files.files.contains_key(&region(0)));
+        assert!(!files.files.contains_key(&region(1)));
+        assert!(files.files.contains_key(&region(-1)));
+    }
+
+    #[test]
+    fn missing_files_are_not_cached() {
+        let mut files = OpenRegionFiles::<Cursor<Vec<u8>>>::default();
+        let region = RegionPos(IVec3::ZERO);
+        let missing = files.get_or_open(region, || Ok::<_, Infallible>(None));
+        assert!(missing.unwrap().is_none());
+        assert!(files.files.is_empty());
+    }
+}
@@ -0,0 +1,219 @@
+use crate::voxel::{MeshLayer, Voxel, VoxelAxis, MAX_LIGHT};
+use bevy::{prelude::*, utils::HashMap};
+use serde::Deserialize;
+use std::sync::Arc;
+use thiserror::Error;
+
+/// The atlas tiles used by each face of a voxel.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Deserialize)]
+pub enum VoxelTextures {
+    /// The same atlas index on every face.
+    All(u32),
+    /// Separate atlas indices for the top, sides, and bottom.
+    Faces { top: u32, side: u32, bottom: u32 },
+}
+
+impl Default for VoxelTextures {
+    fn default() -> Self {
+        Self::All(0)
+    }
+}
+
+impl VoxelTextures {
+    /// Get the atlas index for the face pointing along the provided normal.
+    pub fn atlas_index(&self, face_normal: VoxelAxis) -> u32 {
+        match *self {
+            Self::All(index) => index,
+            Self::Faces { top, side, bottom } => match face_normal {
+                VoxelAxis::PosY => top,
+                VoxelAxis::NegY => bottom,
+                _ => side,
+            },
+        }
+    }
+}
+
+fn default_solid() -> bool {
+    true
+}
+
+fn default_hardness() -> f32 {
+    1.0
+}
+
+/// A single voxel type, as defined in the voxel registry file.
+#[derive(Debug, Clone, Deserialize)]
+pub struct VoxelDefinition {
+    pub id: u16,
+    pub name: String,
+    #[serde(default = "default_solid")]
+    pub solid: bool,
+    /// Doesn't hide the faces behind it. Solid transparent voxels, like
+    /// glass, are drawn in the chunk's cutout mesh.
+    #[serde(default)]
+    pub transparent: bool,
+    /// Drawn in the chunk's translucent mesh so it can be blended with
+    /// whatever is behind it, and left out of the collider.
+    #[serde(default)]
+    pub translucent: bool,
+    /// Negative hardness means the voxel can't be broken.
+    #[serde(default = "default_hardness")]
+    pub hardness: f32,
+    /// Block light given off, from 0 up to [`MAX_LIGHT`].
+    #[serde(default)]
+    pub light: u8,
+    #[serde(default)]
+    pub textures: VoxelTextures,
+}
+
+impl VoxelDefinition {
+    pub fn does_cull_as_solid(&self) -> bool {
+        self.solid && !self.transparent
+    }
+
+    pub fn is_breakable(&self) -> bool {
+        self.hardness >= 0.0
+    }
+}
+
+/// The format of the RON file the registry is loaded from.
+#[derive(Debug, Deserialize)]
+pub struct VoxelRegistryFile {
+    pub voxels: Vec<VoxelDefinition>,
+}
+
+#[derive(Debug, Error)]
+pub enum VoxelRegistryError {
+    #[error("voxel id 0 must be defined as a non-solid voxel (air)")]
+    InvalidAir,
+    #[error("voxel id {0} is defined more than once")]
+    DuplicateId(u16),
+    #[error("voxel name \"{0}\" is defined more than once")]
+    DuplicateName(String),
+    #[error(
+        "voxel \"{0}\" gives off light {1}, but the brightest light is {max}",
+        max = MAX_LIGHT
+    )]
+    TooBright(String, u8),
+}
+
+#[derive(Debug)]
+struct VoxelRegistryInner {
+    definitions: Vec<Option<VoxelDefinition>>,
+    names: HashMap<String, Voxel>,
+}
+
+/// All the voxel types that can exist in the world, indexed by their
+/// [`Voxel`] ID. Cheap to clone, so it can be sent to generation and
+/// meshing tasks.
+#[derive(Debug, Clone, Asset, Resource, TypePath)]
+pub struct VoxelRegistry(Arc<VoxelRegistryInner>);
+
+impl VoxelRegistry {
+    pub fn new(voxels: Vec<VoxelDefinition>) -> Result<Self, VoxelRegistryError> {
+        let max_id = voxels.iter().map(|def| def.id).max().unwrap_or(0);
+        let mut definitions = vec![None; max_id as usize + 1];
+        let mut names = HashMap::with_capacity(voxels.len());
+
+        for definition in voxels {
+            let id = definition.id;
+            if definition.light > MAX_LIGHT {
+                return Err(VoxelRegistryError::TooBright(
+                    definition.name,
+                    definition.light,
+                ));
+            }
+            if names.insert(definition.name.clone(), Voxel(id)).is_some() {
+                return Err(VoxelRegistryError::DuplicateName(definition.name));
+            }
+            let slot = &mut definitions[id as usize];
+            if slot.is_some() {
+                return Err(VoxelRegistryError::DuplicateId(id));
+            }
+            *slot = Some(definition);
+        }
+
+        match &definitions[Voxel::AIR.0 as usize] {
+            Some(air) if !air.solid => {
+                Ok(Self(Arc::new(VoxelRegistryInner { definitions, names })))
+            }
+            _ => Err(VoxelRegistryError::InvalidAir),
+        }
+    }
+
+    /// The registry the game ships with, for tests and tools that don't go
+    /// through the asset server.
+    pub fn builtin() -> Self {
+        Self::builtin_with("(voxels: [])")
+    }
+
+    /// [`Self::builtin`] plus the voxels in `extra`, a registry file of its
+    /// own.
+    pub fn builtin_with(extra: &str) -> Self {
+        let mut file: VoxelRegistryFile =
+            ron::de::from_str(include_str!("../../assets/data/voxels.registry.ron"))
+                .expect("builtin voxel registry should parse");
+        let extra: VoxelRegistryFile = ron::de::from_str(extra).expect("extra voxels should parse");
+        file.voxels.extend(extra.voxels);
+        Self::new(file.voxels).expect("builtin voxel registry should be valid")
+    }
+
+    pub fn get(&self, voxel: Voxel) -> Option<&VoxelDefinition> {
+        self.0
+            .definitions
+            .get(voxel.0 as usize)
+            .and_then(Option::as_ref)
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<Voxel> {
+        self.0.names.get(name).copied()
+    }
+
+    pub fn name(&self, voxel: Voxel) -> &str {
+        self.get(voxel)
+            .map(|def| def.name.as_str())
+            .unwrap_or("unknown")
+    }
+
+    /// Unknown voxels don't cull their neighbors.
+    pub fn does_cull_as_solid(&self, voxel: Voxel) -> bool {
+        self.get(voxel)
+            .map(VoxelDefinition::does_cull_as_solid)
+            .unwrap_or(false)
+    }
+
+    /// Light spreads through anything that doesn't cull as solid, including
+    /// unknown voxels.
+    pub fn passes_light(&self, voxel: Voxel) -> bool {
+        !self.does_cull_as_solid(voxel)
+    }
+
+    pub fn light_emission(&self, voxel: Voxel) -> u8 {
+        self.get(voxel).map(|def| def.light).unwrap_or(0)
+    }
+
+    /// Whether things bump into the voxel, rather than passing through it
+    /// like water.
+    pub fn is_solid(&self, voxel: Voxel) -> bool {
+        self.get(voxel).map(|def| def.solid).unwrap_or(false)
+    }
+
+    pub fn is_translucent(&self, voxel: Voxel) -> bool {
+        self.get(voxel).map(|def| def.translucent).unwrap_or(false)
+    }
+
+    /// Which of a chunk's meshes the voxel is drawn in.
+    pub fn mesh_layer(&self, voxel: Voxel) -> MeshLayer {
+        match self.get(voxel) {
+            Some(def) if def.translucent => MeshLayer::Translucent,
+            Some(def) if def.solid && def.transparent => MeshLayer::Cutout,
+            _ => MeshLayer::Opaque,
+        }
+    }
+
+    pub fn atlas_index(&self, voxel: Voxel, face_normal: VoxelAxis) -> u32 {
+        self.get(voxel)
+            .map(|def| def.textures.atlas_index(face_normal))
+            .unwrap_or(0)
+    }
+}
@@ -1,25 +1,14 @@
 use serde::{Deserialize, Serialize};
 
+/// A voxel type, identified by its ID in the
+/// [`VoxelRegistry`](crate::voxel::VoxelRegistry). ID 0 is always air.
 #[derive(Default, Debug, Copy, Clone, Eq, PartialEq, Hash, Serialize, Deserialize)]
-pub enum Voxel {
-    #[default]
-    Air,
-    Stone,
-    Grass,
-    Dirt,
-}
+pub struct Voxel(pub u16);
 
 impl Voxel {
-    pub fn does_cull_as_solid(&self) -> bool {
-        *self != Voxel::Air
-    }
+    pub const AIR: Self = Self(0);
 
-    pub fn atlas_index(&self) -> u32 {
-        match *self {
-            Voxel::Air => 0,
-            Voxel::Stone => 1,
-            Voxel::Grass => 0,
-            Voxel::Dirt => 2,
-        }
+    pub fn is_air(&self) -> bool {
+        *self == Self::AIR
     }
 }
@@ -0,0 +1,436 @@
+use super::{
+    light_chunk,
+    world_noise::{Chunk2dNoiseValues, WorldNoiseSettings},
+    BiomeTable, Chunk, ChunkPos, InChunkPos, PendingWrites, Voxel, VoxelRegistry, WorldGenConfig,
+    CHUNK_WIDTH,
+};
+use bevy::prelude::*;
+use itertools::iproduct;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Something that can fill chunks with voxels. Generators are shared
+/// between the generation tasks, so they can't hold any mutable state.
+pub trait WorldGenerator: Send + Sync {
+    /// Values shared by every chunk in a column, like a heightmap. They're
+    /// cached by the chunk world and handed to every chunk in the column, so
+    /// expensive 2D noise only gets sampled once. Generators that don't need
+    /// any can leave this as `None`.
+    fn column_noise(&self, _column: IVec2) -> Option<Chunk2dNoiseValues> {
+        None
+    }
+
+    /// Fill in the chunk at `pos`. `column` is whatever [`Self::column_noise`]
+    /// returned for the chunk's column.
+    fn generate_chunk(&self, pos: ChunkPos, column: Option<&Chunk2dNoiseValues>) -> Chunk;
+
+    /// Add extra bits like trees to a freshly generated chunk. Anything that
+    /// doesn't fit in the chunk gets returned so it can be written into its
+    /// neighbours once they're loaded.
+    fn decorate_chunk(
+        &self,
+        _chunk: &mut Chunk,
+        _pos: ChunkPos,
+        _column: Option<&Chunk2dNoiseValues>,
+    ) -> PendingWrites {
+        PendingWrites::default()
+    }
+
+    /// Whether nothing generates above this chunk, so it can be lit by the
+    /// sky before the chunk above it is loaded. The default suits generators
+    /// that keep everything below Y 0.
+    fn open_sky_above(&self, pos: ChunkPos, _column: Option<&Chunk2dNoiseValues>) -> bool {
+        (pos.0.y + 1) * CHUNK_WIDTH as i32 >= 0
+    }
+
+    /// The world Y of the ground in the middle of the origin chunk, where new
+    /// players spawn. `None` means it isn't known yet (e.g. the column noise
+    /// for the origin hasn't been generated).
+    fn spawn_height(&self, column: Option<&Chunk2dNoiseValues>) -> Option<i32>;
+}
+
+/// Generate and decorate a chunk, and get it ready to be added to the world.
+pub fn generate_chunk_with(
+    generator: &dyn WorldGenerator,
+    pos: ChunkPos,
+    column: Option<&Chunk2dNoiseValues>,
+    registry: &VoxelRegistry,
+) -> (Chunk, PendingWrites) {
+    let mut chunk = generator.generate_chunk(pos, column);
+    let outside_writes = generator.decorate_chunk(&mut chunk, pos, column);
+
+    // Fresh chunks haven't been saved yet, but there's no need to save
+    // empty ones since they'll just generate as air again.
+    chunk.modified = !chunk.definitely_empty;
+    chunk.update_edge_slice_bits(registry);
+    light_chunk(&mut chunk, registry, generator.open_sky_above(pos, column));
+    (chunk, outside_writes)
+}
+
+/// The generator for the world that's currently loaded.
+#[derive(Resource, Clone)]
+pub struct WorldGeneratorRes(pub Arc<dyn WorldGenerator>);
+
+/// One layer of a superflat world.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlatLayer {
+    pub block: String,
+    pub thickness: u32,
+}
+
+impl FlatLayer {
+    pub fn new(block: &str, thickness: u32) -> Self {
+        Self {
+            block: block.to_string(),
+            thickness,
+        }
+    }
+}
+
+/// Which generator a world uses. This is what gets saved with the world,
+/// the actual generator is built from it when the world is loaded.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum GeneratorPreset {
+    /// The normal noise terrain, with biomes, caves and trees. It's shaped
+    /// by the world's [`WorldGenConfig`].
+    #[default]
+    Noise,
+    /// Flat layers, listed from the bottom up. The top of the last layer is
+    /// at Y 0.
+    Superflat { layers: Vec<FlatLayer> },
+    /// Nothing but a little platform to spawn on.
+    Void,
+    /// Alternating voxels below Y 0, flipped every other chunk so chunk
+    /// borders are easy to spot. Handy for debugging meshing.
+    Checkerboard,
+}
+
+impl GeneratorPreset {
+    /// One of each preset, in the order the new world menu cycles through them.
+    pub fn all() -> Vec<Self> {
+        vec![
+            Self::Noise,
+            Self::default_superflat(),
+            Self::Void,
+            Self::Checkerboard,
+        ]
+    }
+
+    pub fn default_superflat() -> Self {
+        Self::Superflat {
+            layers: vec![
+                FlatLayer::new("bedrock", 1),
+                FlatLayer::new("stone", 3),
+                FlatLayer::new("dirt", 2),
+                FlatLayer::new("grass", 1),
+            ],
+        }
+    }
+
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            Self::Noise => "Default",
+            Self::Superflat { .. } => "Superflat",
+            Self::Void => "Void",
+            Self::Checkerboard => "Checkerboard",
+        }
+    }
+
+    pub fn build(
+        &self,
+        seed: u32,
+        config: &WorldGenConfig,
+        biome_table: &BiomeTable,
+        registry: &VoxelRegistry,
+    ) -> Arc<dyn WorldGenerator> {
+        match self {
+            Self::Noise => Arc::new(WorldNoiseSettings::new(
+                seed,
+                config,
+                biome_table.clone(),
+                registry,
+            )),
+            Self::Superflat { layers } => Arc::new(SuperflatGenerator::new(layers, registry)),
+            Self::Void => Arc::new(VoidGenerator::new(registry)),
+            Self::Checkerboard => Arc::new(CheckerboardGenerator::new(registry)),
+        }
+    }
+}
+
+/// Look up a voxel a preset wants, falling back to stone so a typo doesn't
+/// take the whole game down.
+fn preset_voxel(registry: &VoxelRegistry, name: &str) -> Voxel {
+    registry.by_name(name).unwrap_or_else(|| {
+        warn!("world generator uses unknown voxel \"{name}\", using stone instead");
+        registry.by_name("stone").unwrap_or_default()
+    })
+}
+
+fn empty_chunk() -> Chunk {
+    Chunk {
+        definitely_empty: true,
+        ..default()
+    }
+}
+
+/// Set the voxels of every in-chunk position `voxel_at` returns something
+/// for. `voxel_at` gets the world position of the voxel.
+fn fill_chunk(pos: ChunkPos, voxel_at: impl Fn(IVec3) -> Option<Voxel>) -> Chunk {
+    let mut chunk = empty_chunk();
+    let chunk_origin = pos.0 * CHUNK_WIDTH as i32;
+
+    for (z, y, x) in iproduct!(0..CHUNK_WIDTH, 0..CHUNK_WIDTH, 0..CHUNK_WIDTH) {
+        let in_chunk = UVec3::new(x, y, z);
+        if let Some(voxel) = voxel_at(chunk_origin + in_chunk.as_ivec3()) {
+            chunk.definitely_empty = false;
+            chunk.voxels.set(InChunkPos::new(in_chunk).unwrap(), voxel);
+        }
+    }
+    chunk
+}
+
+/// Flat layers of blocks stacked from the bottom up, ending at Y 0.
+pub struct SuperflatGenerator {
+    /// World Y ranges (exclusive end) of each layer, bottom first.
+    layers: Vec<(i32, i32, Voxel)>,
+}
+
+impl SuperflatGenerator {
+    pub fn new(layers: &[FlatLayer], registry: &VoxelRegistry) -> Self {
+        let total = layers
+            .iter()
+            .map(|layer| layer.thickness as i32)
+            .sum::<i32>();
+        let mut bottom = -total;
+        let layers = layers
+            .iter()
+            .map(|layer| {
+                let top = bottom + layer.thickness as i32;
+                let range = (bottom, top, preset_voxel(registry, &layer.block));
+                bottom = top;
+                range
+            })
+            .collect();
+        Self { layers }
+    }
+}
+
+impl WorldGenerator for SuperflatGenerator {
+    fn generate_chunk(&self, pos: ChunkPos, _column: Option<&Chunk2dNoiseValues>) -> Chunk {
+        let chunk_bottom = pos.0.y * CHUNK_WIDTH as i32;
+        let chunk_top = chunk_bottom + CHUNK_WIDTH as i32;
+        // Skip the per-voxel work for chunks the layers don't reach
+        if !self
+            .layers
+            .iter()
+            .any(|&(bottom, top, _)| bottom < chunk_top && top > chunk_bottom)
+        {
+            return empty_chunk();
+        }
+
+        fill_chunk(pos, |world_pos| {
+            self.layers
+                .iter()
+                .find(|&&(bottom, top, _)| (bottom..top).contains(&world_pos.y))
+                .map(|&(_, _, voxel)| voxel)
+        })
+    }
+
+    fn spawn_height(&self, _column: Option<&Chunk2dNoiseValues>) -> Option<i32> {
+        Some(0)
+    }
+}
+
+/// Half the width of the void world's spawn platform.
+const VOID_PLATFORM_RADIUS: i32 = 2;
+
+/// Empty, apart from a small stone platform under the spawn point.
+pub struct VoidGenerator {
+    platform: Voxel,
+}
+
+impl VoidGenerator {
+    pub fn new(registry: &VoxelRegistry) -> Self {
+        Self {
+            platform: preset_voxel(registry, "stone"),
+        }
+    }
+}
+
+impl WorldGenerator for VoidGenerator {
+    fn generate_chunk(&self, pos: ChunkPos, _column: Option<&Chunk2dNoiseValues>) -> Chunk {
+        // The platform only ever sits in one chunk
+        if pos.0 != IVec3::new(0, -1, 0) {
+            return empty_chunk();
+        }
+
+        let middle = CHUNK_WIDTH as i32 / 2;
+        fill_chunk(pos, |world_pos| {
+            let on_platform = world_pos.y == -1
+                && (world_pos.x - middle).abs() <= VOID_PLATFORM_RADIUS
+                && (world_pos.z - middle).abs() <= VOID_PLATFORM_RADIUS;
+            on_platform.then_some(self.platform)
+        })
+    }
+
+    fn spawn_height(&self, _column: Option<&Chunk2dNoiseValues>) -> Option<i32> {
+        Some(0)
+    }
+}
+
+/// A chunk thick floor of alternating voxels below Y 0.
+pub struct CheckerboardGenerator {
+    blocks: [Voxel; 2],
+}
+
+impl CheckerboardGenerator {
+    pub fn new(registry: &VoxelRegistry) -> Self {
+        Self {
+            blocks: [
+                preset_voxel(registry, "stone"),
+                preset_voxel(registry, "dirt"),
+            ],
+        }
+    }
+}
+
+impl WorldGenerator for CheckerboardGenerator {
+    fn generate_chunk(&self, pos: ChunkPos, _column: Option<&Chunk2dNoiseValues>) -> Chunk {
+        if pos.0.y != -1 {
+            return empty_chunk();
+        }
+
+        // Flip the pattern every other chunk, so the borders stand out
+        let chunk_parity = (pos.0.x + pos.0.z).rem_euclid(2);
+        fill_chunk(pos, |world_pos| {
+            let parity = (world_pos.x + world_pos.y + world_pos.z + chunk_parity).rem_euclid(2);
+            Some(self.blocks[parity as usize])
+        })
+    }
+
+    fn spawn_height(&self, _column: Option<&Chunk2dNoiseValues>) -> Option<i32> {
+        Some(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::{BiomeTableFile, VoxelPos};
+
+    fn build(preset: &GeneratorPreset, registry: &VoxelRegistry) -> Arc<dyn WorldGenerator> {
+        let biomes: BiomeTableFile =
+            ron::de::from_str(include_str!("../../assets/data/overworld.biomes.ron")).unwrap();
+        preset.build(
+            1234,
+            &WorldGenConfig::original(),
+            &BiomeTable::new(biomes.biomes).unwrap(),
+            registry,
+        )
+    }
+
+    /// Generate the chunk the voxel is in and look it up.
+    fn voxel_at(generator: &dyn WorldGenerator, world_pos: IVec3) -> Voxel {
+        let chunk_pos = ChunkPos::from(VoxelPos(world_pos));
+        let column = generator.column_noise(chunk_pos.0.xz());
+        let chunk = generator.generate_chunk(chunk_pos, column.as_ref());
+        let in_chunk = (world_pos - VoxelPos::from(chunk_pos).0).as_uvec3();
+        chunk.at(InChunkPos::new(in_chunk).unwrap())
+    }
+
+    #[test]
+    fn superflat_layers_stack_up_to_zero() {
+        let registry = VoxelRegistry::builtin();
+        let generator = build(
+            &GeneratorPreset::Superflat {
+                layers: vec![
+                    FlatLayer::new("bedrock", 1),
+                    // Thick enough to cross a chunk border
+                    FlatLayer::new("stone", 40),
+                    FlatLayer::new("grass", 1),
+                ],
+            },
+            &registry,
+        );
+
+        for y in -50..10 {
+            let expected = match y {
+                -42 => "bedrock",
+                -41..=-2 => "stone",
+                -1 => "grass",
+                _ => "air",
+            };
+            for (x, z) in [(0, 0), (-17, 40)] {
+                let voxel = voxel_at(generator.as_ref(), IVec3::new(x, y, z));
+                assert_eq!(registry.name(voxel), expected, "at y {y}");
+            }
+        }
+        assert!(
+            generator
+                .generate_chunk(ChunkPos(IVec3::new(0, 3, 0)), None)
+                .definitely_empty
+        );
+    }
+
+    #[test]
+    fn void_is_empty_apart_from_the_platform() {
+        let registry = VoxelRegistry::builtin();
+        let generator = build(&GeneratorPreset::Void, &registry);
+        let stone = registry.by_name("stone").unwrap();
+        let middle = CHUNK_WIDTH as i32 / 2;
+
+        for (x, y, z) in iproduct!(-1..=1, -2..=1, -1..=1) {
+            let pos = ChunkPos(IVec3::new(x, y, z));
+            let chunk = generator.generate_chunk(pos, None);
+            let origin = VoxelPos::from(pos).0;
+            for (vx, vy, vz) in iproduct!(0..CHUNK_WIDTH, 0..CHUNK_WIDTH, 0..CHUNK_WIDTH) {
+                let in_chunk = UVec3::new(vx, vy, vz);
+                let world_pos = origin + in_chunk.as_ivec3();
+                let on_platform = world_pos.y == -1
+                    && (world_pos.x - middle).abs() <= VOID_PLATFORM_RADIUS
+                    && (world_pos.z - middle).abs() <= VOID_PLATFORM_RADIUS;
+                let expected = match on_platform {
+                    true => stone,
+                    false => Voxel::AIR,
+                };
+                assert_eq!(chunk.at(InChunkPos::new(in_chunk).unwrap()), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn checkerboard_alternates_and_flips_between_chunks() {
+        let registry = VoxelRegistry::builtin();
+        let generator = build(&GeneratorPreset::Checkerboard, &registry);
+        let at = |x, y, z| voxel_at(generator.as_ref(), IVec3::new(x, y, z));
+
+        for (x, y, z) in iproduct!(0..CHUNK_WIDTH as i32 - 1, -31..-1, [0, 7]) {
+            assert_ne!(at(x, y, z), at(x + 1, y, z));
+            assert_ne!(at(x, y, z), at(x, y + 1, z));
+        }
+        // Neighbors on either side of a chunk border match instead
+        let border = CHUNK_WIDTH as i32;
+        for (y, z) in iproduct!(-31..0, [0, 7]) {
+            assert_eq!(at(border - 1, y, z), at(border, y, z));
+            assert_ne!(at(border - 1, y, z), Voxel::AIR);
+        }
+        assert_eq!(at(0, 0, 0), Voxel::AIR);
+        assert_eq!(at(0, -32, 0), Voxel::AIR);
+    }
+
+    #[test]
+    fn players_spawn_on_the_ground() {
+        let registry = VoxelRegistry::builtin();
+        let middle = CHUNK_WIDTH as i32 / 2;
+        for preset in GeneratorPreset::all() {
+            let generator = build(&preset, &registry);
+            let column = generator.column_noise(IVec2::ZERO);
+            let height = generator.spawn_height(column.as_ref()).unwrap();
+
+            let ground = voxel_at(generator.as_ref(), IVec3::new(middle, height - 1, middle));
+            let spawn = voxel_at(generator.as_ref(), IVec3::new(middle, height, middle));
+            assert!(registry.is_solid(ground), "{preset:?} spawns in the air");
+            assert!(!registry.is_solid(spawn), "{preset:?} spawns underground");
+        }
+    }
+}
@@ -1,121 +1,579 @@
-use super::{BiomeTable, Chunk, InChunkPos, Voxel, CHUNK_WIDTH};
-use bevy::prelude::*;
-use itertools::iproduct;
-use noise::{
-    utils::{NoiseMapBuilder, PlaneMapBuilder},
-    Add, Constant, Fbm, Min, MultiFractal, Multiply, NoiseFn, Perlin, Power,
+use super::{
+    world_generator::WorldGenerator, BiomeTable, Chunk, ChunkPos, Decorator, InChunkPos,
+    PendingWrites, Voxel, VoxelRegistry, WorldGenConfig, CHUNK_SQUARE, CHUNK_WIDTH,
+    MAX_DECORATION_HEIGHT,
 };
+use bevy::{math::DVec2, prelude::*};
+use itertools::iproduct;
+use noise::{Add, Constant, Fbm, Min, MultiFractal, Multiply, NoiseFn, Perlin, Power};
+use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
 #[derive(Clone)]
 pub struct Chunk2dNoiseValues {
     pub chunk_pos: IVec2,
+    /// Terrain height with the biome height modifiers already applied.
     pub heightmap: Vec<f64>,
     pub temperature: Vec<f64>,
     pub humidity: Vec<f64>,
+    /// Picks between the biomes in the same temperature/humidity cell.
+    pub variant: Vec<f64>,
+    /// Index of each column's biome in the [`BiomeTable`].
+    pub biomes: Vec<usize>,
+}
+
+pub const DEFAULT_SEA_LEVEL: i32 = 0;
+
+/// Everything that generates below the surface. Positions and frequencies
+/// are in voxels, so the same settings work no matter the chunk size.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UndergroundSettings {
+    /// World Y of the unbreakable floor. Nothing generates below it.
+    pub floor_y: i32,
+    pub floor_block: String,
+    pub cheese_frequency: f64,
+    /// Cheese caves are carved wherever their noise is above this, so
+    /// higher means fewer, smaller caves.
+    pub cheese_threshold: f64,
+    /// Cheese caves stay at least this many voxels below the surface, so
+    /// they don't leave the ground full of craters.
+    pub cheese_min_depth: i32,
+    pub spaghetti_frequency: f64,
+    /// Spaghetti caves are carved where two noise fields are both within
+    /// this of zero, which makes long winding tunnels.
+    pub spaghetti_width: f64,
+    /// Spaghetti caves narrow down to nothing over this many voxels below
+    /// the surface, so they don't cut holes in it. 0 lets them run right
+    /// through it.
+    pub spaghetti_surface_fade: i32,
+    /// Ores replace stone, and are checked in order.
+    pub ores: Vec<OreSettings>,
+}
+
+// These are part of `WorldGenConfig::original`, so changing them would
+// change the caves in old worlds.
+impl Default for UndergroundSettings {
+    fn default() -> Self {
+        Self {
+            floor_y: -128,
+            floor_block: "bedrock".to_string(),
+            cheese_frequency: 0.025,
+            cheese_threshold: 0.4,
+            cheese_min_depth: 8,
+            spaghetti_frequency: 0.018,
+            spaghetti_width: 0.045,
+            spaghetti_surface_fade: 0,
+            ores: vec![
+                OreSettings::new("coal_ore", -128, 80, 0.12, 0.55),
+                OreSettings::new("iron_ore", -128, 20, 0.14, 0.62),
+                OreSettings::new("gold_ore", -128, -40, 0.16, 0.7),
+            ],
+        }
+    }
+}
+
+/// Blobs of ore, placed wherever the ore's noise is above `threshold`
+/// between `min_y` and `max_y` (inclusive).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OreSettings {
+    pub block: String,
+    pub min_y: i32,
+    pub max_y: i32,
+    pub frequency: f64,
+    pub threshold: f64,
+}
+
+impl OreSettings {
+    pub fn new(block: &str, min_y: i32, max_y: i32, frequency: f64, threshold: f64) -> Self {
+        Self {
+            block: block.to_string(),
+            min_y,
+            max_y,
+            frequency,
+            threshold,
+        }
+    }
+}
+
+struct Ore {
+    voxel: Voxel,
+    min_y: i32,
+    max_y: i32,
+    threshold: f64,
+    noise: Box<dyn NoiseFn<f64, 3> + Send + Sync>,
+}
+
+/// The noise for [`UndergroundSettings`], built once per world.
+struct Underground {
+    settings: UndergroundSettings,
+    floor: Voxel,
+    cheese_noise: Box<dyn NoiseFn<f64, 3> + Send + Sync>,
+    spaghetti_noise: [Box<dyn NoiseFn<f64, 3> + Send + Sync>; 2],
+    ores: Vec<Ore>,
+}
+
+impl Underground {
+    fn new(seed: u32, settings: UndergroundSettings, registry: &VoxelRegistry) -> Self {
+        let voxel = |name: &str| {
+            registry.by_name(name).unwrap_or_else(|| {
+                warn!("underground generation uses unknown voxel \"{name}\", using stone instead");
+                registry.by_name("stone").unwrap_or_default()
+            })
+        };
+        let spaghetti = |salt: u32| -> Box<dyn NoiseFn<f64, 3> + Send + Sync> {
+            Box::new(
+                Fbm::<Perlin>::new(sub_seed(seed, salt))
+                    .set_octaves(2)
+                    .set_frequency(settings.spaghetti_frequency),
+            )
+        };
+
+        Self {
+            floor: voxel(&settings.floor_block),
+            cheese_noise: Box::new(
+                Fbm::<Perlin>::new(sub_seed(seed, 1))
+                    .set_octaves(2)
+                    .set_frequency(settings.cheese_frequency),
+            ),
+            spaghetti_noise: [spaghetti(2), spaghetti(3)],
+            ores: settings
+                .ores
+                .iter()
+                .enumerate()
+                .map(|(i, ore)| Ore {
+                    voxel: voxel(&ore.block),
+                    min_y: ore.min_y,
+                    max_y: ore.max_y,
+                    threshold: ore.threshold,
+                    noise: Box::new(
+                        Fbm::<Perlin>::new(sub_seed(seed, 100 + i as u32))
+                            .set_octaves(1)
+                            .set_frequency(ore.frequency),
+                    ),
+                })
+                .collect(),
+            settings,
+        }
+    }
+
+    /// Whether a cave should be carved out at this position. `depth` is how
+    /// far below the surface it is.
+    fn is_cave(&self, pos: [f64; 3], depth: i32) -> bool {
+        if depth >= self.settings.cheese_min_depth
+            && self.cheese_noise.get(pos) > self.settings.cheese_threshold
+        {
+            return true;
+        }
+        let fade = self.settings.spaghetti_surface_fade;
+        let width = match fade > 0 {
+            true => self.settings.spaghetti_width * (depth as f64 / fade as f64).clamp(0.0, 1.0),
+            false => self.settings.spaghetti_width,
+        };
+        self.spaghetti_noise
+            .iter()
+            .all(|noise| noise.get(pos).abs() < width)
+    }
+
+    fn ore_at(&self, pos: [f64; 3], world_y: i32) -> Option<Voxel> {
+        self.ores
+            .iter()
+            .filter(|ore| (ore.min_y..=ore.max_y).contains(&world_y))
+            .find(|ore| ore.noise.get(pos) > ore.threshold)
+            .map(|ore| ore.voxel)
+    }
 }
 
-#[derive(Resource, Clone)]
+/// Noise functions each need their own seed, otherwise they'd all line up.
+fn sub_seed(seed: u32, salt: u32) -> u32 {
+    seed.wrapping_mul(34857923) ^ salt.wrapping_mul(2654435761)
+}
+
+/// A biome's blocks, looked up in the registry ahead of time.
+#[derive(Copy, Clone)]
+struct BiomeBlocks {
+    surface: Voxel,
+    filler: Voxel,
+    filler_depth: u32,
+}
+
+#[derive(Clone)]
 pub struct WorldNoiseSettings {
     heightmap_noise: Arc<dyn NoiseFn<f64, 2> + Send + Sync>,
     temperature_noise: Arc<dyn NoiseFn<f64, 2> + Send + Sync>,
     humidity_noise: Arc<dyn NoiseFn<f64, 2> + Send + Sync>,
-    #[allow(unused)]
+    variant_noise: Arc<dyn NoiseFn<f64, 2> + Send + Sync>,
+    /// Voxels per unit of the 2D noise above.
+    surface_scale: f64,
     biome_table: BiomeTable,
+    /// Indexed the same as the biomes in `biome_table`.
+    biome_blocks: Arc<[BiomeBlocks]>,
+    underground: Arc<Underground>,
+    decorator: Decorator,
+    /// Open air below this world Y is filled with water.
+    sea_level: i32,
+    stone: Voxel,
+    water: Voxel,
 }
 
 impl WorldNoiseSettings {
-    pub fn new(seed: u32, biome_table: BiomeTable) -> Self {
+    pub fn new(
+        seed: u32,
+        config: &WorldGenConfig,
+        biome_table: BiomeTable,
+        registry: &VoxelRegistry,
+    ) -> Self {
         let offset_seed = seed.wrapping_mul(34857923) ^ 487529837;
+        let height = &config.heightmap;
+        let voxel = |name: &str| {
+            registry
+                .by_name(name)
+                .unwrap_or_else(|| panic!("voxel registry is missing \"{name}\""))
+        };
+        let (stone, water) = (voxel("stone"), voxel("water"));
+        let biome_blocks = biome_table
+            .biomes()
+            .iter()
+            .map(|biome| {
+                // Don't take the whole game down over a typo in a biome
+                let block = |name: &str| {
+                    registry.by_name(name).unwrap_or_else(|| {
+                        warn!(
+                            "biome \"{}\" uses unknown voxel \"{name}\", using stone instead",
+                            biome.name()
+                        );
+                        stone
+                    })
+                };
+                BiomeBlocks {
+                    surface: block(biome.surface_block()),
+                    filler: block(biome.filler_block()),
+                    filler_depth: biome.filler_depth(),
+                }
+            })
+            .collect();
 
         Self {
             heightmap_noise: Arc::new(Add::new(
-                Constant::new(10.0),
+                Constant::new(height.base_height),
                 Add::new(
                     Multiply::new(
-                        Constant::new(90.0),
-                        Fbm::<Perlin>::new(seed)
-                            .set_frequency(0.02)
-                            .set_persistence(0.6),
+                        Constant::new(height.hills_amplitude),
+                        height.hills.build(seed),
                     ),
                     Multiply::new(
-                        Constant::new(300.0),
+                        Constant::new(height.ridges_amplitude),
                         Min::new(
                             Power::new(
-                                Fbm::<Perlin>::new(seed).set_frequency(0.015).set_octaves(1),
-                                Constant::new(2.0),
+                                height.ridges.build(seed),
+                                Constant::new(height.ridges_power),
                             ),
                             Power::new(
-                                Fbm::<Perlin>::new(offset_seed)
-                                    .set_frequency(0.015)
-                                    .set_octaves(1),
-                                Constant::new(2.0),
+                                height.ridges.build(offset_seed),
+                                Constant::new(height.ridges_power),
                             ),
                         ),
                     ),
                 ),
             )),
-            temperature_noise: Arc::new(
-                Fbm::<Perlin>::new(seed).set_octaves(2).set_frequency(0.003),
-            ),
-            humidity_noise: Arc::new(
-                Fbm::<Perlin>::new(offset_seed)
-                    .set_octaves(2)
-                    .set_frequency(0.014),
-            ),
+            temperature_noise: Arc::new(config.temperature.build(seed)),
+            humidity_noise: Arc::new(config.humidity.build(offset_seed)),
+            variant_noise: Arc::new(config.variant.build(seed ^ 0x5eed_b10e)),
+            surface_scale: config.surface_scale,
             biome_table,
+            biome_blocks,
+            underground: Arc::new(Underground::new(seed, config.underground.clone(), registry)),
+            decorator: Decorator::new(seed, registry),
+            sea_level: config.sea_level,
+            stone,
+            water,
         }
     }
 
+    /// Sample the noise once per voxel column in the chunk, so neighboring
+    /// chunks carry on exactly where this one stops. `scale` is how many
+    /// voxels one unit of noise covers.
     pub fn chunk_2d_noise_fn(
         noise_fn: &(impl NoiseFn<f64, 2> + ?Sized),
         chunk_pos: IVec2,
+        scale: f64,
     ) -> Vec<f64> {
-        let chunk_pos = chunk_pos.as_dvec2();
-        PlaneMapBuilder::<_, 2>::new(noise_fn)
-            .set_size(CHUNK_WIDTH as usize, CHUNK_WIDTH as usize)
-            .set_x_bounds(chunk_pos.x, chunk_pos.x + 1.0)
-            .set_y_bounds(chunk_pos.y, chunk_pos.y + 1.0)
-            .build()
-            .into_iter()
-            .collect::<Vec<_>>()
+        iproduct!(0..CHUNK_WIDTH as i32, 0..CHUNK_WIDTH as i32)
+            .map(|(z, x)| {
+                noise_fn.get(Self::noise_pos(chunk_pos, IVec2::new(x, z), scale).to_array())
+            })
+            .collect()
+    }
+
+    /// Where a column of the chunk samples the noise. Columns past the edge
+    /// of the chunk land on the neighbor's columns. This steps from the
+    /// chunk's corner the same way the `PlaneMapBuilder` chunks used to be
+    /// sampled with does, so with a scale of [`CHUNK_WIDTH`] the noise comes
+    /// out exactly as it did before.
+    fn noise_pos(chunk_pos: IVec2, column: IVec2, scale: f64) -> DVec2 {
+        let units_per_chunk = CHUNK_WIDTH as f64 / scale;
+        chunk_pos.as_dvec2() * units_per_chunk + column.as_dvec2() * (1.0 / scale)
     }
 
     pub fn generate_chunk_2d_noise(&self, chunk_pos: IVec2) -> Chunk2dNoiseValues {
+        let sample = |noise_fn: &(dyn NoiseFn<f64, 2> + Send + Sync)| {
+            Self::chunk_2d_noise_fn(noise_fn, chunk_pos, self.surface_scale)
+        };
+        let mut heightmap = sample(self.heightmap_noise.as_ref());
+        let temperature = sample(self.temperature_noise.as_ref());
+        let humidity = sample(self.humidity_noise.as_ref());
+        let variant = sample(self.variant_noise.as_ref());
+        let mut biomes = Vec::with_capacity(CHUNK_SQUARE as usize);
+
+        for (i, height) in heightmap.iter_mut().enumerate() {
+            let blend = self
+                .biome_table
+                .blend(temperature[i], humidity[i], variant[i]);
+            *height = self.biome_table.blend_height(&blend, *height);
+            biomes.push(blend.dominant);
+        }
+
         Chunk2dNoiseValues {
             chunk_pos,
-            heightmap: Self::chunk_2d_noise_fn(self.heightmap_noise.as_ref(), chunk_pos),
-            temperature: Self::chunk_2d_noise_fn(self.temperature_noise.as_ref(), chunk_pos),
-            humidity: Self::chunk_2d_noise_fn(self.humidity_noise.as_ref(), chunk_pos),
+            heightmap,
+            temperature,
+            humidity,
+            variant,
+            biomes,
         }
     }
+}
 
-    pub fn generate_chunk_from_noise(&self, y_level: i32, noise: &Chunk2dNoiseValues) -> Chunk {
+impl WorldGenerator for WorldNoiseSettings {
+    fn column_noise(&self, column: IVec2) -> Option<Chunk2dNoiseValues> {
+        Some(self.generate_chunk_2d_noise(column))
+    }
+
+    fn generate_chunk(&self, pos: ChunkPos, column: Option<&Chunk2dNoiseValues>) -> Chunk {
+        // The chunk world hands over the cached column noise, this is just
+        // in case someone calls it directly
+        let generated;
+        let noise = match column {
+            Some(noise) => noise,
+            None => {
+                generated = self.generate_chunk_2d_noise(pos.0.xz());
+                &generated
+            }
+        };
         let mut chunk = Chunk::default();
         let heightmap = noise.heightmap.as_slice();
+        let biomes = noise.biomes.as_slice();
 
         chunk.definitely_empty = true;
 
+        let chunk_origin = pos.0 * CHUNK_WIDTH as i32;
+        let underground = self.underground.as_ref();
+        // Everything below the floor is left empty
+        let floor_y = underground.settings.floor_y - chunk_origin.y;
+        let bottom = floor_y.clamp(0, CHUNK_WIDTH as i32) as u32;
+        let sea_level = self.sea_level - chunk_origin.y;
+
         for (z, x) in iproduct!(0..CHUNK_WIDTH, 0..CHUNK_WIDTH) {
-            let height_i = heightmap[(z * CHUNK_WIDTH + x) as usize].round() as i32
-                - (y_level * CHUNK_WIDTH as i32);
+            let column = (z * CHUNK_WIDTH + x) as usize;
+            let height_i = heightmap[column].round() as i32 - chunk_origin.y;
             let height_u = (height_i.max(0) as u32).min(CHUNK_WIDTH);
+            let blocks = self.biome_blocks[biomes[column]];
+            let filler_top = height_i - 1;
+            // Grass doesn't grow at the bottom of the sea
+            let surface = match height_i <= sea_level {
+                true => blocks.filler,
+                false => blocks.surface,
+            };
+
+            for y in bottom..height_u {
+                let y_i = y as i32;
+                let world_pos = chunk_origin + IVec3::new(x as i32, y_i, z as i32);
+                let sample_pos = world_pos.as_dvec3().to_array();
+
+                let voxel = if y_i == floor_y {
+                    underground.floor
+                } else if underground.is_cave(sample_pos, filler_top - y_i) {
+                    continue;
+                } else if y_i < filler_top - blocks.filler_depth as i32 {
+                    underground
+                        .ore_at(sample_pos, world_pos.y)
+                        .unwrap_or(self.stone)
+                } else if y_i < filler_top {
+                    blocks.filler
+                } else {
+                    surface
+                };
 
-            for y in 0..height_u {
                 chunk.definitely_empty = false;
                 // Call set on the voxel data rather than the chunk, to prevent the extra check
-                chunk.voxels.set(
-                    InChunkPos::new(UVec3::new(x, y, z)).unwrap(),
-                    match y {
-                        y if (y as i32) < (height_i - 2) => Voxel::Stone,
-                        y if (y as i32) < (height_i - 1) => Voxel::Dirt,
-                        _ => Voxel::Grass,
-                    },
-                );
+                chunk
+                    .voxels
+                    .set(InChunkPos::new(UVec3::new(x, y, z)).unwrap(), voxel);
+            }
+
+            // Fill the gap between the ground and the sea level. Caves are
+            // left dry, otherwise everything below sea level would flood.
+            let water_bottom = height_u.max(bottom);
+            let water_top = sea_level.clamp(0, CHUNK_WIDTH as i32) as u32;
+            for y in water_bottom..water_top {
+                chunk.definitely_empty = false;
+                chunk
+                    .voxels
+                    .set(InChunkPos::new(UVec3::new(x, y, z)).unwrap(), self.water);
             }
         }
 
-        chunk.update_edge_slice_bits();
         chunk
     }
+
+    fn decorate_chunk(
+        &self,
+        chunk: &mut Chunk,
+        pos: ChunkPos,
+        column: Option<&Chunk2dNoiseValues>,
+    ) -> PendingWrites {
+        match column {
+            Some(noise) => self
+                .decorator
+                .decorate(chunk, pos, &noise.heightmap, self.sea_level),
+            None => PendingWrites::default(),
+        }
+    }
+
+    fn open_sky_above(&self, pos: ChunkPos, column: Option<&Chunk2dNoiseValues>) -> bool {
+        let Some(noise) = column else {
+            return false;
+        };
+        // The first voxel above the highest ground in the column
+        let Some(above_ground) = noise
+            .heightmap
+            .iter()
+            .map(|height| height.round() as i32)
+            .max()
+        else {
+            return false;
+        };
+        (pos.0.y + 1) * CHUNK_WIDTH as i32 > above_ground + MAX_DECORATION_HEIGHT
+    }
+
+    fn spawn_height(&self, column: Option<&Chunk2dNoiseValues>) -> Option<i32> {
+        // Middle of the chunk, rounded the same way the terrain is
+        column.map(|noise| noise.heightmap[(CHUNK_SQUARE / 2) as usize].round() as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel::BiomeTableFile;
+    use noise::utils::{NoiseMapBuilder, PlaneMapBuilder};
+
+    fn noise_settings(seed: u32) -> WorldNoiseSettings {
+        let biomes: BiomeTableFile =
+            ron::de::from_str(include_str!("../../assets/data/overworld.biomes.ron")).unwrap();
+        WorldNoiseSettings::new(
+            seed,
+            &WorldGenConfig::original(),
+            BiomeTable::new(biomes.biomes).unwrap(),
+            &VoxelRegistry::builtin(),
+        )
+    }
+
+    #[test]
+    fn spaghetti_caves_fade_out_at_the_surface() {
+        let registry = VoxelRegistry::builtin();
+        let settings = UndergroundSettings {
+            // Only spaghetti caves
+            cheese_threshold: f64::INFINITY,
+            spaghetti_surface_fade: 4,
+            ..default()
+        };
+        let faded = Underground::new(1234, settings.clone(), &registry);
+        let unfaded = Underground::new(
+            1234,
+            UndergroundSettings {
+                spaghetti_surface_fade: 0,
+                ..settings
+            },
+            &registry,
+        );
+
+        let positions = iproduct!(0..64, 0..8, 0..64)
+            .map(|(x, y, z)| [x as f64, y as f64, z as f64])
+            .collect::<Vec<_>>();
+        assert!(positions.iter().any(|pos| unfaded.is_cave(*pos, 0)));
+        for pos in positions {
+            assert!(!faded.is_cave(pos, 0));
+            assert_eq!(faded.is_cave(pos, 4), unfaded.is_cave(pos, 4));
+        }
+    }
+
+    #[test]
+    fn original_scale_matches_old_sampling() {
+        // How chunks were sampled before the surface scale existed: one
+        // unit of noise stretched across each chunk
+        let old_sampling = |noise_fn: &Perlin, chunk_pos: IVec2| {
+            let chunk_pos = chunk_pos.as_dvec2();
+            PlaneMapBuilder::<_, 2>::new(*noise_fn)
+                .set_size(CHUNK_WIDTH as usize, CHUNK_WIDTH as usize)
+                .set_x_bounds(chunk_pos.x, chunk_pos.x + 1.0)
+                .set_y_bounds(chunk_pos.y, chunk_pos.y + 1.0)
+                .build()
+                .into_iter()
+                .collect::<Vec<_>>()
+        };
+
+        let perlin = Perlin::new(7);
+        let scale = WorldGenConfig::original().surface_scale;
+        for chunk_pos in [
+            IVec2::ZERO,
+            IVec2::new(-1, 0),
+            IVec2::new(2, -3),
+            IVec2::splat(-5000),
+        ] {
+            assert_eq!(
+                WorldNoiseSettings::chunk_2d_noise_fn(&perlin, chunk_pos, scale),
+                old_sampling(&perlin, chunk_pos)
+            );
+        }
+    }
+
+    #[test]
+    fn adjacent_chunks_agree_on_border_columns() {
+        let settings = noise_settings(1234);
+        let scale = settings.surface_scale;
+        let width = CHUNK_WIDTH as i32;
+
+        for (a, b) in [
+            (IVec2::new(-1, 0), IVec2::new(0, 0)),
+            (IVec2::new(3, 5), IVec2::new(4, 5)),
+            (IVec2::new(-7, -8), IVec2::new(-7, -7)),
+        ] {
+            let step = b - a;
+            let b_noise = settings.generate_chunk_2d_noise(b);
+            for i in 0..width {
+                // The first column of `b`, and the same column found by
+                // stepping one column past the end of `a`
+                let b_column = match step.x {
+                    0 => IVec2::new(i, 0),
+                    _ => IVec2::new(0, i),
+                };
+                let a_column = b_column + step * width;
+                let pos = WorldNoiseSettings::noise_pos(b, b_column, scale);
+                assert_eq!(WorldNoiseSettings::noise_pos(a, a_column, scale), pos);
+
+                let index = (b_column.y * width + b_column.x) as usize;
+                let height = settings.heightmap_noise.get(pos.to_array());
+                let blend = settings.biome_table.blend(
+                    settings.temperature_noise.get(pos.to_array()),
+                    settings.humidity_noise.get(pos.to_array()),
+                    settings.variant_noise.get(pos.to_array()),
+                );
+                assert_eq!(
+                    settings.biome_table.blend_height(&blend, height),
+                    b_noise.heightmap[index]
+                );
+            }
+        }
+    }
 }
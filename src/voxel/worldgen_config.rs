@@ -0,0 +1,138 @@
+use super::{
+    world_noise::{UndergroundSettings, DEFAULT_SEA_LEVEL},
+    CHUNK_WIDTH,
+};
+use bevy::prelude::*;
+use noise::{Fbm, MultiFractal, Perlin};
+use serde::{Deserialize, Serialize};
+
+/// Every knob for the noise terrain generator. New worlds copy the defaults
+/// from `data/default.worldgen.ron`, and each world saves its own copy so
+/// tweaking the defaults doesn't reshape existing worlds.
+///
+/// Anything missing from a RON file falls back to [`WorldGenConfig::original`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Asset, Resource, TypePath)]
+#[serde(default)]
+pub struct WorldGenConfig {
+    /// Open air below this world Y is filled with water.
+    pub sea_level: i32,
+    /// How many voxels one unit of the heightmap and climate noise covers.
+    /// Their frequencies are per unit, so this stretches the whole surface
+    /// at once.
+    pub surface_scale: f64,
+    pub heightmap: HeightmapConfig,
+    pub temperature: FbmConfig,
+    pub humidity: FbmConfig,
+    /// Picks between biomes that share a temperature/humidity cell.
+    pub variant: FbmConfig,
+    pub underground: UndergroundSettings,
+}
+
+impl WorldGenConfig {
+    /// The terrain as it was before the config existed. Worlds saved back
+    /// then don't have a config, so they get this one. Don't change it, or
+    /// those worlds will grow new mountains right next to the old ones!
+    pub fn original() -> Self {
+        Self {
+            sea_level: DEFAULT_SEA_LEVEL,
+            // Each unit of noise used to be stretched across a whole chunk,
+            // one sample per column
+            surface_scale: CHUNK_WIDTH as f64,
+            heightmap: HeightmapConfig {
+                base_height: 10.0,
+                hills_amplitude: 90.0,
+                hills: FbmConfig {
+                    frequency: 0.02,
+                    persistence: 0.6,
+                    ..default()
+                },
+                ridges_amplitude: 300.0,
+                ridges: FbmConfig {
+                    frequency: 0.015,
+                    octaves: 1,
+                    ..default()
+                },
+                ridges_power: 2.0,
+            },
+            temperature: FbmConfig {
+                frequency: 0.003,
+                octaves: 2,
+                ..default()
+            },
+            humidity: FbmConfig {
+                frequency: 0.014,
+                octaves: 2,
+                ..default()
+            },
+            variant: FbmConfig {
+                frequency: 0.01,
+                octaves: 1,
+                ..default()
+            },
+            underground: UndergroundSettings::default(),
+        }
+    }
+}
+
+impl Default for WorldGenConfig {
+    fn default() -> Self {
+        Self::original()
+    }
+}
+
+/// The shape of the terrain before biomes get their hands on it. It's the
+/// sum of:
+/// - `base_height`
+/// - rolling hills, scaled by `hills_amplitude`
+/// - ridges, made from the minimum of two noise fields raised to
+///   `ridges_power`, scaled by `ridges_amplitude`. Squaring the noise
+///   squashes most of it towards zero, so only the odd spot turns into a
+///   mountain.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HeightmapConfig {
+    pub base_height: f64,
+    pub hills_amplitude: f64,
+    pub hills: FbmConfig,
+    pub ridges_amplitude: f64,
+    pub ridges: FbmConfig,
+    pub ridges_power: f64,
+}
+
+impl Default for HeightmapConfig {
+    fn default() -> Self {
+        WorldGenConfig::original().heightmap
+    }
+}
+
+/// Settings for one layer of fractal Perlin noise. Defaults to the `noise`
+/// crate's own defaults.
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FbmConfig {
+    pub frequency: f64,
+    pub octaves: usize,
+    pub persistence: f64,
+    pub lacunarity: f64,
+}
+
+impl FbmConfig {
+    pub fn build(&self, seed: u32) -> Fbm<Perlin> {
+        Fbm::<Perlin>::new(seed)
+            .set_frequency(self.frequency)
+            .set_octaves(self.octaves)
+            .set_persistence(self.persistence)
+            .set_lacunarity(self.lacunarity)
+    }
+}
+
+impl Default for FbmConfig {
+    fn default() -> Self {
+        Self {
+            frequency: Fbm::<Perlin>::DEFAULT_FREQUENCY,
+            octaves: Fbm::<Perlin>::DEFAULT_OCTAVE_COUNT,
+            persistence: Fbm::<Perlin>::DEFAULT_PERSISTENCE,
+            lacunarity: Fbm::<Perlin>::DEFAULT_LACUNARITY,
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//! Makes sure the same seed keeps generating the same world. Each chunk in
+//! [`CHUNKS`] is generated (and decorated) for every seed in [`SEEDS`], and
+//! the hashes of the voxels are checked against `tests/golden/worldgen.ron`.
+//!
+//! If the terrain is meant to change, regenerate the hashes with:
+//!
+//! ```text
+//! BLESS_WORLDGEN=1 cargo test --test worldgen_golden
+//! ```
+//!
+//! Remember that old worlds will grow seams where new chunks meet saved ones.
+
+use bevy::prelude::*;
+use cjs_whole_new_world::voxel::{
+    generate_chunk_with, world_noise::WorldNoiseSettings, BiomeTable, BiomeTableFile, ChunkPos,
+    InChunkPos, PendingWrites, VoxelRegistry, WorldGenConfig, WorldGenerator, CHUNK_WIDTH,
+};
+use itertools::iproduct;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+const SEEDS: [u32; 3] = [0, 1234, 3_141_592_653];
+
+/// Surface, sky, caves and ores, the bedrock floor, and a few far from the
+/// origin in every direction.
+const CHUNKS: [[i32; 3]; 8] = [
+    [0, 0, 0],
+    [0, -1, 0],
+    [-1, 0, 2],
+    [3, 1, -2],
+    [0, 4, 0],
+    [2, -3, 1],
+    [0, -5, 0],
+    [-40, 0, 57],
+];
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct GoldenChunk {
+    seed: u32,
+    chunk: [i32; 3],
+    /// Hash of every voxel in the chunk.
+    voxels: String,
+    /// Hash of the decorations that spilled into neighboring chunks.
+    decorations: String,
+}
+
+fn golden_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/golden/worldgen.ron")
+}
+
+fn asset(path: &str) -> String {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("assets")
+        .join(path);
+    std::fs::read_to_string(&path)
+        .unwrap_or_else(|err| panic!("failed to read {}: {err}", path.display()))
+}
+
+/// FNV-1a, so the hashes don't depend on anything outside this file.
+#[derive(Copy, Clone)]
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(self) -> String {
+        format!("{:016x}", self.0)
+    }
+}
+
+fn hash_decorations(decorations: PendingWrites) -> String {
+    let mut writes = decorations
+        .into_chunks()
+        .flat_map(|(chunk_pos, writes)| {
+            writes
+                .into_iter()
+                .map(move |write| (chunk_pos.0.to_array(), write.pos.to_array(), write.voxel.0))
+        })
+        .collect::<Vec<_>>();
+    writes.sort();
+
+    let mut hash = Fnv::new();
+    for (chunk_pos, pos, voxel) in writes {
+        for coord in chunk_pos {
+            hash.write(&coord.to_le_bytes());
+        }
+        for coord in pos {
+            hash.write(&coord.to_le_bytes());
+        }
+        hash.write(&voxel.to_le_bytes());
+    }
+    hash.finish()
+}
+
+fn generate_golden() -> Vec<GoldenChunk> {
+    let registry = VoxelRegistry::builtin();
+    let biome_file: BiomeTableFile =
+        ron::de::from_str(&asset("data/overworld.biomes.ron")).unwrap();
+    let biome_table = BiomeTable::new(biome_file.biomes).unwrap();
+    // The original config never changes, unlike the default one new worlds
+    // get, so only generation changes show up here.
+    let config = WorldGenConfig::original();
+
+    let mut golden = vec![];
+    for seed in SEEDS {
+        let generator = WorldNoiseSettings::new(seed, &config, biome_table.clone(), &registry);
+        for chunk in CHUNKS {
+            let pos = ChunkPos(IVec3::from_array(chunk));
+            let column = generator.column_noise(pos.0.xz());
+            let (generated, decorations) =
+                generate_chunk_with(&generator, pos, column.as_ref(), &registry);
+
+            let mut voxels = Fnv::new();
+            for (z, y, x) in iproduct!(0..CHUNK_WIDTH, 0..CHUNK_WIDTH, 0..CHUNK_WIDTH) {
+                let voxel = generated.at(InChunkPos::new(UVec3::new(x, y, z)).unwrap());
+                voxels.write(&voxel.0.to_le_bytes());
+            }
+
+            golden.push(GoldenChunk {
+                seed,
+                chunk,
+                voxels: voxels.finish(),
+                decorations: hash_decorations(decorations),
+            });
+        }
+    }
+    golden
+}
+
+#[test]
+fn worldgen_matches_golden_hashes() {
+    let generated = generate_golden();
+
+    if std::env::var_os("BLESS_WORLDGEN").is_some() {
+        let pretty = ron::ser::PrettyConfig::default().struct_names(true);
+        let ron = ron::ser::to_string_pretty(&generated, pretty).unwrap();
+        std::fs::create_dir_all(golden_path().parent().unwrap()).unwrap();
+        std::fs::write(golden_path(), ron + "\n").unwrap();
+        return;
+    }
+
+    let golden: Vec<GoldenChunk> = match std::fs::read_to_string(golden_path()) {
+        Ok(ron) => ron::de::from_str(&ron).unwrap(),
+        Err(err) => panic!(
+            "failed to read {}: {err}\nrun `BLESS_WORLDGEN=1 cargo test --test worldgen_golden` to create it",
+            golden_path().display()
+        ),
+    };
+
+    let mismatches = generated
+        .iter()
+        .filter(|chunk| !golden.contains(chunk))
+        .map(|chunk| format!("  seed {} chunk {:?}", chunk.seed, chunk.chunk))
+        .collect::<Vec<_>>();
+    assert!(
+        mismatches.is_empty() && golden.len() == generated.len(),
+        "worldgen changed for:\n{}\nif that was on purpose, run `BLESS_WORLDGEN=1 cargo test --test worldgen_golden`",
+        mismatches.join("\n")
+    );
+}